@@ -0,0 +1,275 @@
+//! Datasets that grow as data is appended to them
+
+use std::marker::PhantomData;
+use std::ffi;
+
+#[cfg(feature = "with-mpi")]
+use mpi::traits::*;
+
+#[cfg(not(feature = "with-mpi"))]
+use no_mpi::*;
+
+use hdf5_sys::{
+    h5,
+    h5d,
+    h5i,
+    h5p,
+    h5s,
+};
+
+use crate::{
+    check,
+    GroupHolder,
+    Hdf5Type,
+    OutputError,
+};
+
+/// A one-dimensional dataset of unlimited size, to which data can be
+/// appended in chunks, e.g. to avoid holding all the data in memory
+/// before writing it.
+///
+/// Each call to [append](AppendableDataset::append) must be made by
+/// *all processes* in the communicator, and concatenates the data
+/// from each MPI task in rank order, just as for [Dataset::write](crate::Dataset::write).
+/// The dataset is closed when the handle is dropped.
+///
+/// An appendable dataset with a unit, description, aliases or reduced
+/// precision is created from a [Dataset](crate::Dataset) handle by
+/// [Dataset::appendable](crate::Dataset::appendable).
+pub struct AppendableDataset<'a, T, C> where T: Hdf5Type, C: Communicator {
+    comm: &'a C,
+    id: h5i::hid_t,
+    len: h5::hsize_t,
+    pd: PhantomData<T>,
+}
+
+impl<'a, T, C> AppendableDataset<'a, T, C> where T: Hdf5Type, C: Communicator {
+    /// Creates an empty, extensible dataset inside the specified group (or file),
+    /// which is stored in chunks of `chunk_size` elements. If `single_precision`,
+    /// double-precision data is stored in single precision.
+    pub fn create_in<G>(parent: &'a G, name: ffi::CString, chunk_size: usize, single_precision: bool) -> Result<Self, OutputError> where G: GroupHolder<C> {
+        let id = unsafe {
+            // What type are we writing?
+            let datatype = T::new(); // deallocated at end of scope
+            let type_id = datatype.id();
+
+            // What type is stored in the file? HDF5 converts on write
+            let narrowed = if single_precision { datatype.single_precision() } else { None };
+            let file_type_id = narrowed.as_ref().map_or(type_id, |dt| dt.id());
+
+            // Initially empty, but can be extended without limit
+            let dims = [0];
+            let max_dims = [h5s::H5S_UNLIMITED];
+            let filespace = check!( h5s::H5Screate_simple(
+                1,
+                dims.as_ptr(),
+                max_dims.as_ptr(),
+            ))?;
+
+            // Extensible datasets must be chunked
+            let plist_id = check!(h5p::H5Pcreate(*h5p::H5P_CLS_DATASET_CREATE))?;
+            let chunk = [chunk_size.max(1) as h5::hsize_t];
+            check!(h5p::H5Pset_chunk(plist_id, 1, chunk.as_ptr()))?;
+
+            let id = check!( h5d::H5D_create(
+                parent.id(),
+                name.as_ptr(),
+                file_type_id,
+                filespace,
+                h5p::H5P_DEFAULT,
+                plist_id,
+                h5p::H5P_DEFAULT,
+            ))?;
+
+            check!(h5p::H5Pclose(plist_id))?;
+            check!(h5s::H5Sclose(filespace))?;
+
+            id
+        };
+
+        Ok(Self {
+            comm: parent.comm(),
+            id,
+            len: 0,
+            pd: PhantomData::<T>,
+        })
+    }
+
+    pub(crate) fn id(&self) -> h5i::hid_t {
+        self.id
+    }
+
+    /// Returns the total number of elements written so far
+    pub fn len(&self) -> usize {
+        self.len as usize
+    }
+
+    /// Returns true if nothing has been written to the dataset
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Extends the dataset by the data held by all MPI tasks, written
+    /// in rank order after any data that has already been appended.
+    pub fn append(&mut self, data: &[T]) -> Result<(), OutputError> {
+        let rank = self.comm.rank();
+        let ntasks = self.comm.size();
+
+        let local_count = data.len() as h5::hsize_t;
+        let mut counts = vec![0 as h5::hsize_t; ntasks as usize];
+        self.comm.all_gather_into(&local_count, &mut counts[..]);
+
+        let count: h5::hsize_t = counts.iter().sum();
+        if count == 0 {
+            return Ok(());
+        }
+
+        let local_offset: h5::hsize_t = counts.iter().take(rank as usize).sum();
+
+        unsafe {
+            let datatype = T::new(); // deallocated at end of scope
+            let type_id = datatype.id();
+
+            // Extend the dataset, collectively
+            let dims = [self.len + count];
+            check!(h5d::H5Dset_extent(self.id, dims.as_ptr()))?;
+
+            // Each process defines dataset in memory and writes
+            // to a particular hyperslab
+            let dims = [local_count];
+            let memspace = check!( h5s::H5Screate_simple(
+                1,
+                dims.as_ptr(),
+                std::ptr::null()
+            ))?;
+
+            let filespace = check!(h5d::H5Dget_space(self.id))?;
+            let start = [self.len + local_offset];
+            let count = [local_count];
+            check!( h5s::H5Sselect_hyperslab(
+                filespace,
+                h5s::H5S_SELECT_SET,
+                start.as_ptr(),
+                std::ptr::null(),
+                count.as_ptr(),
+                std::ptr::null(),
+            ))?;
+
+            // Create property list for collective write
+            let plist_id = check!(h5p::H5Pcreate(*h5p::H5P_CLS_DATASET_XFER))?;
+
+            #[cfg(feature = "with-mpi")]
+            check!( h5p::H5Pset_dxpl_mpio(
+                plist_id,
+                h5p::H5FD_mpio_xfer_t::H5FD_MPIO_COLLECTIVE
+            ))?;
+
+            check!( h5d::H5Dwrite(
+                self.id,
+                type_id,
+                memspace,
+                filespace,
+                plist_id,
+                data.as_ptr() as *const ffi::c_void,
+            ))?;
+
+            check!(h5s::H5Sclose(filespace))?;
+            check!(h5s::H5Sclose(memspace))?;
+            check!(h5p::H5Pclose(plist_id))?;
+        }
+
+        self.len += count;
+
+        Ok(())
+    }
+}
+
+impl<'a, T, C> Drop for AppendableDataset<'a, T, C> where T: Hdf5Type, C: Communicator {
+    fn drop(&mut self) {
+        unsafe {
+            h5d::H5Dclose(self.id);
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "with-mpi")))]
+mod tests {
+    use crate::{ParallelFile, ScatteredDataset};
+    use super::*;
+
+    #[test]
+    fn chunked_equals_bulk() {
+        let universe = no_mpi::initialize().unwrap();
+        let world = universe.world();
+
+        let filename = std::env::temp_dir().join("hdf5_writer_appendable_test.h5");
+        let filename = filename.to_str().unwrap();
+        let data: Vec<f64> = (0..1000).map(|i| (i as f64).sqrt()).collect();
+
+        {
+            let file = ParallelFile::create(&world, filename).unwrap();
+            file.new_dataset("bulk").unwrap().write(&data[..]).unwrap();
+
+            let mut ds = file.new_appendable_dataset::<f64>("chunked", 64).unwrap();
+            for chunk in data.chunks(37) {
+                ds.append(chunk).unwrap();
+            }
+            // appending nothing is harmless
+            ds.append(&[]).unwrap();
+            assert_eq!(ds.len(), data.len());
+        }
+
+        let file = ParallelFile::open(&world, filename).unwrap();
+        let ScatteredDataset { data: bulk, dims: bulk_dims } = file.open_dataset("bulk").unwrap().read::<[f64]>().unwrap();
+        let ScatteredDataset { data: chunked, dims: chunked_dims } = file.open_dataset("chunked").unwrap().read::<[f64]>().unwrap();
+        drop(file);
+        std::fs::remove_file(filename).unwrap();
+
+        println!("bulk dims = {:?}, chunked dims = {:?}", bulk_dims, chunked_dims);
+        assert_eq!(bulk_dims, chunked_dims);
+        assert_eq!(bulk, chunked);
+        assert_eq!(chunked, data);
+    }
+
+    #[test]
+    fn appendable_with_metadata() {
+        let universe = no_mpi::initialize().unwrap();
+        let world = universe.world();
+
+        let filename = std::env::temp_dir().join("hdf5_writer_appendable_metadata_test.h5");
+        let filename = filename.to_str().unwrap();
+        let data: Vec<f64> = (0..100).map(|i| 0.5 * (i as f64)).collect();
+
+        {
+            let file = ParallelFile::create(&world, filename).unwrap();
+            let mut ds = file.new_dataset("x").unwrap()
+                .with_unit("m").unwrap()
+                .with_desc("some lengths").unwrap()
+                .with_alias("y").unwrap()
+                .with_single_precision(true)
+                .appendable::<f64>(16)
+                .unwrap();
+            for chunk in data.chunks(30) {
+                ds.append(chunk).unwrap();
+            }
+        }
+
+        let file = ParallelFile::open(&world, filename).unwrap();
+        let ds = file.open_dataset("x").unwrap();
+        let unit = ds.open_attribute("unit").unwrap().read::<String>().unwrap();
+        let desc = ds.open_attribute("desc").unwrap().read::<String>().unwrap();
+        let precision = ds.open_attribute("precision").unwrap().read::<String>().unwrap();
+        let x = ds.read::<[f64]>().unwrap().take();
+        let y = file.open_dataset("y").unwrap().read::<[f64]>().unwrap().take();
+        drop(file);
+        std::fs::remove_file(filename).unwrap();
+
+        println!("unit = {:?}, desc = {:?}, precision = {:?}", unit, desc, precision);
+        assert_eq!(unit, "m");
+        assert_eq!(desc, "some lengths");
+        assert_eq!(precision, "single");
+        // halves are exact in single precision
+        assert_eq!(x, data);
+        assert_eq!(y, data);
+    }
+}
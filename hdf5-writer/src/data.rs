@@ -303,6 +303,10 @@ impl<T> Hdf5Data for [T] where T: Hdf5Type {
             let datatype = T::new(); // deallocated at end of scope
             let type_id = datatype.id();
 
+            // What type is stored in the file? HDF5 converts on write
+            let narrowed = if ds.single_precision() { datatype.single_precision() } else { None };
+            let file_type_id = narrowed.as_ref().map_or(type_id, |dt| dt.id());
+
             // Create dataspace for dataset
             let dims = [count];
             let filespace = check!( h5s::H5Screate_simple(
@@ -315,7 +319,7 @@ impl<T> Hdf5Data for [T] where T: Hdf5Type {
             let dset_id = check!( h5d::H5D_create(
                 parent_id,
                 name.as_ptr(),
-                type_id,
+                file_type_id,
                 filespace,
                 h5p::H5P_DEFAULT,
                 h5p::H5P_DEFAULT,
@@ -409,7 +413,11 @@ impl<T> Hdf5Data for [T] where T: Hdf5Type {
             }
         };
 
-        if !types_are_equal && !types_are_compatible {
+        // was the data stored in single precision?
+        let types_are_narrowed = datatype.single_precision()
+            .map_or(false, |narrow| unsafe { h5t::H5Tequal(ds.type_id(), narrow.id()) > 0 });
+
+        if !types_are_equal && !types_are_compatible && !types_are_narrowed {
             let type_name = std::any::type_name::<T>().to_owned();
             return Err(OutputError::TypeMismatch(type_name));
         }
@@ -480,19 +488,22 @@ impl<T> Hdf5Data for [T] where T: Hdf5Type {
                 std::ptr::null()
             ))?;
 
-            let buffer_len = if types_are_equal {
-                nelems
-            } else {
+            let buffer_len = if types_are_compatible {
                 // dealing with a compatible array type
                 let len = block.last().unwrap();
                 nelems / len
+            } else {
+                nelems
             };
 
+            // if necessary, convert back to double precision
+            let mem_type_id = if types_are_narrowed { target_type_id } else { ds.type_id() };
+
             let mut buffer: Vec<T> = Vec::with_capacity(buffer_len as usize);
 
             check!( h5d::H5Dread(
                 ds.id(),
-                ds.type_id(),
+                mem_type_id,
                 memspace,
                 filespace,
                 h5p::H5P_DEFAULT,
@@ -766,4 +777,77 @@ impl Hdf5Data for str {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(all(test, not(feature = "with-mpi")))]
+mod tests {
+    use crate::{Datatype, ParallelFile};
+    use super::*;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Triple([f64; 3]);
+
+    impl Hdf5Type for Triple {
+        fn new() -> Datatype {
+            Datatype::array::<f64>(3)
+        }
+    }
+
+    #[test]
+    fn single_precision_round_trip() {
+        let universe = no_mpi::initialize().unwrap();
+        let world = universe.world();
+
+        let filename = std::env::temp_dir().join("hdf5_writer_single_precision_test.h5");
+        let filename = filename.to_str().unwrap();
+        let scalars: Vec<f64> = (0..1000).map(|i| (i as f64).sqrt() * std::f64::consts::PI).collect();
+        let triples: Vec<Triple> = scalars.iter().map(|x| Triple([*x, -x.exp().recip(), 1.0e6 * x])).collect();
+        let ids: Vec<u64> = (0..1000).collect();
+
+        {
+            let file = ParallelFile::create(&world, filename).unwrap();
+            file.new_dataset("scalars").unwrap().with_single_precision(true).write(&scalars[..]).unwrap()
+                .new_dataset("triples").unwrap().with_single_precision(true).write(&triples[..]).unwrap()
+                .new_dataset("ids").unwrap().with_single_precision(true).write(&ids[..]).unwrap()
+                .new_dataset("exact").unwrap().write(&scalars[..]).unwrap();
+        }
+
+        let file = ParallelFile::open(&world, filename).unwrap();
+        let ds = file.open_dataset("scalars").unwrap();
+        let precision = ds.open_attribute("precision").unwrap().read::<String>().unwrap();
+        let single = ds.read::<[f64]>().unwrap().take();
+        let ds = file.open_dataset("triples").unwrap();
+        let triple_precision = ds.open_attribute("precision").unwrap().read::<String>().unwrap();
+        let single_triples = ds.read::<[Triple]>().unwrap().take();
+        // integers and unflagged datasets are untouched
+        let ds = file.open_dataset("ids").unwrap();
+        let ids_have_precision = ds.open_attribute("precision").is_ok();
+        let read_ids = ds.read::<[u64]>().unwrap().take();
+        let exact = file.open_dataset("exact").unwrap().read::<[f64]>().unwrap().take();
+        drop(file);
+        std::fs::remove_file(filename).unwrap();
+
+        println!("precision attributes = {:?}, {:?}", precision, triple_precision);
+        assert_eq!(precision, "single");
+        assert_eq!(triple_precision, "single");
+        assert!(!ids_have_precision);
+        assert_eq!(read_ids, ids);
+        assert_eq!(exact, scalars);
+
+        let error = |a: f64, b: f64| if b == 0.0 { a.abs() } else { ((a - b) / b).abs() };
+        let max_error = scalars.iter()
+            .zip(single.iter())
+            .map(|(a, b)| error(*b, *a))
+            .chain(
+                triples.iter()
+                    .zip(single_triples.iter())
+                    .flat_map(|(a, b)| (0..3).map(move |i| error(b.0[i], a.0[i])))
+            )
+            .fold(0.0f64, |acc, e| acc.max(e));
+
+        println!("max relative error = {:.3e}, f32 epsilon = {:.3e}", max_error, std::f32::EPSILON);
+        assert!(max_error > 0.0);
+        assert!(max_error <= std::f32::EPSILON as f64);
+    }
+}
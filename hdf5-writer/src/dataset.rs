@@ -21,9 +21,12 @@ use hdf5_sys::{
 
 use crate::{
     to_c_string,
+    AppendableDataset,
     GroupHolder,
     OutputError,
     data::Hdf5Data,
+    Datatype,
+    Hdf5Type,
     check,
     check_silent,
 };
@@ -36,6 +39,7 @@ pub struct Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
     condition: bool,
     specific_rank: Option<i32>,
     aliases: Vec<ffi::CString>,
+    single_precision: bool,
     pd: PhantomData<C>,
 }
 
@@ -50,6 +54,7 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
             condition: true,
             specific_rank,
             aliases: vec![],
+            single_precision: false,
             pd: PhantomData::<C>,
         }
     }
@@ -63,6 +68,10 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
         self.specific_rank
     }
 
+    pub fn single_precision(&self) -> bool {
+        self.single_precision
+    }
+
     /// Assign a unit to the data output.
     /// This can only fail if the argument cannot be converted
     /// to a C-style string, i.e. it contains NUL bytes.
@@ -104,6 +113,16 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
         self
     }
 
+    /// If `enabled`, double-precision data (or arrays thereof) in a slice
+    /// is stored in the file in single precision, halving its size. The
+    /// conversion is done by HDF5 during the write and the data in memory
+    /// is unchanged. Datasets stored in this way are given the attribute
+    /// `precision = "single"`.
+    pub fn with_single_precision(mut self, enabled: bool) -> Self {
+        self.single_precision = enabled;
+        self
+    }
+
     /// Adds an alternative name for this dataset by creating an appropriate
     /// soft link
     pub fn with_alias(mut self, alias: &str) -> Result<Self, OutputError> {
@@ -112,41 +131,45 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
         Ok(self)
     }
 
-    unsafe fn attach_attribute(dataset: h5i::hid_t, name: &str, value: &ffi::CStr) -> Result<(), OutputError> {
-        // Create dataspace for attribute
-        let space_id = check!(h5s::H5Screate(h5s::H5S_SCALAR))?;
+    /// Creates the dataset as an empty, extensible dataset, stored in chunks of
+    /// `chunk_size` elements, to which data is added by [AppendableDataset::append].
+    /// Its unit, description, aliases and precision are set immediately.
+    /// This must be called by *all processes*: any condition or restriction to
+    /// a specific task is ignored.
+    pub fn appendable<T>(self, chunk_size: usize) -> Result<AppendableDataset<'a, T, C>, OutputError> where T: Hdf5Type {
+        let ds = AppendableDataset::create_in(self.parent, self.name.clone(), chunk_size, self.single_precision)?;
 
-        // Create type
-        let type_id = check!(h5t::H5Tcopy(*h5t::H5T_C_S1) )?;
-        check!(h5t::H5Tset_size(type_id, value.to_bytes().len()))?;
-        check!(h5t::H5Tset_strpad(type_id, h5t::H5T_STR_NULLTERM))?;
-        check!(h5t::H5Tset_cset(type_id, h5t::H5T_CSET_UTF8))?;
+        unsafe {
+            for alias in self.aliases {
+                check!(h5l::H5Lcreate_soft(
+                    self.name.as_ptr(),
+                    self.parent.id(),
+                    alias.as_ptr(),
+                    h5p::H5P_DEFAULT,
+                    h5p::H5P_DEFAULT
+                ))?;
+            }
 
-        // Create the attribute itself
-        let name = to_c_string(name)?;
-        let attr_id = check!( h5a::H5Acreate(
-            dataset,
-            name.as_ptr(),
-            type_id,
-            space_id,
-            h5p::H5P_DEFAULT,
-            h5p::H5P_DEFAULT,
-        ))?;
-
-        // Write the data
-        check!( h5a::H5Awrite(
-            attr_id,
-            type_id,
-            value.as_ptr() as *const ffi::c_void
-        ))?;
+            if let Some(unit) = self.unit {
+                attach_attribute(ds.id(), "unit", unit.as_ref())?;
+            }
 
-        // Close stuff
-        check!(h5a::H5Aclose(attr_id))?;
-        check!(h5s::H5Sclose(space_id))?;
-        check!(h5t::H5Tclose(type_id))?;
+            if let Some(desc) = self.desc {
+                attach_attribute(ds.id(), "desc", desc.as_ref())?;
+            }
 
-        // Otherwise, all good
-        Ok(())
+            if self.single_precision {
+                let type_id = check!(h5d::H5Dget_type(ds.id()))?;
+                let narrowed = Datatype::is_single_precision(type_id);
+                check!(h5t::H5Tclose(type_id))?;
+                if narrowed {
+                    let value = to_c_string("single")?;
+                    attach_attribute(ds.id(), "precision", value.as_ref())?;
+                }
+            }
+        }
+
+        Ok(ds)
     }
 
     /// Writes data (a scalar value `&T`, slice `&[T]` or a string slice `&str`) to current
@@ -178,11 +201,21 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
                 }
 
                 if self.unit.is_some() && dset_id.is_some() {
-                    Self::attach_attribute(dset_id.unwrap(), "unit", self.unit.unwrap().as_ref())?;
+                    attach_attribute(dset_id.unwrap(), "unit", self.unit.unwrap().as_ref())?;
                 }
 
                 if self.desc.is_some() && dset_id.is_some() {
-                    Self::attach_attribute(dset_id.unwrap(), "desc", self.desc.unwrap().as_ref())?;
+                    attach_attribute(dset_id.unwrap(), "desc", self.desc.unwrap().as_ref())?;
+                }
+
+                if self.single_precision && dset_id.is_some() {
+                    let type_id = check!(h5d::H5Dget_type(dset_id.unwrap()))?;
+                    let narrowed = Datatype::is_single_precision(type_id);
+                    check!(h5t::H5Tclose(type_id))?;
+                    if narrowed {
+                        let value = to_c_string("single")?;
+                        attach_attribute(dset_id.unwrap(), "precision", value.as_ref())?;
+                    }
                 }
 
                 if dset_id.is_some() {
@@ -195,6 +228,72 @@ impl<'a, G, C> Dataset<'a, G, C> where G: GroupHolder<C>, C: Communicator {
     }
 }
 
+/// Attaches a scalar string attribute to the HDF5 object (a dataset, group or file) `object`
+pub(crate) unsafe fn attach_attribute(object: h5i::hid_t, name: &str, value: &ffi::CStr) -> Result<(), OutputError> {
+    // Create dataspace for attribute
+    let space_id = check!(h5s::H5Screate(h5s::H5S_SCALAR))?;
+
+    // Create type
+    let type_id = check!(h5t::H5Tcopy(*h5t::H5T_C_S1) )?;
+    check!(h5t::H5Tset_size(type_id, value.to_bytes().len()))?;
+    check!(h5t::H5Tset_strpad(type_id, h5t::H5T_STR_NULLTERM))?;
+    check!(h5t::H5Tset_cset(type_id, h5t::H5T_CSET_UTF8))?;
+
+    // Create the attribute itself
+    let name = to_c_string(name)?;
+    let attr_id = check!( h5a::H5Acreate(
+        object,
+        name.as_ptr(),
+        type_id,
+        space_id,
+        h5p::H5P_DEFAULT,
+        h5p::H5P_DEFAULT,
+    ))?;
+
+    // Write the data
+    check!( h5a::H5Awrite(
+        attr_id,
+        type_id,
+        value.as_ptr() as *const ffi::c_void
+    ))?;
+
+    // Close stuff
+    check!(h5a::H5Aclose(attr_id))?;
+    check!(h5s::H5Sclose(space_id))?;
+    check!(h5t::H5Tclose(type_id))?;
+
+    // Otherwise, all good
+    Ok(())
+}
+
+/// Attaches a scalar attribute, of any type implementing [Hdf5Type], to the
+/// HDF5 object (a dataset, group or file) `object`
+pub(crate) unsafe fn attach_scalar_attribute<T>(object: h5i::hid_t, name: &str, value: &T) -> Result<(), OutputError> where T: Hdf5Type {
+    let space_id = check!(h5s::H5Screate(h5s::H5S_SCALAR))?;
+    let datatype = T::new();
+
+    let name = to_c_string(name)?;
+    let attr_id = check!( h5a::H5Acreate(
+        object,
+        name.as_ptr(),
+        datatype.id(),
+        space_id,
+        h5p::H5P_DEFAULT,
+        h5p::H5P_DEFAULT,
+    ))?;
+
+    check!( h5a::H5Awrite(
+        attr_id,
+        datatype.id(),
+        value as *const T as *const ffi::c_void
+    ))?;
+
+    check!(h5a::H5Aclose(attr_id))?;
+    check!(h5s::H5Sclose(space_id))?;
+
+    Ok(())
+}
+
 pub struct DatasetReader<'a, C> where C: Communicator {
     comm: &'a C,
     id: h5i::hid_t,
@@ -320,11 +419,17 @@ impl<'a, C> DatasetReader<'a, C> where C: Communicator {
 
     /// Opens the attribute of the given name, which is attached to this dataset.
     pub fn open_attribute(&'a self, name: &str) -> Result<Self, OutputError> {
+        Self::open_attribute_of(self.comm, self.id(), name)
+    }
+
+    /// Opens the attribute of the given name, which is attached to the
+    /// HDF5 object (a dataset, group or file) `object`.
+    pub(crate) fn open_attribute_of(comm: &'a C, object: h5i::hid_t, name: &str) -> Result<Self, OutputError> {
         let name = to_c_string(name)?;
 
         let id = unsafe {
             check_silent!( h5a::H5Aopen(
-                self.id(),
+                object,
                 name.as_ptr(),
                 h5p::H5P_DEFAULT,
             ))?
@@ -360,7 +465,7 @@ impl<'a, C> DatasetReader<'a, C> where C: Communicator {
         let dims: Vec<_> = dims.into_iter().map(|n| n as usize).collect();
 
         Ok(Self {
-            comm: self.comm(),
+            comm,
             id,
             type_id,
             dims,
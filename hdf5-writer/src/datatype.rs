@@ -79,6 +79,44 @@ impl Datatype {
         };
         Datatype { builtin: false, id }
     }
+
+    /// If this datatype is a double-precision float, or a one-dimensional
+    /// array of them, returns the equivalent single-precision datatype.
+    pub fn single_precision(&self) -> Option<Self> {
+        let f64_type = <f64 as Hdf5Type>::new();
+        unsafe {
+            let class = h5t::H5Tget_class(self.id);
+            if class == h5t::H5T_FLOAT && h5t::H5Tequal(self.id, f64_type.id()) > 0 {
+                Some(<f32 as Hdf5Type>::new())
+            } else if class == h5t::H5T_ARRAY && h5t::H5Tget_array_ndims(self.id) == 1 {
+                let base = Datatype { builtin: false, id: h5t::H5Tget_super(self.id) };
+                if h5t::H5Tequal(base.id(), f64_type.id()) > 0 {
+                    let mut len = 0;
+                    h5t::H5Tget_array_dims(self.id, &mut len);
+                    Some(Datatype::array::<f32>(len as usize))
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Returns true if the datatype with identifier `id` is a single-precision
+    /// float, or a one-dimensional array of them.
+    pub(crate) unsafe fn is_single_precision(id: h5i::hid_t) -> bool {
+        let f32_type = <f32 as Hdf5Type>::new();
+        let class = h5t::H5Tget_class(id);
+        if class == h5t::H5T_FLOAT {
+            h5t::H5Tequal(id, f32_type.id()) > 0
+        } else if class == h5t::H5T_ARRAY && h5t::H5Tget_array_ndims(id) == 1 {
+            let base = Datatype { builtin: false, id: h5t::H5Tget_super(id) };
+            h5t::H5Tequal(base.id(), f32_type.id()) > 0
+        } else {
+            false
+        }
+    }
 }
 
 impl Drop for Datatype {
@@ -13,7 +13,8 @@ use hdf5_sys::{
 };
 
 use crate::{
-    check, to_c_string, Dataset, DatasetReader, OutputError
+    check, to_c_string, AppendableDataset, Dataset, DatasetReader, Hdf5Type, OutputError,
+    dataset::{attach_attribute, attach_scalar_attribute},
 };
 
 pub trait GroupHolder<C: Communicator>: Sized {
@@ -82,12 +83,42 @@ pub trait GroupHolder<C: Communicator>: Sized {
         Ok(Dataset::create_in(&self, name, self.specific_rank()))
     }
 
+    /// Creates a new, empty dataset within the current group or file, which can be
+    /// extended by appending data to it in pieces. This must be called by *all processes*.
+    fn new_appendable_dataset<'a, T>(&'a self, name: &str, chunk_size: usize) -> Result<AppendableDataset<'a, T, C>, OutputError> where T: Hdf5Type {
+        self.new_dataset(name)?.appendable(chunk_size)
+    }
+
     /// Opens a handle to a dataset within the current group or file.
     /// The name can be a fully qualified path, e.g. '/root/folder/subfolder/...'
     fn open_dataset<'a>(&'a self, name: &str) -> Result<DatasetReader<'a, C>, OutputError> {
         let name = to_c_string(name)?;
         DatasetReader::open_in(self, name)
     }
+
+    /// Attaches a scalar attribute, e.g. an `f64`, to the current group or file.
+    /// This must be called by *all processes*, with the same value.
+    fn write_attribute<T>(&self, name: &str, value: &T) -> Result<&Self, OutputError> where T: Hdf5Type {
+        unsafe {
+            attach_scalar_attribute(self.id(), name, value)?;
+        }
+        Ok(self)
+    }
+
+    /// Attaches a string attribute to the current group or file.
+    /// This must be called by *all processes*, with the same value.
+    fn write_string_attribute(&self, name: &str, value: &str) -> Result<&Self, OutputError> {
+        let value = to_c_string(value)?;
+        unsafe {
+            attach_attribute(self.id(), name, value.as_ref())?;
+        }
+        Ok(self)
+    }
+
+    /// Opens the attribute of the given name, which is attached to the current group or file
+    fn open_attribute<'a>(&'a self, name: &str) -> Result<DatasetReader<'a, C>, OutputError> {
+        DatasetReader::open_attribute_of(self.comm(), self.id(), name)
+    }
 }
 
 pub struct Group<'a, C> where C: Communicator {
@@ -2,6 +2,7 @@
 
 use std::ffi;
 
+mod appendable;
 mod data;
 mod dataset;
 mod datatype;
@@ -9,6 +10,7 @@ mod error;
 mod file;
 mod group;
 
+pub use appendable::*;
 pub use data::ScatteredDataset;
 pub use dataset::*;
 pub use datatype::*;
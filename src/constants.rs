@@ -21,10 +21,27 @@ pub const PROTON_MASS: f64 = 1.672622e-27;
 pub const ELECTRON_MASS_MEV: f64 = 0.510999;
 /// Sauter-Schwinger field, E = m^2 c^3 / (e hbar)
 pub const CRITICAL_FIELD: f64 = 1.323285e18;
+/// Synonym for `CRITICAL_FIELD`, units of V/m
+#[allow(unused)]
+pub const SCHWINGER_FIELD: f64 = CRITICAL_FIELD;
 /// Fine-structure constant
 pub const ALPHA_FINE: f64 = 7.29735257e-3;
 /// Reduced Compton length / speed of light = hbar / (m c^2)
 pub const COMPTON_TIME: f64 = 1.28808867e-21;
 /// Classical electron radius = alpha * Compton length
 #[allow(unused)]
-pub const CLASSICAL_ELECTRON_RADIUS: f64 = 2.817940e-15;
\ No newline at end of file
+pub const CLASSICAL_ELECTRON_RADIUS: f64 = 2.817940e-15;
+
+/// Returns the electric field `e_si` (in V/m) in units of the Schwinger field
+#[allow(unused)]
+pub fn field_in_schwinger_units(e_si: f64) -> f64 {
+    e_si / SCHWINGER_FIELD
+}
+
+/// Returns the quantum parameter χ of a particle with Lorentz factor `gamma`,
+/// where `field_perp` (in V/m) is the magnitude of the component of
+/// E + v × B that is perpendicular to the particle velocity v.
+#[allow(unused)]
+pub fn chi_from_field(gamma: f64, field_perp: f64) -> f64 {
+    gamma * field_in_schwinger_units(field_perp)
+}
@@ -0,0 +1,163 @@
+//! Restriction of the simulation domain to a cylinder
+
+use rand::prelude::*;
+use crate::geometry::{FourVector, ThreeVector, StokesVector};
+use super::{Field, EquationOfMotion, PushScheme, RadiationMode, RadiationEvent, PairCreationEvent};
+
+/// Wraps a field, replacing its natural extent by a cylinder,
+/// coaxial with the laser, of given radius that lies between
+/// `z_min` and `z_max` (inclusive) at all times.
+///
+/// Unlike [ScaledField](super::ScaledField), all the physics of the inner field
+/// is retained: only `contains` is changed. As particles outside the
+/// cylinder are never pushed, they must be initialized inside it;
+/// `ideal_initial_z` is clamped to `[z_min, z_max]` accordingly.
+pub struct CylindricalDomain<F: Field> {
+    inner: F,
+    radius: f64,
+    z_min: f64,
+    z_max: f64,
+}
+
+impl<F: Field> CylindricalDomain<F> {
+    #[allow(unused)]
+    pub fn new(inner: F, radius: f64, z_min: f64, z_max: f64) -> Self {
+        assert!(z_min <= z_max, "Cylindrical domain must have z_min <= z_max (requested {:.3e} to {:.3e}).", z_min, z_max);
+        CylindricalDomain {
+            inner,
+            radius,
+            z_min,
+            z_max,
+        }
+    }
+}
+
+impl<F: Field> Field for CylindricalDomain<F> {
+    fn max_timestep(&self) -> Option<f64> {
+        self.inner.max_timestep()
+    }
+
+    fn contains(&self, r: FourVector) -> bool {
+        r[1].hypot(r[2]) < self.radius && r[3] >= self.z_min && r[3] <= self.z_max
+    }
+
+    fn ideal_initial_z(&self) -> f64 {
+        self.inner.ideal_initial_z().clamp(self.z_min, self.z_max)
+    }
+
+    fn push(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+        self.inner.push(r, u, rqm, dt, eqn)
+    }
+
+    fn push_with_scheme(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion, scheme: PushScheme) -> (FourVector, FourVector, f64, f64) {
+        self.inner.push_with_scheme(r, u, rqm, dt, eqn, scheme)
+    }
+
+    fn power(&self, r: FourVector, u: FourVector, rqm: f64) -> f64 {
+        self.inner.power(r, u, rqm)
+    }
+
+    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode, max_recoil: f64) -> Option<RadiationEvent> {
+        self.inner.radiate(r, u, dt, rng, mode, max_recoil)
+    }
+
+    fn photon_polarization(&self, r: FourVector, u: FourVector, k: FourVector, mode: RadiationMode) -> StokesVector {
+        self.inner.photon_polarization(r, u, k, mode)
+    }
+
+    fn pair_create<R: Rng>(&self, r: FourVector, ell: FourVector, pol: StokesVector, dt: f64, rng: &mut R, rate_increase: f64) -> (f64, StokesVector, Option<PairCreationEvent>) {
+        self.inner.pair_create(r, ell, pol, dt, rng, rate_increase)
+    }
+
+    fn is_cycle_averaged(&self) -> bool {
+        self.inner.is_cycle_averaged()
+    }
+
+    fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+        self.inner.fields(r)
+    }
+
+    fn local_a0(&self, r: FourVector) -> f64 {
+        self.inner.local_a0(r)
+    }
+
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        self.inner.photon_quantum_parameter(r, ell)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        self.inner.carrier_frequency()
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        self.inner.nominal_a0()
+    }
+
+    fn energy(&self) -> (f64, &'static str) {
+        self.inner.energy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::*;
+    use crate::field::{Laser, FastPlaneWave, PlaneWave, Polarization};
+    use super::*;
+
+    #[test]
+    fn cylindrical_domain() {
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let dt = laser.max_timestep().unwrap();
+        let (radius, z_min, z_max) = (5.0e-6, -10.0e-6, 10.0e-6);
+        let field = laser.with_domain_cylinder(radius, z_min, z_max);
+
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+
+        // outside the cylinder, radially and longitudinally
+        for r in [FourVector::new(0.0, 1.1 * radius, 0.0, 0.0), FourVector::new(0.0, 0.0, 0.0, 1.1 * z_max)].iter() {
+            println!("r = [{:.3e}, {:.3e}, {:.3e}]: contained = {}", r[1], r[2], r[3], field.contains(*r));
+            assert!(!field.contains(*r));
+        }
+
+        // inside, the particle is pushed until it leaves through z = z_min
+        let mut r = FourVector::new(0.0, 0.5 * radius, 0.0, 0.0);
+        let mut u = u;
+        let mut steps = 0;
+        assert!(field.contains(r));
+        while field.contains(r) {
+            let (r_new, u_new, _, _) = field.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            r = r_new;
+            u = u_new;
+            steps += 1;
+        }
+
+        let expected = (0.0 - z_min) / (SPEED_OF_LIGHT * dt);
+        println!("took {} steps to leave the cylinder at z = {:.3e}, expected ~{:.0}", steps, r[3], expected);
+        assert!(steps > 0);
+        assert!(r[3] < z_min && r[3] > z_min - 2.0 * SPEED_OF_LIGHT * dt);
+        assert!((steps as f64 - expected).abs() < 0.1 * expected);
+    }
+
+    #[test]
+    fn cylinder_forwards_averaged_laser() {
+        let laser: Laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let z0 = laser.ideal_initial_z();
+        let field = laser.with_domain_cylinder(5.0e-6, -0.5 * z0, 0.5 * z0);
+
+        // particles start inside the domain
+        let z_init = field.ideal_initial_z();
+        println!("ideal_initial_z = {:.3e} (unwrapped {:.3e})", z_init, z0);
+        assert_eq!(z_init, 0.5 * z0);
+        assert!(field.contains(FourVector::new(-z_init, 0.0, 0.0, z_init)));
+
+        // the cycle-averaged power is that of the inner laser
+        let laser: Laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let r = FourVector::new(0.0, 0.0, 0.0, 1.0e-6);
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+        let power = field.power(r, u, rqm);
+        println!("power = {:.3e}, expected {:.3e}", power, laser.power(r, u, rqm));
+        assert!(power != 0.0);
+        assert_eq!(power, laser.power(r, u, rqm));
+    }
+}
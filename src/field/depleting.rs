@@ -0,0 +1,179 @@
+//! Depletion of a field by the energy absorbed by particles
+
+use crate::geometry::{FourVector, ThreeVector};
+use super::{Field, ScaledField};
+
+/// Wraps a field, reducing its amplitude globally as particles absorb
+/// energy from it, so that the total energy of the field and the
+/// particles is conserved.
+///
+/// The absorbed energy must be passed to `deplete` between pushes.
+/// The amplitude is reduced via a [ScaledField], so wrapping a
+/// cycle-averaged field panics.
+pub struct DepletingField<F: Field> {
+    field: ScaledField<F>,
+    initial_energy: f64,
+    depleted: f64,
+}
+
+impl<F: Field> DepletingField<F> {
+    #[allow(unused)]
+    pub fn new(inner: F) -> Self {
+        let (initial_energy, _) = inner.energy();
+        DepletingField {
+            field: ScaledField::new(inner, 1.0),
+            initial_energy,
+            depleted: 0.0,
+        }
+    }
+
+    /// Removes `energy`, in the units of `energy()`, from the field,
+    /// reducing its amplitude accordingly. The amplitude cannot fall below zero.
+    ///
+    /// The energy absorbed by a particle during a push is returned in units
+    /// of the electron rest energy, and must be multiplied by its weight
+    /// and converted before being passed here. If the field is infinitely
+    /// extended, e.g. a plane wave, whose energy is given in J/m^2, the
+    /// absorbed energy must also be divided by the transverse area that
+    /// the particles represent.
+    #[allow(unused)]
+    pub fn deplete(&mut self, energy: f64) {
+        self.depleted = (self.depleted + energy).min(self.initial_energy);
+        let factor = (1.0 - self.depleted / self.initial_energy).sqrt();
+        self.field.set_factor(factor);
+    }
+
+    /// Returns the total energy removed from the field so far
+    #[allow(unused)]
+    pub fn depleted_energy(&self) -> f64 {
+        self.depleted
+    }
+
+    /// Returns the factor by which the field amplitude has been reduced
+    #[allow(unused)]
+    pub fn factor(&self) -> f64 {
+        self.field.factor()
+    }
+}
+
+impl<F: Field> Field for DepletingField<F> {
+    fn max_timestep(&self) -> Option<f64> {
+        self.field.max_timestep()
+    }
+
+    fn contains(&self, r: FourVector) -> bool {
+        self.field.contains(r)
+    }
+
+    fn ideal_initial_z(&self) -> f64 {
+        self.field.ideal_initial_z()
+    }
+
+    fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+        self.field.fields(r)
+    }
+
+    fn local_a0(&self, r: FourVector) -> f64 {
+        self.field.local_a0(r)
+    }
+
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        self.field.photon_quantum_parameter(r, ell)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        self.field.carrier_frequency()
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        self.field.nominal_a0()
+    }
+
+    fn energy(&self) -> (f64, &'static str) {
+        self.field.energy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::*;
+    use crate::field::{EquationOfMotion, FastFocusedLaser, FocusedLaser, Polarization};
+    use super::*;
+
+    /// Pushes a set of electrons, initially at rest near the focal spot,
+    /// through the laser, depleting it after every step by the energy
+    /// absorbed by all electrons of the given `weight`. Returns the final
+    /// momenta, the total absorbed energy in J, and the laser.
+    fn run(weight: f64) -> (Vec<FourVector>, f64, DepletingField<FastFocusedLaser>) {
+        let waist = 4.0e-6;
+        let laser = FastFocusedLaser::new(2.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0);
+        let mut laser = DepletingField::new(laser);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+
+        let mut pt: Vec<(FourVector, FourVector)> = (0..8)
+            .map(|i| {
+                let theta = 2.0 * std::f64::consts::PI * (i as f64) / 8.0;
+                let r = FourVector::new(-z0, 0.5 * waist * theta.cos(), 0.5 * waist * theta.sin(), 0.0);
+                (r, FourVector::new(1.0, 0.0, 0.0, 0.0))
+            })
+            .collect();
+
+        let mut absorbed = 0.0;
+        while pt.iter().any(|(r, _)| laser.contains(*r)) {
+            let mut work = 0.0;
+            for (r, u) in pt.iter_mut().filter(|(r, _)| laser.contains(*r)) {
+                let new = laser.push(*r, *u, rqm, dt, EquationOfMotion::Lorentz);
+                *r = new.0;
+                *u = new.1;
+                work += new.3;
+            }
+            let energy = weight * work * ELECTRON_MASS * SPEED_OF_LIGHT_SQD;
+            absorbed += energy;
+            laser.deplete(energy);
+        }
+
+        (pt.into_iter().map(|(_, u)| u).collect(), absorbed, laser)
+    }
+
+    #[test]
+    fn energy_conservation() {
+        let (initial_energy, _) = FastFocusedLaser::new(2.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Linear, 0.0).energy();
+
+        // heavy beam
+        let weight = 1.0e10;
+        let (u, absorbed, laser) = run(weight);
+        let (final_energy, unit) = laser.energy();
+        assert_eq!(unit, "J");
+
+        // energy lost by the laser vs that actually gained by the electrons
+        let gain: f64 = u.iter().map(|u| weight * (u[0] - 1.0) * ELECTRON_MASS * SPEED_OF_LIGHT_SQD).sum();
+        let error = ((laser.depleted_energy() - gain) / gain).abs();
+        println!(
+            "laser energy: initial = {:.6e} {}, final = {:.6e} {}, depleted = {:.6e} J [absorbed = {:.6e} J], kinetic energy gain = {:.6e} J, factor = {:.6e}, error = {:.3e}",
+            initial_energy, unit, final_energy, unit, laser.depleted_energy(), absorbed, gain, laser.factor(), error,
+        );
+        assert!(gain > 0.0 && laser.factor() < 1.0);
+        assert!(initial_energy - final_energy > 0.0);
+        assert!(error < 0.05);
+
+        // a single electron has negligible effect
+        let (u_depleted, _, laser) = run(1.0);
+        let (u_undepleted, _, _) = run(0.0);
+        let max_error = u_depleted.iter()
+            .zip(u_undepleted.iter())
+            .map(|(a, b)| ((a[0] - b[0]) / b[0]).abs())
+            .fold(0.0, f64::max);
+        println!("weight = 1: factor = 1 - {:.3e}, max. error in final gamma = {:.3e}", 1.0 - laser.factor(), max_error);
+        assert!(1.0 - laser.factor() < 1.0e-9);
+        assert!(max_error < 1.0e-6);
+    }
+
+    #[test]
+    #[should_panic]
+    fn depleting_averaged_laser() {
+        let laser = FocusedLaser::new(2.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Linear, 0.0);
+        let _ = DepletingField::new(laser);
+    }
+}
@@ -1,5 +1,6 @@
 use std::f64::consts;
 use num_complex::Complex;
+use rand::prelude::*;
 
 use crate::field::{Field, Polarization};
 use crate::constants::*;
@@ -17,6 +18,9 @@ pub struct FastFocusedLaser {
     pol: Polarization,
     pol_angle: f64,
     envelope: Envelope,
+    padding: f64,
+    gaussian_n_sigma: Option<f64>,
+    cep: f64,
 }
 
 impl FastFocusedLaser {
@@ -35,6 +39,9 @@ impl FastFocusedLaser {
                 Polarization::Linear => pol_angle,
             },
             envelope: Envelope::Gaussian,
+            padding: 1.0,
+            gaussian_n_sigma: None,
+            cep: 0.0,
         }
     }
 
@@ -44,6 +51,65 @@ impl FastFocusedLaser {
         cpy
     }
 
+    /// Extends the simulation domain, i.e. the region where
+    /// `contains()` is true and the distance `ideal_initial_z()`,
+    /// by the given `factor` (the default is unity).
+    #[allow(unused)]
+    pub fn with_domain_padding(self, factor: f64) -> Self {
+        let mut cpy = self;
+        cpy.padding = factor;
+        cpy
+    }
+
+    /// Truncates the simulation domain at `n_sigma` standard deviations
+    /// of a Gaussian envelope, as for
+    /// [PlaneWave::with_gaussian_truncation](super::PlaneWave::with_gaussian_truncation).
+    #[allow(unused)]
+    pub fn with_gaussian_truncation(self, n_sigma: f64) -> Self {
+        let mut cpy = self;
+        cpy.gaussian_n_sigma = Some(n_sigma);
+        cpy
+    }
+
+    /// Returns the phase at which the domain is truncated, if
+    /// set by [with_gaussian_truncation](Self::with_gaussian_truncation).
+    fn gaussian_extent(&self) -> Option<f64> {
+        // intensity envelope is exp[-phi^2 / (2 sigma^2)]
+        let sigma = consts::PI * self.n_cycles() / (2.0 * consts::LN_2).sqrt();
+        self.gaussian_n_sigma.map(|n| n * sigma)
+    }
+
+    /// Rotates the polarization axis of a linearly polarized laser
+    /// by `psi` (in radians) around the laser propagation axis,
+    /// replacing the angle given at construction.
+    /// Circularly polarized lasers are unaffected.
+    #[allow(unused)]
+    pub fn with_polarization_angle(self, psi: f64) -> Self {
+        let mut cpy = self;
+        cpy.pol_angle = match cpy.pol {
+            Polarization::Circular => 0.0,
+            Polarization::Linear => psi,
+        };
+        cpy
+    }
+
+    /// Sets the carrier-envelope phase (CEP) of the laser, in radians.
+    /// The default is zero.
+    #[allow(unused)]
+    pub fn with_carrier_envelope_phase(self, cep: f64) -> Self {
+        let mut cpy = self;
+        cpy.cep = cep;
+        cpy
+    }
+
+    /// Sets the carrier-envelope phase of the laser to a value drawn
+    /// uniformly from [0, 2π), e.g. to average over shots.
+    #[allow(unused)]
+    pub fn with_random_cep<R: Rng>(self, rng: &mut R) -> Self {
+        let cep = 2.0 * consts::PI * rng.gen::<f64>();
+        self.with_carrier_envelope_phase(cep)
+    }
+
     fn omega(&self) -> f64 {
         SPEED_OF_LIGHT * self.wavevector[0]
     }
@@ -163,18 +229,19 @@ impl Field for FastFocusedLaser {
         let max_phase = match self.envelope {
             Envelope::CosSquared => consts::PI * self.n_cycles(),
             Envelope::Flattop => consts::PI * (self.n_cycles() + 1.0),
-            Envelope::Gaussian => 6.0 * consts::PI * self.n_cycles(), // = 3 omega tau
+            Envelope::Gaussian => self.gaussian_extent().unwrap_or(6.0 * consts::PI * self.n_cycles()), // = 3 omega tau
         };
-        phase < max_phase
+        phase < self.padding * max_phase
     }
 
     fn ideal_initial_z(&self) -> f64 {
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
-        match self.envelope {
+        let z0 = match self.envelope {
             Envelope::CosSquared => 0.5 * wavelength * self.n_cycles(),
             Envelope::Flattop => 0.5 * wavelength * (self.n_cycles() + 1.0),
-            Envelope::Gaussian => 2.0 * wavelength * self.n_cycles(),
-        }
+            Envelope::Gaussian => self.gaussian_extent().map_or(2.0 * wavelength * self.n_cycles(), |phase| 0.5 * phase / self.wavevector[0]),
+        };
+        self.padding * z0
     }
 
     /// Returns a tuple of the electric and magnetic fields E and B
@@ -192,7 +259,7 @@ impl Field for FastFocusedLaser {
         let (f, df_phi) = self.envelope_and_grad(phase);
 
         // field components from A_x
-        let (re_E, im_E, re_B, im_B) = self.beam(r, 0.0);
+        let (re_E, im_E, re_B, im_B) = self.beam(r, self.cep);
         // pulsed E = (f - i f') psi e^(i phi) => Re(pulsed E) = f Re(E) + f' Im(E)
         let (E_x, B_x) = (f * re_E + df_phi * im_E, f * re_B + df_phi * im_B);
 
@@ -204,7 +271,7 @@ impl Field for FastFocusedLaser {
                 // need to swap definitions of x and y, as well as rotating the E, B vectors
                 let r_prime = ThreeVector::from(r).rotate_around(axis, -consts::FRAC_PI_2);
                 let r_prime = FourVector::new(r[0], r_prime[0], r_prime[1], r_prime[2]);
-                let (re_E, im_E, re_B, im_B) = self.beam(r_prime, 0.0);
+                let (re_E, im_E, re_B, im_B) = self.beam(r_prime, self.cep);
                 let (E_y, B_y) = (f * im_E - df_phi * re_E, f * im_B - df_phi * re_B);
                 (E_y.rotate_around(axis, consts::FRAC_PI_2), B_y.rotate_around(axis, consts::FRAC_PI_2))
             }
@@ -217,6 +284,14 @@ impl Field for FastFocusedLaser {
         (E, B, a)
     }
 
+    fn carrier_frequency(&self) -> Option<f64> {
+        Some(self.omega())
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        Some(self.a0)
+    }
+
     fn energy(&self) -> (f64, &'static str) {
         let intensity = {
             let amplitude = (ELECTRON_MASS * SPEED_OF_LIGHT * self.omega() * self.a0) / ELEMENTARY_CHARGE;
@@ -313,4 +388,48 @@ mod tests {
 
         assert!(error < 1.0e-3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn longitudinal_work() {
+        // tightly focused, so E_z is a significant fraction of E_x off axis
+        let wavelength = 0.8e-6;
+        let waist = 1.5 * wavelength;
+        let laser = FastFocusedLaser::new(2.0, wavelength, waist, 4.0, Polarization::Linear, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+
+        // electron initially at rest, off axis in the focal plane
+        let z0 = laser.ideal_initial_z();
+        let mut r = FourVector::new(-z0, 0.5 * waist, 0.0, 0.0);
+        let mut u = FourVector::new(1.0, 0.0, 0.0, 0.0);
+
+        let mut work = 0.0;
+        let mut work_z = 0.0;
+        let (mut max_gain, mut max_work_z, mut max_error) = (0.0_f64, 0.0_f64, 0.0_f64);
+        while laser.contains(r) {
+            let new = laser.push(r, u, rqm, dt, EquationOfMotion::Lorentz);
+            r = new.0;
+            u = new.1;
+            work += new.3;
+
+            // work done by the longitudinal field alone
+            let (E, _, _) = laser.fields(r);
+            work_z += rqm * E[2] * u[3] * dt / (u[0] * SPEED_OF_LIGHT);
+
+            max_gain = max_gain.max(u[0] - 1.0);
+            max_work_z = max_work_z.max(work_z.abs());
+            max_error = max_error.max((work - (u[0] - 1.0)).abs());
+        }
+
+        println!(
+            "max gamma - 1 = {:.4e}, max |work by E_z| = {:.4e}, max |work - (gamma - 1)| = {:.4e}",
+            max_gain, max_work_z, max_error,
+        );
+
+        // absorbed energy tracks the change in energy, which is only possible
+        // if the longitudinal field is included
+        assert!(max_error < 0.01 * max_gain);
+        assert!(max_work_z > 0.01 * max_gain);
+    }
+}
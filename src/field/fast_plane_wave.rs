@@ -1,4 +1,5 @@
 use std::f64::consts;
+use rand::prelude::*;
 
 use crate::field::{Field, Polarization};
 use crate::constants::*;
@@ -16,6 +17,10 @@ pub struct FastPlaneWave {
     pol_angle: f64,
     chirp_b: f64,
     envelope: Envelope,
+    padding: f64,
+    gaussian_n_sigma: Option<f64>,
+    beat: (f64, f64),
+    cep: f64,
 }
 
 impl FastPlaneWave {
@@ -33,15 +38,122 @@ impl FastPlaneWave {
             },
             chirp_b,
             envelope: Envelope::CosSquared,
+            padding: 1.0,
+            gaussian_n_sigma: None,
+            beat: (0.0, 0.0),
+            cep: 0.0,
         }
     }
 
+    /// Constructs a linearly chirped plane wave, with a Gaussian envelope,
+    /// that has the given `duration` (the FWHM of the intensity, in seconds)
+    /// and `bandwidth` (the FWHM of the spectral intensity, as a fraction of
+    /// the central frequency), which are specified independently.
+    ///
+    /// For a Gaussian amplitude envelope exp(-α ϕ^2) and carrier phase
+    /// ϕ (1 + b ϕ), the fractional bandwidth is Δ = Δ_0 [1 + (b / α)^2]^(1/2),
+    /// where Δ_0 = 2 ln2 / (π N) is the transform limit for a pulse
+    /// of N cycles. The chirp parameter is therefore b = α [(Δ / Δ_0)^2 - 1]^(1/2),
+    /// which requires that the bandwidth is not smaller than the transform limit.
+    #[allow(unused)]
+    pub fn with_duration_and_bandwidth(a0: f64, wavelength: f64, duration: f64, bandwidth: f64, pol: Polarization, pol_angle: f64) -> Self {
+        let n_cycles = SPEED_OF_LIGHT * duration / wavelength;
+        let transform_limit = 2.0 * consts::LN_2 / (consts::PI * n_cycles);
+        assert!(
+            bandwidth >= transform_limit,
+            "The requested bandwidth of {:.3e} is smaller than the transform limit of {:.3e} for a {:.3e} s pulse!",
+            bandwidth, transform_limit, duration
+        );
+        let alpha = 0.5 * consts::LN_2 / (consts::PI * n_cycles).powi(2);
+        let chirp_b = alpha * ((bandwidth / transform_limit).powi(2) - 1.0).sqrt();
+        FastPlaneWave::new(a0, wavelength, n_cycles, pol, pol_angle, chirp_b)
+            .with_envelope(Envelope::Gaussian)
+    }
+
     pub fn with_envelope(self, envelope: Envelope) -> Self {
         let mut cpy = self;
         cpy.envelope = envelope;
         cpy
     }
 
+    /// Extends the simulation domain, i.e. the region where
+    /// `contains()` is true and the distance `ideal_initial_z()`,
+    /// by the given `factor` (the default is unity).
+    #[allow(unused)]
+    pub fn with_domain_padding(self, factor: f64) -> Self {
+        let mut cpy = self;
+        cpy.padding = factor;
+        cpy
+    }
+
+    /// Truncates the simulation domain at `n_sigma` standard deviations
+    /// of a Gaussian envelope, as for
+    /// [PlaneWave::with_gaussian_truncation](super::PlaneWave::with_gaussian_truncation).
+    #[allow(unused)]
+    pub fn with_gaussian_truncation(self, n_sigma: f64) -> Self {
+        let mut cpy = self;
+        cpy.gaussian_n_sigma = Some(n_sigma);
+        cpy
+    }
+
+    /// Returns the phase at which the domain is truncated, if
+    /// set by [with_gaussian_truncation](Self::with_gaussian_truncation).
+    fn gaussian_extent(&self) -> Option<f64> {
+        // intensity envelope is exp[-phi^2 / (2 sigma^2)]
+        let sigma = consts::PI * self.n_cycles / (2.0 * consts::LN_2).sqrt();
+        self.gaussian_n_sigma.map(|n| n * sigma)
+    }
+
+    /// Rotates the polarization axis of a linearly polarized laser
+    /// by `psi` (in radians) around the laser propagation axis,
+    /// replacing the angle given at construction.
+    /// Circularly polarized lasers are unaffected.
+    #[allow(unused)]
+    pub fn with_polarization_angle(self, psi: f64) -> Self {
+        let mut cpy = self;
+        cpy.pol_angle = match cpy.pol {
+            Polarization::Circular => 0.0,
+            Polarization::Linear => psi,
+        };
+        cpy
+    }
+
+    /// Sets the carrier-envelope phase (CEP) of the laser, in radians.
+    /// The default is zero.
+    #[allow(unused)]
+    pub fn with_carrier_envelope_phase(self, cep: f64) -> Self {
+        let mut cpy = self;
+        cpy.cep = cep;
+        cpy
+    }
+
+    /// Sets the carrier-envelope phase of the laser to a value drawn
+    /// uniformly from [0, 2π), e.g. to average over shots.
+    #[allow(unused)]
+    pub fn with_random_cep<R: Rng>(self, rng: &mut R) -> Self {
+        let cep = 2.0 * consts::PI * rng.gen::<f64>();
+        self.with_carrier_envelope_phase(cep)
+    }
+
+    /// Modulates the amplitude envelope of the pulse by a factor
+    /// `1 + depth cos(2π beat_freq t)`, where `t` is the time relative
+    /// to the peak of the pulse and `beat_freq` is given in Hz.
+    #[allow(unused)]
+    pub fn with_beat_modulation(self, depth: f64, beat_freq: f64) -> Self {
+        let mut cpy = self;
+        cpy.beat = (depth, beat_freq);
+        cpy
+    }
+
+    /// Returns the beat modulation of the amplitude envelope
+    /// and its derivative with respect to phase
+    fn beat_modulation(&self, phase: f64) -> (f64, f64) {
+        let (depth, beat_freq) = self.beat;
+        let ratio = beat_freq / (SPEED_OF_LIGHT * self.wavevector[0]);
+        let arg = 2.0 * consts::PI * ratio * phase;
+        (1.0 + depth * arg.cos(), -2.0 * consts::PI * ratio * depth * arg.sin())
+    }
+
     #[allow(unused)]
     fn k(&self) -> FourVector {
         self.wavevector
@@ -51,6 +163,134 @@ impl FastPlaneWave {
         SPEED_OF_LIGHT * self.wavevector[0]
     }
 
+    /// Returns the carrier phase ψ, the envelope f (including any beat
+    /// modulation) and their derivatives dψ/dϕ and df/dϕ, at phase ϕ.
+    /// The normalized potential is a0 f(ϕ) {sin ψ, δ cos ψ, 0},
+    /// where δ = 0 for LP and 1 for CP, before rotation by the
+    /// polarization angle.
+    #[inline(always)]
+    fn carrier_and_envelope(&self, phi: f64) -> (f64, f64, f64, f64) {
+        let delta = match self.pol {
+            Polarization::Linear => 0.0f64,
+            Polarization::Circular => 1.0f64,
+        };
+
+        // psi is the (potentially time-dependent) carrier phase
+        let (psi, dpsi_dphi) = if cfg!(feature = "compensating-chirp") && self.envelope == Envelope::CosSquared {
+            let beta = self.chirp_b * 0.5 * (1.0 + delta.powi(2)) * self.a0.powi(2);
+            let f = (phi / (2.0 * self.n_cycles)).cos().powi(2);
+            (
+                phi + (beta / 16.0) * (6.0 * phi + 8.0 * self.n_cycles * (phi / self.n_cycles).sin() + self.n_cycles * (2.0 * phi / self.n_cycles).sin()),
+                1.0 + beta * f * f,
+            )
+        } else {
+            (
+                phi * (1.0 + self.chirp_b * phi),
+                1.0 + 2.0 * self.chirp_b * phi,
+            )
+        };
+
+        let psi = psi + self.cep;
+
+        // envelope and gradient
+        let (f, df_dphi) = match self.envelope {
+            Envelope::CosSquared => {
+                if phi.abs() < self.n_cycles * consts::PI {
+                    (
+                        (phi / (2.0 * self.n_cycles)).cos().powi(2),
+                        -(phi / self.n_cycles).sin() / (2.0 * self.n_cycles)
+                    )
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+
+            Envelope::Flattop => {
+                if phi.abs() > consts::PI * (self.n_cycles + 1.0) {
+                    (0.0, 0.0)
+                } else if phi.abs() > consts::PI * (self.n_cycles - 1.0) {
+                    let arg = 0.25 * (phi.abs() - (self.n_cycles - 1.0) * consts::PI);
+                    (arg.cos().powi(2), -phi.signum() * 0.25 * (2.0 * arg).sin())
+                } else {
+                    (1.0, 0.0)
+                }
+            },
+
+            Envelope::Gaussian => {
+                let arg = -0.5 * (phi / (consts::PI * self.n_cycles)).powi(2);
+                (
+                    arg.exp2(),
+                    -consts::LN_2 * phi * arg.exp2() / (consts::PI * self.n_cycles).powi(2)
+                )
+            }
+        };
+
+        let (m, dm_dphi) = self.beat_modulation(phi);
+        let (f, df_dphi) = (m * f, m * df_dphi + dm_dphi * f);
+
+        (psi, dpsi_dphi, f, df_dphi)
+    }
+
+    /// Returns the normalized vector potential a = e A / (m c) at phase `phi`.
+    fn normalized_potential(&self, phi: f64) -> ThreeVector {
+        let delta = match self.pol {
+            Polarization::Linear => 0.0f64,
+            Polarization::Circular => 1.0f64,
+        };
+        let (psi, _, f, _) = self.carrier_and_envelope(phi);
+        let a = self.a0 * f * ThreeVector::new(psi.sin(), delta * psi.cos(), 0.0);
+        a.rotate_around_z(self.pol_angle)
+    }
+
+    /// Returns the position and normalized momentum of a particle, with
+    /// charge to mass ratio `rqm`, that is at `r0` with momentum `u0`
+    /// before it encounters the laser, at each of the given `phases`,
+    /// neglecting radiation reaction.
+    ///
+    /// In a plane wave, the momentum is known in closed form as a function
+    /// of phase ϕ, given the normalized potential a(ϕ): for Δ = -(q / e) (m_e / m) a(ϕ),
+    ///   u(ϕ) = u0 + Δ + k (2 u0·Δ + Δ^2) / (2 k·u0),
+    /// with the dot products taken between spatial vectors. The position is
+    /// obtained by integrating dr/dϕ = u / (k·u0) by Simpson's rule.
+    /// The phases must be in increasing order and greater than `k·r0`,
+    /// where the field should vanish.
+    #[allow(unused)]
+    pub fn analytic_trajectory(&self, r0: FourVector, u0: FourVector, rqm: f64, phases: &[f64]) -> Vec<(FourVector, FourVector)> {
+        let k = self.wavevector;
+        let k_u0 = k * u0;
+        let charge = rqm * ELECTRON_MASS / ELEMENTARY_CHARGE;
+
+        let momentum = |phi: f64| -> FourVector {
+            let delta = -charge * self.normalized_potential(phi);
+            let u_perp = ThreeVector::new(u0[1], u0[2], 0.0);
+            let c = (2.0 * (u_perp * delta) + delta.norm_sqr()) / (2.0 * k_u0);
+            u0 + delta.with_time(0.0) + c * k
+        };
+
+        // maximum phase interval used for the quadrature
+        let max_dphi = 0.01;
+
+        let mut phi = k * r0;
+        let mut r = r0;
+        let mut trajectory = Vec::with_capacity(phases.len());
+
+        for phase in phases.iter() {
+            let n = 2 * ((phase - phi) / (2.0 * max_dphi)).ceil().max(1.0) as usize;
+            let h = (phase - phi) / (n as f64);
+            let integral = (0..=n)
+                .map(|i| {
+                    let weight = if i == 0 || i == n { 1.0 } else if i % 2 == 1 { 4.0 } else { 2.0 };
+                    weight * momentum(phi + (i as f64) * h)
+                })
+                .fold(FourVector::new(0.0, 0.0, 0.0, 0.0), |acc, u| acc + u);
+            r = r + (h / 3.0) * integral / k_u0;
+            phi = *phase;
+            trajectory.push((r, momentum(phi)));
+        }
+
+        trajectory
+    }
+
     /// Calculates the integrated intensity, i.e. the power per unit area,
     /// by numerically integration.
     fn integrated_intensity(&self, points_per_wavelength: i32) -> f64 {
@@ -94,7 +334,13 @@ impl Field for FastPlaneWave {
         };
         let dt = 1.0 / (SPEED_OF_LIGHT * self.wavevector[0] * chirp);
         let multiplier = (3_f64.sqrt() / (5.0 * ALPHA_FINE * self.a0)).min(0.1);
-        Some(dt * multiplier)
+        let dt = dt * multiplier;
+        let dt = if self.beat.0 != 0.0 {
+            dt.min(0.05 / self.beat.1.abs()) // resolve the beat
+        } else {
+            dt
+        };
+        Some(dt)
     }
 
     fn contains(&self, r: FourVector) -> bool {
@@ -102,18 +348,19 @@ impl Field for FastPlaneWave {
         let max_phase = match self.envelope {
             Envelope::CosSquared => consts::PI * self.n_cycles,
             Envelope::Flattop => consts::PI * (self.n_cycles + 1.0),
-            Envelope::Gaussian => 6.0 * consts::PI * self.n_cycles, // = 3 omega tau
+            Envelope::Gaussian => self.gaussian_extent().unwrap_or(6.0 * consts::PI * self.n_cycles), // = 3 omega tau
         };
-        phase < max_phase
+        phase < self.padding * max_phase
     }
 
     fn ideal_initial_z(&self) -> f64 {
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
-        match self.envelope {
+        let z0 = match self.envelope {
             Envelope::CosSquared => 0.5 * wavelength * self.n_cycles,
             Envelope::Flattop => 0.5 * wavelength * (self.n_cycles + 1.0),
-            Envelope::Gaussian => 2.0 * wavelength * self.n_cycles,
-        }
+            Envelope::Gaussian => self.gaussian_extent().map_or(2.0 * wavelength * self.n_cycles, |phase| 0.5 * phase / self.wavevector[0]),
+        };
+        self.padding * z0
     }
 
     #[allow(non_snake_case)]
@@ -130,54 +377,7 @@ impl Field for FastPlaneWave {
         };
 
         let phi: f64 = self.wavevector * r;
-
-        // psi is the (potentially time-dependent) carrier phase
-        let (psi, dpsi_dphi) = if cfg!(feature = "compensating-chirp") && self.envelope == Envelope::CosSquared {
-            let beta = self.chirp_b * 0.5 * (1.0 + delta.powi(2)) * self.a0.powi(2);
-            let f = (phi / (2.0 * self.n_cycles)).cos().powi(2);
-            (
-                phi + (beta / 16.0) * (6.0 * phi + 8.0 * self.n_cycles * (phi / self.n_cycles).sin() + self.n_cycles * (2.0 * phi / self.n_cycles).sin()),
-                1.0 + beta * f * f,
-            )
-        } else {
-            (
-                phi * (1.0 + self.chirp_b * phi),
-                1.0 + 2.0 * self.chirp_b * phi,
-            )
-        };
-
-        // envelope and gradient
-        let (f, df_dphi) = match self.envelope {
-            Envelope::CosSquared => {
-                if phi.abs() < self.n_cycles * consts::PI {
-                    (
-                        (phi / (2.0 * self.n_cycles)).cos().powi(2),
-                        -(phi / self.n_cycles).sin() / (2.0 * self.n_cycles)
-                    )
-                } else {
-                    (0.0, 0.0)
-                }
-            }
-
-            Envelope::Flattop => {
-                if phi.abs() > consts::PI * (self.n_cycles + 1.0) {
-                    (0.0, 0.0)
-                } else if phi.abs() > consts::PI * (self.n_cycles - 1.0) {
-                    let arg = 0.25 * (phi.abs() - (self.n_cycles - 1.0) * consts::PI);
-                    (arg.cos().powi(2), -phi.signum() * 0.25 * (2.0 * arg).sin())
-                } else {
-                    (1.0, 0.0)
-                }
-            },
-
-            Envelope::Gaussian => {
-                let arg = -0.5 * (phi / (consts::PI * self.n_cycles)).powi(2);
-                (
-                    arg.exp2(),
-                    -consts::LN_2 * phi * arg.exp2() / (consts::PI * self.n_cycles).powi(2)
-                )
-            }
-        };
+        let (psi, dpsi_dphi, f, df_dphi) = self.carrier_and_envelope(phi);
 
         // a = A / (m c a0 / e):
         let dax_dphi = psi.sin() * df_dphi + psi.cos() * dpsi_dphi * f;
@@ -194,9 +394,19 @@ impl Field for FastPlaneWave {
         (E, B, a)
     }
 
+    fn carrier_frequency(&self) -> Option<f64> {
+        Some(self.omega())
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        Some(self.a0)
+    }
+
     fn energy(&self) -> (f64, &'static str) {
-        if self.chirp_b != 0.0 || cfg!(feature = "compensating-chirp") {
+        if self.chirp_b != 0.0 || self.beat.0 != 0.0 || cfg!(feature = "compensating-chirp") {
             let ppw = 1.0 + 2.0 * consts::PI * self.chirp_b * self.n_cycles;
+            // the beat must be resolved as well as the carrier
+            let ppw = ppw.max(2.0 * consts::PI * self.beat.1.abs() / self.omega());
             let ppw = (10.0 * ppw) as i32;
             return (self.integrated_intensity(ppw), "J/m^2");
         }
@@ -239,6 +449,7 @@ impl Field for FastPlaneWave {
 
 #[cfg(test)]
 mod tests {
+    use rand_xoshiro::*;
     use crate::EquationOfMotion;
     use super::*;
 
@@ -348,4 +559,297 @@ mod tests {
 
         assert!(error < 1.0e-3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn frozen_wavelength() {
+        let wavelength = 0.8e-6;
+        let laser = FastPlaneWave::new(10.0, wavelength, 16.0, Polarization::Linear, 0.0, 0.0)
+            .with_envelope(Envelope::Flattop);
+        let t = 0.3 * wavelength / SPEED_OF_LIGHT;
+
+        // locate the nodes of E_x, inside the flat-top region
+        let z_start = -4.0 * wavelength;
+        let dz = wavelength / 1000.0;
+        let mut zeros: Vec<f64> = Vec::new();
+        let mut last = laser.fields_at_fixed_time(ThreeVector::new(0.0, 0.0, z_start), t).0[0];
+        for i in 1..8000 {
+            let z = z_start + (i as f64) * dz;
+            let ex = laser.fields_at_fixed_time(ThreeVector::new(0.0, 0.0, z), t).0[0];
+            if ex.signum() != last.signum() {
+                zeros.push(z - 0.5 * dz);
+            }
+            last = ex;
+        }
+
+        let measured = 2.0 * (zeros.last().unwrap() - zeros[0]) / ((zeros.len() - 1) as f64);
+        let error = (measured - wavelength).abs() / wavelength;
+        println!("found {} nodes, wavelength = {:.6e} m [measured], {:.6e} m [expected] => error = {:.3e}", zeros.len(), measured, wavelength, error);
+        assert!(error < 1.0e-3);
+    }
+
+    #[test]
+    fn work_per_step() {
+        let n_cycles = 4.0;
+        let wavelength = 0.8e-6;
+        let laser = FastPlaneWave::new(10.0, wavelength, n_cycles, Polarization::Linear, 0.0, 0.0);
+        let dt = 0.005 * wavelength / SPEED_OF_LIGHT;
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+
+        let z0 = laser.ideal_initial_z();
+        let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+        let mut u = FourVector::new(0.0, 0.0, 0.0, -100.0).unitize();
+        let gamma0 = u[0];
+
+        let mut work = 0.0;
+        let mut integrated_power = 0.0;
+        let mut max_delta: f64 = 0.0;
+        let mut max_error: f64 = 0.0;
+
+        while laser.contains(r) {
+            let power = laser.power(r, u, rqm);
+            let (r_new, u_new, dt_actual, dwork) = laser.push(r, u, rqm, dt, EquationOfMotion::Lorentz);
+            integrated_power += 0.5 * (power + laser.power(r_new, u_new, rqm)) * dt_actual;
+            work += dwork;
+            r = r_new;
+            u = u_new;
+
+            let delta = u[0] - gamma0;
+            max_delta = max_delta.max(delta.abs());
+            max_error = max_error.max((work - delta).abs()).max((integrated_power - delta).abs());
+        }
+
+        let error = max_error / max_delta;
+        println!("max energy change = {:.6e}, max discrepancy in work = {:.6e} => error = {:.3e}", max_delta, max_error, error);
+        assert!(error < 1.0e-2);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn rotated_polarization() {
+        let wavelength = 0.8e-6;
+
+        for psi in [0.0, 0.3, 1.2, -0.7].iter() {
+            let laser = FastPlaneWave::new(10.0, wavelength, 8.0, Polarization::Linear, 0.0, 0.0)
+                .with_polarization_angle(*psi);
+
+            // find the direction of the largest field over a cycle
+            let E_max = (0..1000)
+                .map(|i| {
+                    let ct = wavelength * (i as f64) / 1000.0;
+                    laser.fields(FourVector::new(ct, 0.0, 0.0, 0.0)).0
+                })
+                .fold(ThreeVector::new(0.0, 0.0, 0.0), |max, E| if E.norm_sqr() > max.norm_sqr() { E } else { max });
+
+            // axis is only defined modulo pi
+            let angle = E_max[1].atan2(E_max[0]);
+            let diff = (angle - psi + 0.5 * consts::PI).rem_euclid(consts::PI) - 0.5 * consts::PI;
+            println!("psi = {:.3}, E_max = [{:.3e}, {:.3e}, {:.3e}], major axis at {:.3}, diff = {:.3e}", psi, E_max[0], E_max[1], E_max[2], angle, diff);
+            assert!(diff.abs() < 1.0e-6);
+            assert!(E_max[2] == 0.0);
+        }
+
+        // circular polarization is unaffected
+        let laser = FastPlaneWave::new(10.0, wavelength, 8.0, Polarization::Circular, 0.0, 0.0);
+        let rotated = FastPlaneWave::new(10.0, wavelength, 8.0, Polarization::Circular, 0.0, 0.0)
+            .with_polarization_angle(0.3);
+        let r = FourVector::new(0.1 * wavelength, 0.0, 0.0, 0.0);
+        let (E, _, _) = laser.fields(r);
+        let (E_rot, _, _) = rotated.fields(r);
+        println!("CP: E = [{:.3e}, {:.3e}], E_rot = [{:.3e}, {:.3e}]", E[0], E[1], E_rot[0], E_rot[1]);
+        assert!(E[0] == E_rot[0] && E[1] == E_rot[1]);
+    }
+
+    #[test]
+    fn random_cep() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let wavelength = 0.8e-6;
+
+        // asymmetry between the largest positive and negative values of E_x,
+        // at fixed position, over a few-cycle pulse
+        let asymmetry = |laser: &FastPlaneWave| -> f64 {
+            let (max, min) = (0..400)
+                .map(|i| {
+                    let ct = 2.0 * wavelength * (-1.0 + (i as f64) / 200.0);
+                    laser.fields(FourVector::new(ct, 0.0, 0.0, 0.0)).0[0]
+                })
+                .fold((f64::NEG_INFINITY, f64::INFINITY), |(max, min), e| (max.max(e), min.min(e)));
+            (max + min) / (max - min)
+        };
+
+        let single = asymmetry(&FastPlaneWave::new(10.0, wavelength, 2.0, Polarization::Linear, 0.0, 0.0));
+
+        let shots = 10_000;
+        let mean = (0..shots)
+            .map(|_| {
+                let laser = FastPlaneWave::new(10.0, wavelength, 2.0, Polarization::Linear, 0.0, 0.0)
+                    .with_random_cep(&mut rng);
+                asymmetry(&laser)
+            })
+            .sum::<f64>() / (shots as f64);
+
+        println!("field asymmetry: single shot (CEP = 0) = {:.3e}, average over {} shots = {:.3e}", single, shots, mean);
+        assert!(single.abs() > 0.1);
+        assert!(mean.abs() < 0.01);
+    }
+
+    #[test]
+    fn gaussian_truncation() {
+        let (a0, wavelength, n_cycles) = (1.0, 0.8e-6, 8.0);
+
+        // start where the field is negligible, at the default position
+        let z0 = FastPlaneWave::new(a0, wavelength, n_cycles, Polarization::Circular, 0.0, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .ideal_initial_z();
+
+        // integral of the transverse momentum along the trajectory,
+        // truncated when the particle leaves the domain
+        let deflection = |n_sigma: f64| {
+            let laser = FastPlaneWave::new(a0, wavelength, n_cycles, Polarization::Circular, 0.0, 0.0)
+                .with_envelope(Envelope::Gaussian)
+                .with_gaussian_truncation(n_sigma);
+            let dt = laser.max_timestep().unwrap();
+            let mut u = FourVector::new(0.0, 0.0, 0.0, -100.0).unitize();
+            let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+            let mut total = 0.0;
+            while laser.contains(r) {
+                let new = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                r = new.0;
+                u = new.1;
+                total += u[1].hypot(u[2]) * new.2;
+            }
+            total
+        };
+
+        // in CP, |u_perp| = a0 f(phi), so compare to the integral of the envelope
+        let sigma = consts::PI * n_cycles / (2.0 * consts::LN_2).sqrt();
+        let envelope = |n_sigma: f64| -> f64 {
+            let n_pts = 10_000;
+            let phi_min = -10.0 * sigma;
+            let dphi = (n_sigma * sigma - phi_min) / (n_pts as f64);
+            (0..n_pts)
+                .map(|i| phi_min + (i as f64 + 0.5) * dphi)
+                .map(|phi| (-0.5 * (phi / (consts::PI * n_cycles)).powi(2)).exp2() * dphi)
+                .sum()
+        };
+
+        let (d3, d4) = (deflection(3.0), deflection(4.0));
+        let ratio = d4 / d3;
+        let expected = envelope(4.0) / envelope(3.0);
+        println!("integrated deflection = {:.6e} (3 sigma), {:.6e} (4 sigma), ratio = {:.6}, expected {:.6}", d3, d4, ratio, expected);
+        assert!(d4 > d3);
+        assert!(ratio < 1.05);
+        assert!((ratio - expected).abs() < 1.0e-3);
+    }
+
+    #[test]
+    fn analytic_trajectory() {
+        let a0 = 2.0;
+        let wavelength = 0.8e-6;
+        let laser = FastPlaneWave::new(a0, wavelength, 8.0, Polarization::Linear, 0.0, 0.0)
+            .with_envelope(Envelope::CosSquared);
+        let dt = 0.05 * laser.max_timestep().unwrap();
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+
+        let z0 = laser.ideal_initial_z();
+        let r0 = FourVector::new(-z0, 0.0, 0.0, z0);
+        let u0 = FourVector::new(0.0, 0.0, 0.0, -10.0).unitize();
+
+        let mut r = r0;
+        let mut u = u0;
+        let mut pushed = vec![];
+        while laser.contains(r) {
+            let new = laser.push(r, u, rqm, dt, EquationOfMotion::Lorentz);
+            r = new.0;
+            u = new.1;
+            pushed.push((r, u));
+        }
+
+        let phases: Vec<f64> = pushed.iter().map(|(r, _)| laser.k() * *r).collect();
+        let analytic = laser.analytic_trajectory(r0, u0, rqm, &phases);
+
+        let mut u_err: f64 = 0.0;
+        let mut r_err: f64 = 0.0;
+        let mut u_perp_max: f64 = 0.0;
+        for ((r, u), (r_an, u_an)) in pushed.iter().zip(analytic.iter()) {
+            let du = ThreeVector::from(*u - *u_an).norm_sqr().sqrt();
+            let dr = ThreeVector::from(*r - *r_an).norm_sqr().sqrt();
+            u_err = u_err.max(du / u_an[0]);
+            r_err = r_err.max(dr / wavelength);
+            u_perp_max = u_perp_max.max(u_an[1].abs());
+        }
+
+        println!("{} steps over {:.1} cycles: max u_perp = {:.3}, max error in u = {:.3e}, in r = {:.3e} wavelengths", pushed.len(), (phases.last().unwrap() - laser.k() * r0) / (2.0 * consts::PI), u_perp_max, u_err, r_err);
+        assert!((u_perp_max - a0).abs() < 1.0e-2 * a0);
+        assert!(u_err < 1.0e-4);
+        assert!(r_err < 1.0e-3);
+    }
+
+    #[test]
+    fn duration_and_bandwidth() {
+        let (wavelength, duration, bandwidth) = (0.8e-6, 30.0e-15, 0.1);
+        let laser = FastPlaneWave::with_duration_and_bandwidth(1.0, wavelength, duration, bandwidth, Polarization::Linear, 0.0);
+
+        // returns the FWHM of the sampled curve (x, y), which must have a single peak
+        let fwhm = |x: &[f64], y: &[f64]| -> f64 {
+            let max = y.iter().cloned().fold(0.0, f64::max);
+            let first = y.iter().position(|&v| v > 0.5 * max).unwrap();
+            let last = y.iter().rposition(|&v| v > 0.5 * max).unwrap();
+            let interp = |i: usize, j: usize| x[i] + (0.5 * max - y[i]) * (x[j] - x[i]) / (y[j] - y[i]);
+            interp(last, last + 1) - interp(first - 1, first)
+        };
+
+        // sample the potential, over about 5 FWHM either side of the peak
+        let phase_max = 10.0 * consts::PI * laser.n_cycles;
+        let dphi = 0.1;
+        let n = (2.0 * phase_max / dphi) as usize;
+        let phases: Vec<f64> = (0..=n).map(|i| -phase_max + dphi * (i as f64)).collect();
+        let intensity: Vec<f64> = phases.iter().map(|&phi| laser.carrier_and_envelope(phi).2.powi(2)).collect();
+        let potential: Vec<f64> = phases.iter().map(|&phi| laser.normalized_potential(phi)[0]).collect();
+
+        // spectral intensity, as a function of the frequency in units of the central frequency
+        let frequencies: Vec<f64> = (0..=2000).map(|i| 0.5 + 0.0005 * (i as f64)).collect();
+        let spectrum: Vec<f64> = frequencies.iter()
+            .map(|&omega| {
+                let (re, im) = phases.iter().zip(potential.iter())
+                    .fold((0.0, 0.0), |(re, im), (phi, a)| (re + a * (omega * phi).cos(), im + a * (omega * phi).sin()));
+                re * re + im * im
+            })
+            .collect();
+
+        let duration_fwhm = fwhm(&phases, &intensity) / laser.omega();
+        let bandwidth_fwhm = fwhm(&frequencies, &spectrum);
+        let transform_limit = 2.0 * consts::LN_2 * wavelength / (consts::PI * SPEED_OF_LIGHT * duration);
+        println!(
+            "chirp b = {:.3e}: duration = {:.3e} s [target {:.3e}], bandwidth = {:.3e} [target {:.3e}, transform limit {:.3e}]",
+            laser.chirp_b, duration_fwhm, duration, bandwidth_fwhm, bandwidth, transform_limit,
+        );
+        assert!(((duration_fwhm - duration) / duration).abs() < 1.0e-3);
+        assert!(((bandwidth_fwhm - bandwidth) / bandwidth).abs() < 1.0e-2);
+    }
+
+    #[test]
+    fn beat_modulated_energy() {
+        use crate::field::PlaneWave;
+        let (a0, wavelength, n_cycles) = (10.0, 0.8e-6, 20.0);
+        let (depth, beat_freq) = (0.5, 0.25 * SPEED_OF_LIGHT / wavelength);
+        let (unmodulated, _) = FastPlaneWave::new(a0, wavelength, n_cycles, Polarization::Linear, 0.0, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .energy();
+
+        // many beats within the envelope, so <(1 + d cos)^2> = 1 + d^2 / 2
+        let target = (1.0 + 0.5 * depth * depth) * unmodulated;
+        let (energy, _) = FastPlaneWave::new(a0, wavelength, n_cycles, Polarization::Linear, 0.0, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .with_beat_modulation(depth, beat_freq)
+            .energy();
+        let (averaged_energy, _) = PlaneWave::new(a0, wavelength, n_cycles, Polarization::Linear, 0.0, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .with_beat_modulation(depth, beat_freq)
+            .energy();
+
+        let error = (energy - target).abs() / target;
+        println!("energy = {:.6e} [fast], {:.6e} [averaged], expected {:.6e}, error = {:.3e}", energy, averaged_energy, target, error);
+        assert!(error < 1.0e-3);
+        assert_eq!(energy, averaged_energy);
+    }
+}
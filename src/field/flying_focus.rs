@@ -0,0 +1,271 @@
+//! A radially polarized laser pulse with a moving focal point
+
+use std::f64::consts;
+use num_complex::Complex;
+
+use crate::field::Field;
+use crate::constants::*;
+use crate::geometry::{FourVector, ThreeVector};
+
+/// Represents a radially polarized, focusing laser pulse, including
+/// the fast oscillating carrier wave, whose focal point moves along
+/// the laser axis at a given velocity (a "flying focus").
+/// This velocity is independent of the group velocity of the pulse
+/// and may be superluminal, subluminal or negative (but greater than -c,
+/// so that particles travelling towards -z cross the focus).
+///
+/// The focal point is at `z = 0` at `t = 0`. The envelope, of length
+/// `c τ`, is centred on the focal point and travels with it, while
+/// the phase fronts of the carrier move at the speed of light.
+pub struct FlyingFocus {
+    a0: f64,
+    waist: f64,
+    duration: f64,
+    wavevector: FourVector,
+    focal_velocity: f64,
+    padding: f64,
+}
+
+impl FlyingFocus {
+    /// Constructs a new flying focus pulse, with peak normalized amplitude
+    /// `a0` (that of the radial electric field at the focus),
+    /// `wavelength`, `waist` and duration equivalent to `n_cycles` wavelengths.
+    /// The focal point moves at `focal_velocity`, in units of the speed of light.
+    #[allow(unused)]
+    pub fn new(a0: f64, wavelength: f64, waist: f64, n_cycles: f64, focal_velocity: f64) -> Self {
+        assert!(focal_velocity > -1.0, "focal velocity must be greater than -c (requested {:.3e} c)", focal_velocity);
+        let duration = n_cycles * wavelength / SPEED_OF_LIGHT;
+        let wavevector = (2.0 * consts::PI / wavelength) * FourVector::new(1.0, 0.0, 0.0, 1.0);
+        FlyingFocus {
+            a0,
+            waist,
+            duration,
+            wavevector,
+            focal_velocity,
+            padding: 1.0,
+        }
+    }
+
+    /// Extends the simulation domain, i.e. the region where
+    /// `contains()` is true and the distance `ideal_initial_z()`,
+    /// by the given `factor` (the default is unity).
+    #[allow(unused)]
+    pub fn with_domain_padding(self, factor: f64) -> Self {
+        let mut cpy = self;
+        cpy.padding = factor;
+        cpy
+    }
+
+    /// Returns the longitudinal position of the focal point at lab time `t`.
+    #[allow(unused)]
+    pub fn focal_position(&self, t: f64) -> f64 {
+        self.focal_velocity * SPEED_OF_LIGHT * t
+    }
+
+    /// Returns the phase that determines the envelope, ω(v_f t - z/c),
+    /// which is zero at the focal point and equal to the carrier
+    /// phase if `v_f = c`.
+    fn envelope_phase(&self, r: FourVector) -> f64 {
+        self.wavevector[0] * (self.focal_velocity * r[0] - r[3])
+    }
+
+    fn omega(&self) -> f64 {
+        SPEED_OF_LIGHT * self.wavevector[0]
+    }
+
+    fn rayleigh_range(&self) -> f64 {
+        0.5 * self.wavevector[0] * self.waist.powi(2)
+    }
+
+    /// Returns the number of wavelengths corresponding to the pulse
+    /// duration
+    #[inline]
+    fn n_cycles(&self) -> f64 {
+        SPEED_OF_LIGHT * self.duration * self.wavevector[0] / (2.0 * consts::PI)
+    }
+
+    /// Returns the Gaussian pulse envelope f(ϕ) and its gradient
+    /// df(ϕ)/dϕ at the given phase ϕ
+    #[inline(always)]
+    fn envelope_and_grad(&self, phase: f64) -> (f64, f64) {
+        let tau = self.omega() * self.duration;
+        let envelope = (-2.0 * consts::LN_2 * phase.powi(2) / tau.powi(2)).exp();
+        (envelope, -4.0 * consts::LN_2 * phase * envelope / tau.powi(2))
+    }
+
+    /// The radial and longitudinal electric fields of the lowest-order
+    /// radially polarized (TM01) beam, to first order in the diffraction
+    /// angle, at four position `r`, in units of the peak field.
+    /// The distance to the focal plane is measured from the focal point
+    /// at the current time.
+    #[allow(non_snake_case)]
+    #[inline(always)]
+    fn beam(&self, r: FourVector) -> (Complex<f64>, Complex<f64>) {
+        let z_f = self.focal_position(r[0] / SPEED_OF_LIGHT);
+        let rho = r[1].hypot(r[2]) / self.waist;
+        let z = (r[3] - z_f) / self.rayleigh_range();
+        let e = self.waist / self.rayleigh_range();
+
+        let i: Complex<f64> = Complex::new(0.0, 1.0);
+        let f: Complex<f64> = i / (z + i);
+        let prefactor = f * f * (-f * rho.powi(2) + i * (self.wavevector * r)).exp();
+
+        // normalized such that |E_r| = 1 at the focus, rho = 1/sqrt(2)
+        let norm = (2.0 * consts::E).sqrt();
+        let E_r = norm * rho * prefactor;
+        let E_z = -i * norm * e * (1.0 - f * rho.powi(2)) * prefactor;
+
+        (E_r, E_z)
+    }
+}
+
+impl Field for FlyingFocus {
+    fn max_timestep(&self) -> Option<f64> {
+        let dt = 1.0 / self.omega();
+        let multiplier = (3_f64.sqrt() / (5.0 * ALPHA_FINE * self.a0)).min(0.1);
+        Some(dt * multiplier)
+    }
+
+    fn contains(&self, r: FourVector) -> bool {
+        let phase = self.envelope_phase(r);
+        let max_phase = 6.0 * consts::PI * self.n_cycles(); // = 3 omega tau
+        phase < self.padding * max_phase
+    }
+
+    fn ideal_initial_z(&self) -> f64 {
+        // a particle at z = z0 at t = -z0/c meets the envelope, which
+        // approaches at v_f, after travelling a distance z0 (1 + v_f) / 2
+        let wavelength = 2.0 * consts::PI / self.wavevector[0];
+        self.padding * 4.0 * wavelength * self.n_cycles() / (1.0 + self.focal_velocity)
+    }
+
+    /// Returns a tuple of the electric and magnetic fields E and B
+    /// at the specified four position.
+    ///
+    /// The fields are those of a paraxial TM01 beam, with the distance
+    /// to the focal plane, and the envelope, evaluated relative to the
+    /// moving focal point. The magnetic field is purely azimuthal.
+    #[allow(non_snake_case)]
+    #[inline(always)]
+    fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+        let (f, df_phi) = self.envelope_and_grad(self.envelope_phase(r));
+        let (E_r, E_z) = self.beam(r);
+
+        // pulsed E = (f - i v_f f') psi e^(i phi) => Re(pulsed E) = f Re(E) + v_f f' Im(E)
+        let df_phi = self.focal_velocity * df_phi;
+        let E_r = f * E_r.re + df_phi * E_r.im;
+        let E_z = f * E_z.re + df_phi * E_z.im;
+
+        let E0 = ELECTRON_MASS * SPEED_OF_LIGHT * self.omega() * self.a0 / ELEMENTARY_CHARGE;
+        let B0 = E0 / SPEED_OF_LIGHT;
+
+        let phi = r[2].atan2(r[1]);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let E = E0 * ThreeVector::new(E_r * cos_phi, E_r * sin_phi, E_z);
+        let B = B0 * ThreeVector::new(-E_r * sin_phi, E_r * cos_phi, 0.0);
+        let a = ELEMENTARY_CHARGE * E.norm_sqr().sqrt() / (ELECTRON_MASS * SPEED_OF_LIGHT * self.omega());
+
+        (E, B, a)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        Some(self.omega())
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        Some(self.a0)
+    }
+
+    /// Returns the energy of the pulse, neglecting corrections of
+    /// order the diffraction angle squared.
+    fn energy(&self) -> (f64, &'static str) {
+        let amplitude = ELECTRON_MASS * SPEED_OF_LIGHT * self.omega() * self.a0 / ELEMENTARY_CHARGE;
+        // cycle-averaged power through any transverse plane
+        let power = 0.25 * consts::E * consts::PI * SPEED_OF_LIGHT * VACUUM_PERMITTIVITY * amplitude.powi(2) * self.waist.powi(2);
+        let duration = self.duration * (consts::PI / (4.0 * consts::LN_2)).sqrt();
+        (power * duration, "J")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn moving_focal_point() {
+        let (wavelength, waist) = (0.8e-6, 5.0e-6);
+        let period = wavelength / SPEED_OF_LIGHT;
+        let z_r = consts::PI * waist.powi(2) / wavelength;
+
+        for beta in [0.5, 1.5, -0.5].iter() {
+            let laser = FlyingFocus::new(10.0, wavelength, waist, 10.0, *beta);
+
+            // location of the peak, cycle-averaged intensity, at the radius
+            // where the radial field is largest
+            let peak = |t: f64| {
+                let z_f = laser.focal_position(t);
+                let (z, _) = (0..=4000)
+                    .map(|i| {
+                        let z = z_f + z_r * (0.001 * (i as f64) - 2.0);
+                        let intensity: f64 = [0.0, 0.25 * period].iter()
+                            .map(|dt| {
+                                let ct = SPEED_OF_LIGHT * (t + dt);
+                                let (E, _, _) = laser.fields(FourVector::new(ct, waist / 2_f64.sqrt(), 0.0, z));
+                                E[0].powi(2)
+                            })
+                            .sum();
+                        (z, intensity)
+                    })
+                    .fold((0.0, 0.0), |(z_max, i_max), (z, i)| if i > i_max { (z, i) } else { (z_max, i_max) });
+                z
+            };
+
+            let t = 10.0 * z_r / (beta.abs() * SPEED_OF_LIGHT);
+            let (z1, z2) = (peak(0.0), peak(t));
+            let velocity = (z2 - z1) / (SPEED_OF_LIGHT * t);
+            println!("beta_f = {:.2}: peak at z = {:.4e} m, then {:.4e} m, velocity = {:.6e} c", beta, z1, z2, velocity);
+            assert!((velocity - beta).abs() < 1.0e-3 * beta.abs());
+        }
+    }
+
+    #[test]
+    fn envelope_follows_focus() {
+        let (wavelength, waist, n_cycles) = (0.8e-6, 5.0e-6, 10.0);
+        let z_r = consts::PI * waist.powi(2) / wavelength;
+        let laser = FlyingFocus::new(10.0, wavelength, waist, n_cycles, 0.5);
+
+        // many Rayleigh ranges downstream, the field is only appreciable
+        // near the focal point, not where a pulse moving at c would be
+        let t = 20.0 * z_r / SPEED_OF_LIGHT;
+        let rho = waist / 2_f64.sqrt();
+        let z_f = laser.focal_position(t);
+        // cycle-averaged, by sampling a quarter-period apart
+        let amplitude = |z: f64| {
+            [0.0, 0.25 * wavelength]
+                .iter()
+                .map(|dct| laser.local_a0(FourVector::new(SPEED_OF_LIGHT * t + dct, rho, 0.0, z)).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        };
+        let a_focus = amplitude(z_f);
+        let a_light = amplitude(SPEED_OF_LIGHT * t);
+        println!("a = {:.3e} at the focal point, {:.3e} at z = ct", a_focus, a_light);
+        assert!(a_light < 1.0e-6 * a_focus);
+        assert!(!laser.contains(FourVector::new(SPEED_OF_LIGHT * t, 0.0, 0.0, -z_f)));
+        assert!(laser.contains(FourVector::new(SPEED_OF_LIGHT * t, 0.0, 0.0, z_f)));
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn as_laser() {
+        use crate::field::Laser;
+        let ff = FlyingFocus::new(10.0, 0.8e-6, 5.0e-6, 10.0, 1.5);
+        let (energy, _) = ff.energy();
+        let r = FourVector::new(0.0, 2.0e-6, 1.0e-6, 0.5e-6);
+        let (E, _, _) = ff.fields(r);
+        let laser: Laser = ff.into();
+        assert_eq!(laser.energy().0, energy);
+        assert_eq!(laser.fields(r).0, E);
+    }
+}
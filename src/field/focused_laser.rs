@@ -1,14 +1,16 @@
 use std::f64::consts;
 use rand::prelude::*;
 use rand_distr::StandardNormal;
+use num_complex::Complex;
 
 use crate::field::{Field, Polarization};
 use crate::{constants::*, PairCreationEvent};
-use crate::geometry::{FourVector, StokesVector};
+use crate::geometry::{FourVector, ThreeVector, StokesVector};
 use crate::nonlinear_compton;
 use crate::pair_creation;
+use crate::special_functions::Hermite;
 
-use super::{RadiationMode, EquationOfMotion, RadiationEvent, Envelope};
+use super::{RadiationMode, EquationOfMotion, PushScheme, RadiationEvent, Envelope};
 
 /// Represents the envelope of a focusing laser pulse, i.e.
 /// the field after cycle averaging
@@ -21,6 +23,10 @@ pub struct FocusedLaser {
     pol_angle: f64,
     bandwidth: f64,
     envelope: Envelope,
+    padding: f64,
+    gaussian_n_sigma: Option<f64>,
+    modes: Vec<(u32, u32, Complex<f64>)>,
+    tilt: (f64, f64),
 }
 
 impl FocusedLaser {
@@ -36,15 +42,167 @@ impl FocusedLaser {
             pol_angle,
             bandwidth: 0.0,
             envelope: Envelope::Gaussian,
+            padding: 1.0,
+            gaussian_n_sigma: None,
+            modes: vec![(0, 0, Complex::new(1.0, 0.0))],
+            tilt: (0.0, 0.0),
         }
     }
 
+    /// Constructs a laser with the given peak, cycle-averaged intensity
+    /// `intensity_w_cm2` (in W/cm^2), rather than a0. The other arguments
+    /// are as for [new](FocusedLaser::new).
+    #[allow(unused)]
+    pub fn from_intensity(intensity_w_cm2: f64, wavelength: f64, waist: f64, n_cycles: f64, pol: Polarization, pol_angle: f64) -> Self {
+        let norm = match pol {
+            Polarization::Linear => 0.5,
+            Polarization::Circular => 1.0,
+        };
+        let omega = 2.0 * consts::PI * SPEED_OF_LIGHT / wavelength;
+        let amplitude = (1.0e4 * intensity_w_cm2 / (norm * SPEED_OF_LIGHT * VACUUM_PERMITTIVITY)).sqrt();
+        let a0 = ELEMENTARY_CHARGE * amplitude / (ELECTRON_MASS * SPEED_OF_LIGHT * omega);
+        FocusedLaser::new(a0, wavelength, waist, n_cycles, pol, pol_angle)
+    }
+
+    /// Constructs a laser focused by optics with the given f-number
+    /// `f_number`, rather than with a specified waist. The waist is that
+    /// of the diffraction-limited spot, `w0 = 2 f_number wavelength / π`,
+    /// so that the far-field divergence is `1 / (2 f_number)`.
+    /// The other arguments are as for [new](FocusedLaser::new).
+    #[allow(unused)]
+    pub fn from_f_number(a0: f64, f_number: f64, wavelength: f64, n_cycles: f64, pol: Polarization, pol_angle: f64) -> Self {
+        let waist = 2.0 * f_number * wavelength / consts::PI;
+        FocusedLaser::new(a0, wavelength, waist, n_cycles, pol, pol_angle)
+    }
+
+    /// Returns the far-field half-angle divergence of the laser, `w0 / z_R`, in radians.
+    #[allow(unused)]
+    pub fn divergence(&self) -> f64 {
+        self.waist / self.rayleigh_range()
+    }
+
+    /// Returns the peak, cycle-averaged intensity of the laser, in W/cm^2.
+    #[allow(unused)]
+    pub fn peak_intensity(&self) -> f64 {
+        let norm = match self.pol {
+            Polarization::Linear => 0.5,
+            Polarization::Circular => 1.0,
+        };
+        let amplitude = ELECTRON_MASS * SPEED_OF_LIGHT * self.omega() * self.a0 / ELEMENTARY_CHARGE;
+        1.0e-4 * norm * SPEED_OF_LIGHT * VACUUM_PERMITTIVITY * amplitude.powi(2)
+    }
+
     pub fn with_envelope(self, envelope: Envelope) -> Self {
         let mut cpy = self;
         cpy.envelope = envelope;
         cpy
     }
 
+    /// Extends the simulation domain, i.e. the region where
+    /// `contains()` is true and the distance `ideal_initial_z()`,
+    /// by the given `factor` (the default is unity).
+    #[allow(unused)]
+    pub fn with_domain_padding(self, factor: f64) -> Self {
+        let mut cpy = self;
+        cpy.padding = factor;
+        cpy
+    }
+
+    /// Truncates the simulation domain at `n_sigma` standard deviations
+    /// of a Gaussian envelope, as for
+    /// [PlaneWave::with_gaussian_truncation](super::PlaneWave::with_gaussian_truncation).
+    #[allow(unused)]
+    pub fn with_gaussian_truncation(self, n_sigma: f64) -> Self {
+        let mut cpy = self;
+        cpy.gaussian_n_sigma = Some(n_sigma);
+        cpy
+    }
+
+    /// Returns the phase at which the domain is truncated, if
+    /// set by [with_gaussian_truncation](Self::with_gaussian_truncation).
+    fn gaussian_extent(&self) -> Option<f64> {
+        // intensity envelope is exp[-phi^2 / (2 sigma^2)]
+        let sigma = consts::PI * self.n_cycles() / (2.0 * consts::LN_2).sqrt();
+        self.gaussian_n_sigma.map(|n| n * sigma)
+    }
+
+    /// Rotates the polarization axis of a linearly polarized laser
+    /// by `psi` (in radians) around the laser propagation axis,
+    /// replacing the angle given at construction.
+    /// Circularly polarized lasers are unaffected.
+    #[allow(unused)]
+    pub fn with_polarization_angle(self, psi: f64) -> Self {
+        let mut cpy = self;
+        cpy.pol_angle = match cpy.pol {
+            Polarization::Circular => 0.0,
+            Polarization::Linear => psi,
+        };
+        cpy
+    }
+
+    /// Makes the transverse profile of the laser that of a Hermite-Gaussian
+    /// TEM_mn mode, normalized to have the same power as the fundamental
+    /// (TEM_00) mode with the same a0. The default is `m = n = 0`.
+    #[allow(unused)]
+    pub fn with_hermite_gaussian(self, m: u32, n: u32) -> Self {
+        let mut cpy = self;
+        cpy.modes = vec![(m, n, Complex::new(1.0, 0.0))];
+        cpy
+    }
+
+    /// Makes the transverse profile of the laser a coherent superposition
+    /// of Hermite-Gaussian TEM_mn modes, given as a list of `(m, n, weight)`.
+    /// Each mode is normalized as for [with_hermite_gaussian](Self::with_hermite_gaussian),
+    /// so a mode with unit weight has the same power as the fundamental mode
+    /// with the same a0, and the complex weights set the relative amplitudes
+    /// and phases at focus. Laguerre-Gaussian modes may be constructed in
+    /// this way, as each is a finite superposition of Hermite-Gaussian
+    /// modes of the same order. Repeated modes are combined.
+    #[allow(unused)]
+    pub fn with_mode_superposition(self, modes: Vec<(u32, u32, Complex<f64>)>) -> Self {
+        assert!(!modes.is_empty(), "A superposition must contain at least one mode!");
+        let mut combined: Vec<(u32, u32, Complex<f64>)> = Vec::with_capacity(modes.len());
+        for (m, n, weight) in modes.into_iter() {
+            match combined.iter_mut().find(|(m0, n0, _)| *m0 == m && *n0 == n) {
+                Some(mode) => mode.2 += weight,
+                None => combined.push((m, n, weight)),
+            }
+        }
+        let mut cpy = self;
+        cpy.modes = combined;
+        cpy
+    }
+
+    /// Tilts the wavefront at focus, such that the axis of the beam
+    /// makes angles `theta_x` and `theta_y` (in radians) to the z axis,
+    /// in the x-z and y-z planes respectively. The focal spot remains at the
+    /// origin and the pulse envelope, which depends on `ct - z`, is unchanged,
+    /// so this differs from rotating the whole laser.
+    /// The angles should be small compared to unity.
+    #[allow(unused)]
+    pub fn with_wavefront_tilt(self, theta_x: f64, theta_y: f64) -> Self {
+        let mut cpy = self;
+        cpy.tilt = (theta_x, theta_y);
+        cpy
+    }
+
+    /// Transforms the four-position `r` to coordinates in which the beam
+    /// axis is aligned with z (see [with_wavefront_tilt](Self::with_wavefront_tilt)).
+    fn untilted(&self, r: FourVector) -> FourVector {
+        let (theta_x, theta_y) = self.tilt;
+        FourVector::new(r[0], r[1] - theta_x * r[3], r[2] - theta_y * r[3], r[3])
+    }
+
+    /// Returns the Gouy phase of the laser, (m + n + 1) arctan(z / z_R),
+    /// at longitudinal position `z`. As the carrier phase is averaged over,
+    /// this does not affect the envelope of a single mode.
+    /// For a superposition, this is the Gouy phase of the first mode.
+    #[allow(unused)]
+    pub fn gouy_phase(&self, z: f64) -> f64 {
+        let (m, n, _) = self.modes[0];
+        ((m + n + 1) as f64) * (z / self.rayleigh_range()).atan()
+    }
+
     /// Returns the number of wavelengths corresponding to the pulse
     /// duration
     #[inline]
@@ -75,6 +233,65 @@ impl FocusedLaser {
         0.5 * self.wavevector[0] * self.waist.powi(2)
     }
 
+    /// Returns the factor by which the intensity of the TEM_mn mode,
+    /// or superposition thereof, differs from that of the Gaussian (TEM_00)
+    /// mode, and its gradient with respect to (x, y, z)
+    fn mode_and_grad(&self, r: FourVector) -> (f64, [f64; 3]) {
+        if let [(0, 0, weight)] = self.modes.as_slice() {
+            return (weight.norm_sqr(), [0.0; 3]);
+        }
+
+        let z_r = self.rayleigh_range();
+        let width_sqd = 1.0 + (r[3] / z_r).powi(2);
+        let width = self.waist * width_sqd.sqrt();
+
+        let x = 2_f64.sqrt() * r[1] / width;
+        let y = 2_f64.sqrt() * r[2] / width;
+
+        // dx/dz = -x z / (z_r^2 w^2), and similarly for y
+        let dlnw_dz = r[3] / (z_r.powi(2) * width_sqd);
+
+        // Gouy phase arctan(z / z_r), and its derivative
+        let gouy = (r[3] / z_r).atan();
+        let dgouy_dz = 1.0 / (z_r * width_sqd);
+
+        let factorial = |k: u32| (1..=k).map(|i| i as f64).product::<f64>();
+
+        // complex amplitude of the superposition, relative to the TEM_00 mode,
+        // and its gradient
+        let mut amp = Complex::new(0.0, 0.0);
+        let mut grad_amp = [Complex::new(0.0, 0.0); 3];
+
+        for &(m, n, weight) in self.modes.iter() {
+            let (h_m, dh_m) = x.hermite_and_deriv(m);
+            let (h_n, dh_n) = y.hermite_and_deriv(n);
+
+            // power normalization, 2^(m+n) m! n!
+            let norm = 2_f64.powi((m + n) as i32) * factorial(m) * factorial(n);
+
+            // higher-order modes acquire an additional Gouy phase (m + n) arctan(z / z_r)
+            let order = (m + n) as f64;
+            let weight = weight * Complex::from_polar(1.0 / norm.sqrt(), -order * gouy);
+
+            amp += weight * h_m * h_n;
+            grad_amp[0] += weight * dh_m * h_n * 2_f64.sqrt() / width;
+            grad_amp[1] += weight * h_m * dh_n * 2_f64.sqrt() / width;
+            grad_amp[2] += weight * Complex::new(
+                -(dh_m * h_n * x + h_m * dh_n * y) * dlnw_dz,
+                -order * h_m * h_n * dgouy_dz,
+            );
+        }
+
+        let mode = amp.norm_sqr();
+        let grad = [
+            2.0 * (amp.conj() * grad_amp[0]).re,
+            2.0 * (amp.conj() * grad_amp[1]).re,
+            2.0 * (amp.conj() * grad_amp[2]).re,
+        ];
+
+        (mode, grad)
+    }
+
     /// Returns the mean-squared pulse envelope ⟨f^2(ϕ)⟩ and its gradient
     /// d⟨f^2(ϕ)⟩/dz at the given phase ϕ
     fn envelope_and_grad(&self, phase: f64) -> (f64, f64) {
@@ -108,6 +325,8 @@ impl FocusedLaser {
     }
 
     pub fn a_sqd(&self, r: FourVector) -> f64 {
+        let r = self.untilted(r);
+
         // Gaussian beam
         let z_r = self.rayleigh_range();
         let width_sqd = 1.0 + (r[3] / z_r).powi(2);
@@ -118,6 +337,10 @@ impl FocusedLaser {
         };
         let beam = norm * self.a0.powi(2) * (-2.0 * rho_sqd / width_sqd).exp() / width_sqd;
 
+        // Higher-order mode
+        let (mode, _) = self.mode_and_grad(r);
+        let beam = beam * mode;
+
         // Pulse envelope
         let phase = self.wavevector * r; // - r[3] * rho_sqd / (z_r * width_sqd);
         let (envelope, _) = self.envelope_and_grad(phase);
@@ -129,6 +352,15 @@ impl FocusedLaser {
     /// potential, i.e. ∇^μ <a^2> = (∂/∂t, -∂/∂x, -∂/∂y, -∂/∂z) <a^2>,
     /// as a function of four-position
     pub fn grad_a_sqd(&self, r: FourVector) -> FourVector {
+        let grad = self.untilted_grad_a_sqd(self.untilted(r));
+        // d/dz acquires a contribution from the transverse gradients
+        let (theta_x, theta_y) = self.tilt;
+        FourVector::new(grad[0], grad[1], grad[2], grad[3] - theta_x * grad[1] - theta_y * grad[2])
+    }
+
+    /// As [grad_a_sqd](Self::grad_a_sqd), but in coordinates where the beam
+    /// axis is aligned with z.
+    fn untilted_grad_a_sqd(&self, r: FourVector) -> FourVector {
         // Gaussian beam
         let z_r = self.rayleigh_range();
         let width_sqd = 1.0 + (r[3] / z_r).powi(2);
@@ -145,6 +377,15 @@ impl FocusedLaser {
             (2.0 * beam * r[3] / (z_r.powi(2) * width_sqd)) * (2.0 * rho_sqd / width_sqd - 1.0)
         ];
 
+        // Higher-order mode
+        let (mode, grad_mode) = self.mode_and_grad(r);
+        let grad_beam = [
+            grad_beam[0] * mode + beam * grad_mode[0],
+            grad_beam[1] * mode + beam * grad_mode[1],
+            grad_beam[2] * mode + beam * grad_mode[2],
+        ];
+        let beam = beam * mode;
+
         // Pulse envelope
         let phase = self.wavevector * r; // - r[3] * rho_sqd / (z_r * width_sqd);
         let (envelope, grad_envelope) = self.envelope_and_grad(phase);
@@ -158,6 +399,46 @@ impl FocusedLaser {
         )
     }
 
+    /// Returns the cycle-averaged ponderomotive force (in N) on an electron
+    /// at four-position `r` with normalized momentum `u`, i.e. the rate of
+    /// change of its momentum with respect to lab time, dp/dt = m c² ∇^μ⟨a²⟩ / (2γ),
+    /// as used by the pusher.
+    #[allow(unused)]
+    pub fn ponderomotive_force(&self, r: FourVector, u: FourVector) -> ThreeVector {
+        // du/dτ = c ∇^μ⟨a²⟩ / 2 and dt = γ dτ
+        let du_dt = 0.5 * SPEED_OF_LIGHT * self.grad_a_sqd(r) / u[0];
+        ELECTRON_MASS * SPEED_OF_LIGHT * ThreeVector::from(du_dt)
+    }
+
+    /// Returns the expected final normalized momentum of an ultrarelativistic
+    /// electron, initially counterpropagating with momentum `u`, which is
+    /// ponderomotively scattered by the laser at impact parameter `b`
+    /// (along x). Assumes that the deflection is small and that the
+    /// collision takes place in the focal plane.
+    #[allow(unused)]
+    pub fn ponderomotive_scattering(&self, u: FourVector, b: f64) -> FourVector {
+        let norm = match self.pol {
+            Polarization::Linear => 0.5,
+            Polarization::Circular => 1.0,
+        };
+
+        // integral of ⟨f^2(ϕ)⟩ over phase
+        let phase_integral = match self.envelope {
+            Envelope::CosSquared => 0.75 * consts::PI * self.n_cycles(),
+            Envelope::Flattop => consts::PI * (2.0 * self.n_cycles() - 0.5),
+            Envelope::Gaussian => self.omega() * self.duration * (consts::PI / (4.0 * consts::LN_2)).sqrt(),
+        };
+
+        // light-front momentum, conserved if the deflection is small
+        let lf = (self.wavevector * u) / self.wavevector[0];
+
+        let u_perp = 2.0 * norm * self.a0.powi(2) * b * (-2.0 * (b / self.waist).powi(2)).exp() * phase_integral
+            / (lf * self.wavevector[0] * self.waist.powi(2));
+
+        let gamma = (lf * lf + 1.0 + u_perp * u_perp) / (2.0 * lf);
+        FourVector::new(gamma, u_perp, 0.0, gamma - lf)
+    }
+
     /// Returns the cycle-averaged radiation reaction force, du/dτ
     #[inline]
     fn landau_lifshitz_force(&self, r: FourVector, u: FourVector) -> FourVector {
@@ -181,6 +462,10 @@ impl FocusedLaser {
 }
 
 impl Field for FocusedLaser {
+    fn is_cycle_averaged(&self) -> bool {
+        true
+    }
+
     fn max_timestep(&self) -> Option<f64> {
         let dt = match self.envelope {
             Envelope::CosSquared | Envelope::Gaussian => 1.0 / self.omega(),
@@ -194,15 +479,21 @@ impl Field for FocusedLaser {
         let max_phase = match self.envelope {
             Envelope::CosSquared => consts::PI * self.n_cycles(),
             Envelope::Flattop => consts::PI * (self.n_cycles() + 1.0),
-            Envelope::Gaussian => 6.0 * consts::PI * self.n_cycles(), // 3.0 * self.omega() * self.duration
+            Envelope::Gaussian => self.gaussian_extent().unwrap_or(6.0 * consts::PI * self.n_cycles()), // 3.0 * self.omega() * self.duration
         };
-        phase < max_phase
+        phase < self.padding * max_phase
     }
 
     /// Advances particle position and momentum using a leapfrog method
     /// in proper time. As a consequence, the change in the time may not
     /// be identical to the requested `dt`.
     fn push(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+        // neutral particles are unaffected by the field
+        if rqm == 0.0 {
+            let r = r + SPEED_OF_LIGHT * u * dt / u[0];
+            return (r, u, dt, 0.0);
+        }
+
         // equations of motion are:
         //   du/dtau = c grad<a^2>(r) / 2 = f(r)
         //   dr/dtau = c u
@@ -257,7 +548,20 @@ impl Field for FocusedLaser {
         (r, u, dt_actual, dwork)
     }
 
-    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode) -> Option<RadiationEvent> {
+    /// The ponderomotive solver is always a leapfrog in proper time,
+    /// so `scheme` is ignored.
+    fn push_with_scheme(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion, _scheme: PushScheme) -> (FourVector, FourVector, f64, f64) {
+        self.push(r, u, rqm, dt, eqn)
+    }
+
+    /// Returns the cycle-averaged rate at which the field does work,
+    /// neglecting radiation reaction.
+    fn power(&self, r: FourVector, u: FourVector, rqm: f64) -> f64 {
+        let scale = (rqm / (ELECTRON_CHARGE / ELECTRON_MASS)).powi(2);
+        0.5 * SPEED_OF_LIGHT * scale * self.grad_a_sqd(r)[0] / u[0]
+    }
+
+    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode, max_recoil: f64) -> Option<RadiationEvent> {
         let a = self.a_sqd(r).sqrt();
         let width = 1.0 + self.bandwidth * rng.sample::<f64,_>(StandardNormal);
         assert!(width > 0.0, "The fractional bandwidth of the pulse, {:.3e}, is large enough that the sampled frequency has fallen below zero!", self.bandwidth);
@@ -267,14 +571,16 @@ impl Field for FocusedLaser {
             let (n, k, pol) = nonlinear_compton::generate(kappa, u, self.pol, self.pol_angle, mode, rng);
             // u' is ignored if recoil is disabled, so we may as well calculate it
             let event = RadiationEvent {
+                r,
                 k,
                 u_prime: u + (n as f64) * kappa - k,
                 pol,
                 a_eff: a,
                 chi: a * (u * kappa),
                 absorption: (n as f64) * kappa[0],
+                recoil_capped: false,
             };
-            Some(event)
+            Some(event.with_recoil_cap(u, max_recoil))
         } else {
             None
         }
@@ -307,24 +613,52 @@ impl Field for FocusedLaser {
 
     fn ideal_initial_z(&self) -> f64 {
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
-        match self.envelope {
+        let z0 = match self.envelope {
             Envelope::CosSquared => 0.5 * wavelength * self.n_cycles(),
             Envelope::Flattop => 0.5 * wavelength * (self.n_cycles() + 1.0),
-            Envelope::Gaussian => 2.0 * wavelength * self.n_cycles(),
-        }
+            Envelope::Gaussian => self.gaussian_extent().map_or(2.0 * wavelength * self.n_cycles(), |phase| 0.5 * phase / self.wavevector[0]),
+        };
+        self.padding * z0
+    }
+
+    fn local_a0(&self, r: FourVector) -> f64 {
+        // a_sqd is the cycle-average, a0^2 / 2 for LP
+        let norm = match self.pol {
+            Polarization::Linear => 2.0,
+            Polarization::Circular => 1.0,
+        };
+        (norm * self.a_sqd(r)).sqrt()
+    }
+
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        let a = self.a_sqd(r).sqrt();
+        let kappa = SPEED_OF_LIGHT * COMPTON_TIME * self.wavevector;
+        a * (ell * kappa)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        Some(self.omega())
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        Some(self.a0)
     }
 
     fn energy(&self) -> (f64, &'static str) {
         use super::FastFocusedLaser;
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
-        FastFocusedLaser::new(self.a0, wavelength, self.waist, self.n_cycles(), self.pol, 0.0)
+        let (energy, unit) = FastFocusedLaser::new(self.a0, wavelength, self.waist, self.n_cycles(), self.pol, 0.0)
             .with_envelope(self.envelope)
-            .energy()
+            .energy();
+        // modes are orthogonal, so their powers add
+        let power: f64 = self.modes.iter().map(|(_, _, weight)| weight.norm_sqr()).sum();
+        (power * energy, unit)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand_xoshiro::*;
     use super::*;
 
     #[test]
@@ -378,4 +712,310 @@ mod tests {
             assert!(error < 5.0e-3);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn emission_peaks_at_focus() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let n_cycles = SPEED_OF_LIGHT * 100.0e-15 / 0.8e-6;
+        let laser = FocusedLaser::new(10.0, 0.8e-6, 2.0e-6, n_cycles, Polarization::Circular, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let z_r = laser.rayleigh_range();
+
+        let mut z_emit: Vec<f64> = Vec::new();
+        for _i in 0..100 {
+            let mut u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+            let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+            while laser.contains(r) {
+                let new = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                r = new.0;
+                u = new.1;
+                if let Some(event) = laser.radiate(r, u, new.2, &mut rng, RadiationMode::Quantum, 1.0) {
+                    z_emit.push(event.r[3]);
+                }
+            }
+        }
+
+        let total = z_emit.len();
+        let near = z_emit.iter().filter(|z| z.abs() < z_r).count();
+        let mean = z_emit.iter().sum::<f64>() / (total as f64);
+        println!("{} emissions, {} within one Rayleigh range of focus, <z> = {:.3e} m (z_R = {:.3e} m)", total, near, mean, z_r);
+        assert!(total > 0);
+        assert!(2 * near > total);
+        assert!(mean.abs() < 0.1 * z_r);
+    }
+
+    #[test]
+    fn domain_padding() {
+        let lambda = 0.8e-6;
+        let n_cycles = SPEED_OF_LIGHT * 30.0e-15 / lambda;
+        let b = 2.0e-6;
+
+        let deflection = |padding: f64| {
+            let laser = FocusedLaser::new(100.0, lambda, 4.0e-6, n_cycles, Polarization::Circular, 0.0)
+                .with_domain_padding(padding);
+            let dt = laser.max_timestep().unwrap();
+            let z0 = laser.ideal_initial_z();
+            let mut u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+            let mut r = FourVector::new(-z0, b, 0.0, z0);
+            while laser.contains(r) {
+                let new = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                r = new.0;
+                u = new.1;
+            }
+            1000.0 * u[1].atan2(-u[3])
+        };
+
+        let truncated = deflection(0.25);
+        let default = deflection(1.0);
+        let padded = deflection(2.0);
+        println!("theta [mrad] = {:.6e} (padding = 0.25), {:.6e} (1.0), {:.6e} (2.0)", truncated, default, padded);
+        assert!(default > truncated);
+        assert!(padded > (1.0 - 1.0e-3) * default);
+    }
+
+    #[test]
+    fn ponderomotive_energy() {
+        let lambda = 0.8e-6;
+        let n_cycles = SPEED_OF_LIGHT * 100.0e-15 / lambda;
+        let laser = FocusedLaser::new(20.0, lambda, 4.0e-6, n_cycles, Polarization::Circular, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let b = 2.0e-6;
+
+        let u0 = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let mut u = u0;
+        let mut r = FourVector::new(-z0, b, 0.0, z0);
+        while laser.contains(r) {
+            let new = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            r = new.0;
+            u = new.1;
+        }
+
+        let expected = laser.ponderomotive_scattering(u0, b);
+        let error = ((u[0] - u0[0]) - (expected[0] - u0[0])).abs() / (expected[0] - u0[0]);
+        println!(
+            "gamma_f - gamma_i = {:.6e} [numerical], {:.6e} [closed form], u_x = {:.6e} [numerical], {:.6e} [closed form], error = {:.3e}",
+            u[0] - u0[0], expected[0] - u0[0], u[1], expected[1], error
+        );
+        assert!(error < 1.0e-2);
+        assert!((u[0] - expected[0]).abs() < 1.0e-2 * expected[0]);
+    }
+
+    #[test]
+    fn hermite_gaussian_nodes() {
+        let waist = 4.0e-6;
+        let count_nodes = |laser: &FocusedLaser, along_x: bool| -> usize {
+            let values: Vec<f64> = (0..2001)
+                .map(|i| {
+                    let s = 3.0 * waist * (-1.0 + (i as f64) / 1000.0);
+                    let r = if along_x {
+                        FourVector::new(0.0, s, 0.123 * waist, 0.0)
+                    } else {
+                        FourVector::new(0.0, 0.123 * waist, s, 0.0)
+                    };
+                    laser.a_sqd(r)
+                })
+                .collect();
+            let max = values.iter().cloned().fold(0.0, f64::max);
+            values.windows(3)
+                .filter(|v| v[1] < v[0] && v[1] < v[2] && v[1] < 1.0e-3 * max)
+                .count()
+        };
+
+        for (m, n) in [(0, 0), (1, 0), (2, 1), (3, 2)].iter() {
+            let laser = FocusedLaser::new(10.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0)
+                .with_hermite_gaussian(*m, *n);
+            let nodes = (count_nodes(&laser, true), count_nodes(&laser, false));
+            println!("TEM_{}{}: {} nodes along x, {} along y", m, n, nodes.0, nodes.1);
+            assert_eq!(nodes, (*m as usize, *n as usize));
+
+            // check gradient by finite differences, away from the focal plane
+            let r = FourVector::new(1.0e-6, 0.7 * waist, -0.4 * waist, 20.0e-6);
+            let grad = laser.grad_a_sqd(r);
+            for i in 1..4 {
+                let mut dr = [0.0; 4];
+                dr[i as usize] = 1.0e-10;
+                let dr: FourVector = dr.into();
+                let deriv = (laser.a_sqd(r + dr) - laser.a_sqd(r - dr)) / 2.0e-10;
+                let error = (grad[i] + deriv).abs() / deriv.abs().max(1.0e-3 * laser.a_sqd(r) / waist);
+                println!("\t d<a^2>/dx_{} = {:.6e} [finite diff. = {:.6e}], error = {:.3e}", i, -grad[i], deriv, error);
+                assert!(error < 1.0e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn intensity_round_trip() {
+        // a0 = 0.855 for a linearly polarized, 1 micron laser at 10^18 W/cm^2
+        let laser = FocusedLaser::from_intensity(1.0e18, 1.0e-6, 4.0e-6, 10.0, Polarization::Linear, 0.0);
+        println!("a0 = {:.6e}, expected 0.855", laser.a0);
+        assert!((laser.a0 - 0.855).abs() < 1.0e-3);
+
+        for pol in [Polarization::Linear, Polarization::Circular].iter() {
+            let intensity = 2.5e21;
+            let laser = FocusedLaser::from_intensity(intensity, 0.8e-6, 2.0e-6, 10.0, *pol, 0.0);
+            let error = (laser.peak_intensity() - intensity).abs() / intensity;
+            println!("a0 = {:.6e}, peak intensity = {:.6e} W/cm^2, error = {:.3e}", laser.a0, laser.peak_intensity(), error);
+            assert!(error < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn f_number_focusing() {
+        let (wavelength, f_number) = (0.8e-6, 10.0);
+        let laser = FocusedLaser::from_f_number(1.0, f_number, wavelength, 100.0, Polarization::Circular, 0.0);
+
+        let expected = 2.0 * f_number * wavelength / consts::PI;
+        println!("w0 = {:.6e} m (expected {:.6e} m), divergence = {:.6e} rad (expected {:.6e})", laser.waist, expected, laser.divergence(), 0.5 / f_number);
+        assert!((laser.waist - expected).abs() < 1.0e-12 * expected);
+        assert!((laser.divergence() - 0.5 / f_number).abs() < 1.0e-12);
+
+        // intensity at the focal plane falls to 1/e^2 of its peak at r = w0,
+        // and the spot size grows as w0 sqrt(1 + (z/zR)^2) ~ theta z in the far field
+        let transverse_intensity = |x: f64, z: f64| laser.a_sqd(FourVector::new(z, x, 0.0, z));
+        let ratio = transverse_intensity(laser.waist, 0.0) / transverse_intensity(0.0, 0.0);
+        println!("I(w0) / I(0) = {:.6e}, expected {:.6e}", ratio, (-2_f64).exp());
+        assert!((ratio - (-2_f64).exp()).abs() < 1.0e-9);
+
+        let z = 5.0 * laser.rayleigh_range();
+        let w = laser.waist * (1.0 + 25_f64).sqrt();
+        let ratio = transverse_intensity(w, z) / transverse_intensity(0.0, z);
+        println!("at z = 5 z_R, w = {:.6e} m (theta z = {:.6e} m), I(w) / I(0) = {:.6e}", w, laser.divergence() * z, ratio);
+        assert!((ratio - (-2_f64).exp()).abs() < 1.0e-9);
+    }
+
+    #[test]
+    fn wavefront_tilt() {
+        let (theta_x, theta_y) = (0.05, -0.02);
+        let laser = FocusedLaser::new(1.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0)
+            .with_wavefront_tilt(theta_x, theta_y);
+        let z_r = laser.rayleigh_range();
+
+        // intensity-weighted centroid in the transverse plane at z, at the
+        // time the peak of the pulse passes through it
+        let centroid = |z: f64| {
+            let w = laser.waist * (1.0 + (z / z_r).powi(2)).sqrt();
+            let (x_c, y_c) = (theta_x * z, theta_y * z);
+            let n = 200;
+            let (mut total, mut x_sum, mut y_sum) = (0.0, 0.0, 0.0);
+            for i in 0..=n {
+                for j in 0..=n {
+                    let x = x_c + 6.0 * w * (2.0 * (i as f64) / (n as f64) - 1.0);
+                    let y = y_c + 6.0 * w * (2.0 * (j as f64) / (n as f64) - 1.0);
+                    let intensity = laser.a_sqd(FourVector::new(z, x, y, z));
+                    total += intensity;
+                    x_sum += intensity * x;
+                    y_sum += intensity * y;
+                }
+            }
+            (x_sum / total, y_sum / total)
+        };
+
+        let (x0, y0) = centroid(0.0);
+        println!("at focus, centroid = ({:.3e}, {:.3e}) m", x0, y0);
+        assert!(x0.abs() < 1.0e-12 && y0.abs() < 1.0e-12);
+
+        let z = 10.0 * z_r;
+        let (x, y) = centroid(z);
+        println!("at z = 10 z_R, centroid direction = ({:.6e}, {:.6e}), expected ({:.6e}, {:.6e})", x / z, y / z, theta_x, theta_y);
+        assert!((x / z - theta_x).abs() < 1.0e-6 * theta_x.abs());
+        assert!((y / z - theta_y).abs() < 1.0e-6 * theta_y.abs());
+
+        // the gradient is consistent with the sheared intensity profile
+        let r = FourVector::new(z, theta_x * z + 0.5 * laser.waist, theta_y * z, z);
+        let h = 1.0e-9;
+        let grad = laser.grad_a_sqd(r);
+        let dz = FourVector::new(0.0, 0.0, 0.0, h);
+        let numerical = (laser.a_sqd(r + dz) - laser.a_sqd(r - dz)) / (2.0 * h);
+        // grad_a_sqd returns (d/dt, -d/dx, -d/dy, -d/dz)
+        let analytical = -grad[3];
+        println!("d<a^2>/dz|_t = {:.6e} [numerical], {:.6e} [analytical]", numerical, analytical);
+        assert!((numerical - analytical).abs() < 1.0e-3 * analytical.abs());
+    }
+
+    #[test]
+    fn single_mode_superposition() {
+        let waist = 4.0e-6;
+        let points = [
+            FourVector::new(0.0, 0.3 * waist, -0.2 * waist, 0.0),
+            FourVector::new(1.0e-6, 0.7 * waist, -0.4 * waist, 20.0e-6),
+            FourVector::new(-5.0e-6, -1.1 * waist, 0.5 * waist, -30.0e-6),
+        ];
+
+        for (m, n) in [(0, 0), (1, 0), (2, 1), (0, 3)].iter() {
+            let pure = FocusedLaser::new(10.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0)
+                .with_hermite_gaussian(*m, *n);
+            // an overall phase has no effect
+            let weight = Complex::from_polar(1.0, 0.7);
+            let superposition = FocusedLaser::new(10.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0)
+                .with_mode_superposition(vec![(*m, *n, weight)]);
+
+            for r in points.iter() {
+                let (a_sqd, expected) = (superposition.a_sqd(*r), pure.a_sqd(*r));
+                println!("TEM_{}{}: <a^2> = {:.6e} [superposition], {:.6e} [pure]", m, n, a_sqd, expected);
+                assert!((a_sqd - expected).abs() < 1.0e-12 * expected);
+                let (grad, expected) = (superposition.grad_a_sqd(*r), pure.grad_a_sqd(*r));
+                for i in 1..4 {
+                    assert!((grad[i] - expected[i]).abs() < 1.0e-9 * expected[i].abs().max(a_sqd / waist));
+                }
+            }
+
+            let (energy, _) = superposition.energy();
+            let (expected, _) = pure.energy();
+            assert!((energy - expected).abs() < 1.0e-12 * expected);
+        }
+
+        // for a genuine superposition, the powers add and the
+        // gradient includes the interference between modes
+        let weights = [Complex::new(0.6, 0.0), Complex::new(0.0, 0.8)];
+        let laser = FocusedLaser::new(10.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0)
+            .with_mode_superposition(vec![(1, 0, weights[0]), (0, 1, weights[1])]);
+        let (energy, _) = laser.energy();
+        let (expected, _) = FocusedLaser::new(10.0, 0.8e-6, waist, 10.0, Polarization::Linear, 0.0).energy();
+        println!("energy = {:.6e} J [superposition], {:.6e} J [TEM_00]", energy, expected);
+        assert!((energy - expected).abs() < 1.0e-12 * expected);
+
+        let r = points[1];
+        let grad = laser.grad_a_sqd(r);
+        for i in 1..4 {
+            let mut dr = [0.0; 4];
+            dr[i as usize] = 1.0e-10;
+            let dr: FourVector = dr.into();
+            let deriv = (laser.a_sqd(r + dr) - laser.a_sqd(r - dr)) / 2.0e-10;
+            let error = (grad[i] + deriv).abs() / deriv.abs().max(1.0e-3 * laser.a_sqd(r) / waist);
+            println!("d<a^2>/dx_{} = {:.6e} [finite diff. = {:.6e}], error = {:.3e}", i, -grad[i], deriv, error);
+            assert!(error < 1.0e-4);
+        }
+    }
+
+    #[test]
+    fn ponderomotive_force_integral() {
+        let lambda = 0.8e-6;
+        let n_cycles = SPEED_OF_LIGHT * 30.0e-15 / lambda;
+        let laser = FocusedLaser::new(50.0, lambda, 4.0e-6, n_cycles, Polarization::Circular, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+
+        let u0 = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let mut u = u0;
+        let mut r = FourVector::new(-z0, 2.0e-6, 0.0, z0);
+        let mut impulse = ThreeVector::new(0.0, 0.0, 0.0);
+        while laser.contains(r) {
+            let (r_new, u_new, dt_actual, _) = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            // trapezoidal rule
+            let force = 0.5 * (laser.ponderomotive_force(r, u) + laser.ponderomotive_force(r_new, u_new));
+            impulse = impulse + force * dt_actual;
+            r = r_new;
+            u = u_new;
+        }
+
+        // change in momentum, in N s
+        let delta_p = ELECTRON_MASS * SPEED_OF_LIGHT * ThreeVector::from(u - u0);
+        let error = (impulse[0] - delta_p[0]).abs() / delta_p[0].abs();
+        println!("integral of F_x dt = {:.6e} N s, change in p_x = {:.6e} N s, error = {:.3e}", impulse[0], delta_p[0], error);
+        assert!(delta_p[0] > 0.0);
+        assert!(error < 1.0e-2);
+        assert!(impulse[1].abs() < 1.0e-6 * impulse[0].abs());
+    }
+}
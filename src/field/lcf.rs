@@ -1,5 +1,8 @@
 //! Default implementations for `push`, `radiate` and `pair_create`,
 //! which assume that the field may be treated as locally constant.
+//!
+//! These are public so that they can be called directly, with
+//! arbitrary fields, e.g. to measure their throughput.
 
 use rand::prelude::*;
 use crate::constants::*;
@@ -7,15 +10,17 @@ use crate::geometry::{FourVector, StokesVector, ThreeVector};
 use crate::lcfa;
 use super::{EquationOfMotion, RadiationMode, RadiationEvent, PairCreationEvent};
 
-/// Returns the position and momentum of a particle with charge-to-mass ratio `rqm`,
-/// which has been accelerated in an electric field `E` and magnetic field `B`
-/// over a time interval `dt`.
+/// Returns the position and momentum of a particle with charge-to-mass ratio `rqm`
+/// (in C/kg), which has been accelerated in an electric field `E` (in V/m) and
+/// magnetic field `B` (in T) over a time interval `dt` (in s), as well as
+/// the change in lab time and the work done by the field (in units of the
+/// electron rest energy).
 /// Assumes that ui is defined at t = 0, and r, E, B are defined at t = dt/2.
-/// If `with_rr` is true, the energy loss due to radiation emission is handled
+/// Depending on `eqn`, the energy loss due to radiation emission is handled
 /// as part of the particle push, following the classical LL prescription.
 #[allow(non_snake_case)]
 #[inline(always)]
-pub(super) fn vay_push(r: FourVector, ui: FourVector, E: ThreeVector, B: ThreeVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+pub fn vay_push(r: FourVector, ui: FourVector, E: ThreeVector, B: ThreeVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
     // velocity in SI units
     let u = ThreeVector::from(ui);
     let gamma = (1.0 + u * u).sqrt(); // enforce mass-shell condition
@@ -40,9 +45,10 @@ pub(super) fn vay_push(r: FourVector, ui: FourVector, E: ThreeVector, B: ThreeVe
             0.0
         };
         let power = 2.0 * ALPHA_FINE * chi * chi / (3.0 * COMPTON_TIME);
-        let g_chi = match eqn {
-            EquationOfMotion::ModifiedLandauLifshitz => lcfa::photon_emission::gaunt_factor(chi),
-            _ => 1.0,
+        let g_chi = if eqn.is_quantum_corrected() {
+            lcfa::photon_emission::gaunt_factor(chi)
+        } else {
+            1.0
         };
         g_chi * power * dt * u_half / u_half_mag
     } else {
@@ -77,20 +83,78 @@ pub(super) fn vay_push(r: FourVector, ui: FourVector, E: ThreeVector, B: ThreeVe
     (r_new, u_new, dt, dwork)
 }
 
-/// Pseudorandomly emit a photon from an electron with normalized
-/// momentum `u`, which is accelerated by an electric field `E` and
-/// magnetic field `B`.
+/// Returns the quantum parameter of a particle with normalized momentum `u`
+/// in an electric field `E` and magnetic field `B`.
 #[allow(non_snake_case)]
 #[inline(always)]
-pub(super) fn radiate<R: Rng>(u: FourVector, E: ThreeVector, B: ThreeVector, a: f64, dt: f64, rng: &mut R, mode: RadiationMode) -> Option<RadiationEvent> {
-    let classical = mode == RadiationMode::Classical;
+fn quantum_parameter(u: FourVector, E: ThreeVector, B: ThreeVector) -> f64 {
     let beta = ThreeVector::from(u) / u[0];
     let E_rf_sqd = (E + SPEED_OF_LIGHT * beta.cross(B)).norm_sqr() - (E * beta).powi(2);
-    let chi = if E_rf_sqd > 0.0 {
+    if E_rf_sqd > 0.0 {
         u[0] * E_rf_sqd.sqrt() / CRITICAL_FIELD
     } else {
         0.0
-    };
+    }
+}
+
+/// Returns the effective field experienced by a photon propagating
+/// along `n`, E - (E·n) n + c n × B, in an electric field `E` and
+/// magnetic field `B`.
+#[allow(non_snake_case)]
+#[inline(always)]
+fn photon_transverse_field(n: ThreeVector, E: ThreeVector, B: ThreeVector) -> ThreeVector {
+    E - (E * n) * n + SPEED_OF_LIGHT * n.cross(B)
+}
+
+/// Returns the quantum parameter of a photon with normalized momentum `ell`
+/// in an electric field `E` and magnetic field `B`.
+/// This is the χ that determines the rate of pair creation.
+#[allow(non_snake_case)]
+#[inline(always)]
+pub fn photon_quantum_parameter(ell: FourVector, E: ThreeVector, B: ThreeVector) -> f64 {
+    let n = ThreeVector::from(ell).normalize();
+    ell[0] * photon_transverse_field(n, E, B).norm_sqr().sqrt() / CRITICAL_FIELD
+}
+
+/// Returns the unit vector along the instantaneous transverse acceleration
+/// of an electron with normalized momentum `u`, in an electric field `E`
+/// and magnetic field `B`.
+#[allow(non_snake_case)]
+#[inline(always)]
+fn acceleration_direction(u: FourVector, E: ThreeVector, B: ThreeVector) -> ThreeVector {
+    let beta = ThreeVector::from(u) / u[0];
+    let long: ThreeVector = beta.normalize();
+    -(E - (long * E) * long / E.norm_sqr().sqrt() + SPEED_OF_LIGHT * beta.cross(B)).normalize()
+}
+
+/// Returns the Stokes parameters of a photon with normalized momentum `k`,
+/// emitted by an (unpolarized) electron with normalized momentum `u`,
+/// in an electric field `E` and magnetic field `B`.
+/// This is the polarization assigned to the photon by `radiate`.
+#[allow(non_snake_case)]
+#[inline(always)]
+pub fn photon_polarization(u: FourVector, k: FourVector, E: ThreeVector, B: ThreeVector, mode: RadiationMode) -> StokesVector {
+    let chi = quantum_parameter(u, E, B);
+    let beta = ThreeVector::from(u) / u[0];
+    let w = acceleration_direction(u, E, B);
+    match mode {
+        RadiationMode::Classical => lcfa::photon_emission::classical::stokes_parameters(k, chi, u[0], beta, w),
+        RadiationMode::Quantum => lcfa::photon_emission::stokes_parameters(k, chi, u[0], beta, w),
+    }
+}
+
+/// Pseudorandomly emit a photon from an electron at position `r`,
+/// with normalized momentum `u`, which is accelerated by an electric field `E` and
+/// magnetic field `B` (in V/m and T), over a time interval `dt` (in s).
+/// `a` is the local normalized amplitude, which is recorded in the event.
+/// The photon energy is limited to `max_recoil` times the electron energy
+/// (see [RadiationEvent::with_recoil_cap]).
+/// Returns information about the emission, if it occurs (see [RadiationEvent]).
+#[allow(non_snake_case)]
+#[inline(always)]
+pub fn radiate<R: Rng>(r: FourVector, u: FourVector, E: ThreeVector, B: ThreeVector, a: f64, dt: f64, rng: &mut R, mode: RadiationMode, max_recoil: f64) -> Option<RadiationEvent> {
+    let classical = mode == RadiationMode::Classical;
+    let chi = quantum_parameter(u, E, B);
 
     let prob = if classical {
         dt * lcfa::photon_emission::classical::rate(chi, u[0])
@@ -106,25 +170,23 @@ pub(super) fn radiate<R: Rng>(u: FourVector, E: ThreeVector, B: ThreeVector, a:
         };
 
         if let Some(theta) = theta {
-            let long: ThreeVector = beta.normalize();
-            let w = -(E - (long * E) * long / E.norm_sqr().sqrt() + SPEED_OF_LIGHT * beta.cross(B)).normalize();
+            let long: ThreeVector = ThreeVector::from(u).normalize();
+            let w = acceleration_direction(u, E, B);
             let perp: ThreeVector = w.rotate_around(long, cphi);
             let k: ThreeVector = omega_mc2 * (theta.cos() * long + theta.sin() * perp);
             let k = FourVector::lightlike(k[0], k[1], k[2]);
-            let pol = if classical {
-                lcfa::photon_emission::classical::stokes_parameters(k, chi, u[0], beta, w)
-            } else {
-                lcfa::photon_emission::stokes_parameters(k, chi, u[0], beta, w)
-            };
+            let pol = photon_polarization(u, k, E, B, mode);
 
             Some(RadiationEvent {
+                r,
                 k,
                 u_prime: u - k,
                 pol,
                 a_eff: a,
                 chi,
-                absorption: 0.0
-            })
+                absorption: 0.0,
+                recoil_capped: false,
+            }.with_recoil_cap(u, max_recoil))
         } else {
             None
         }
@@ -134,18 +196,20 @@ pub(super) fn radiate<R: Rng>(u: FourVector, E: ThreeVector, B: ThreeVector, a:
 }
 
 /// Pseudorandomly create an electron-positron pair from a photon with
-/// normalized momentum `u`, in an electric field `E` and
-/// magnetic field `B`, returning the probability, the actual rate
-/// increase used, the new Stokes parameters of the photon, as well as
-/// the momenta of the electron and positron that are created and
-/// the effective amplitude at the point of creation.
+/// normalized momentum `u` and Stokes parameters `sv`, in an electric field `E` and
+/// magnetic field `B` (in V/m and T), over a time interval `dt` (in s),
+/// with the rate increased by `rate_increase`.
+/// Returns the probability, the new Stokes parameters of the photon, as well as
+/// information about the pair, if created (see [PairCreationEvent]),
+/// including the momenta of the electron and positron and
+/// the effective amplitude `a` at the point of creation.
 #[allow(non_snake_case)]
 #[inline(always)]
-pub(super) fn pair_create<R: Rng>(u: FourVector, sv: StokesVector, E: ThreeVector, B: ThreeVector, a: f64, dt: f64, rng: &mut R, rate_increase: f64) -> (f64, StokesVector, Option<PairCreationEvent>) {
+pub fn pair_create<R: Rng>(u: FourVector, sv: StokesVector, E: ThreeVector, B: ThreeVector, a: f64, dt: f64, rng: &mut R, rate_increase: f64) -> (f64, StokesVector, Option<PairCreationEvent>) {
     let n = ThreeVector::from(u).normalize();
 
     // transverse "acceleration"
-    let a_perp = E - (E * n) * n + SPEED_OF_LIGHT * n.cross(B);
+    let a_perp = photon_transverse_field(n, E, B);
     let E_rf_sqd = a_perp.norm_sqr();
 
     let (chi, prob, sv_new) = if E_rf_sqd > 0.0 {
@@ -190,4 +254,53 @@ pub(super) fn pair_create<R: Rng>(u: FourVector, sv: StokesVector, E: ThreeVecto
     } else {
         (prob, sv_new, None)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_xoshiro::*;
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn representative_inputs() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // head-on collision with a crossed field, chi = 1 for gamma = 1000
+        let gamma = 1000.0;
+        let E_mag = 0.5 * CRITICAL_FIELD / gamma;
+        let E = ThreeVector::new(E_mag, 0.0, 0.0);
+        let B = ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -gamma).unitize();
+        let dt = 1.0e-18;
+
+        // push conserves the mass shell, and the electron is deflected along -x
+        let (r_new, u_new, dt_actual, work) = vay_push(r, u, E, B, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+        println!("push: ct = {:.3e} m, u^2 = {:.6e}, u_x = {:.3e}, work = {:.3e}", r_new[0], u_new * u_new, u_new[1], work);
+        assert!(dt_actual == dt && r_new[0] > 0.0);
+        assert!((u_new * u_new - 1.0).abs() < 1.0e-9);
+        assert!(u_new[1] < 0.0 && work.is_finite());
+
+        // emission is certain if dt is large enough, and conserves momentum
+        let event = radiate(r, u, E, B, 0.0, 1.0, &mut rng, RadiationMode::Quantum, 1.0).unwrap();
+        println!("radiate: chi = {:.3e}, omega = {:.3e}", event.chi, event.k[0]);
+        assert!((event.chi - 1.0).abs() < 1.0e-6);
+        assert!(event.k[0] > 0.0 && event.k[0] < gamma);
+        assert!(event.u_prime == u - event.k);
+
+        // pair creation by a photon with the same energy
+        let ell = FourVector::lightlike(0.0, 0.0, -gamma);
+        // the rate increase is capped, so the event is not certain
+        let (prob, sv, event) = loop {
+            if let (prob, sv, Some(event)) = pair_create(ell, StokesVector::unpolarized(), E, B, 0.0, dt, &mut rng, 1.0e6) {
+                break (prob, sv, event);
+            }
+        };
+        println!("pair_create: prob = {:.3e}, frac = {:.3e}, gamma_e + gamma_p = {:.6e}", prob, event.frac, event.u_e[0] + event.u_p[0]);
+        assert!(prob > 0.0 && prob < 1.0);
+        assert!((sv[0] - 1.0).abs() < 1.0e-12);
+        assert!((event.frac - 10.0 * prob).abs() < 1.0e-9 * event.frac);
+        assert!(event.u_e[0] + event.u_p[0] > gamma);
+    }
+}
@@ -11,12 +11,22 @@ mod focused_laser;
 mod fast_focused_laser;
 mod plane_wave;
 mod fast_plane_wave;
-mod lcf;
+mod scaled;
+mod depleting;
+mod flying_focus;
+mod cylinder;
+mod noisy;
+pub mod lcf;
 
 pub use self::focused_laser::*;
 pub use self::fast_focused_laser::*;
 pub use self::plane_wave::*;
 pub use self::fast_plane_wave::*;
+pub use self::scaled::*;
+pub use self::depleting::*;
+pub use self::flying_focus::*;
+pub use self::cylinder::*;
+pub use self::noisy::*;
 
 /// The polarization of an electromagnetic wave
 #[allow(unused)]
@@ -46,6 +56,23 @@ pub enum Envelope {
     Gaussian = 2,
 }
 
+/// Returns the theoretical ponderomotive deflection angle, in mrad, of an
+/// electron with Lorentz factor 1000 that collides head-on with a linearly
+/// polarized, focused laser pulse with the given temporal `envelope`,
+/// at a distance of 2 μm from the laser axis, perpendicular to the
+/// polarization (e.g. along y for polarization along x, as in the test
+/// `lp_deflection`). The laser has a0 = 100, wavelength 0.8 μm, waist 4 μm
+/// and a duration of 10 cycles. The angle is doubled if the laser is
+/// instead circularly polarized.
+#[allow(unused)]
+pub fn ponderomotive_deflection_coefficient(envelope: Envelope) -> f64 {
+    match envelope {
+        Envelope::CosSquared => 1.13724,
+        Envelope::Flattop => 2.95684,
+        Envelope::Gaussian => 3.22816,
+    }
+}
+
 #[cfg(feature = "hdf5-output")]
 impl Hdf5Type for Envelope {
     fn new() -> Datatype {
@@ -59,18 +86,58 @@ impl Hdf5Type for Envelope {
 
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub enum EquationOfMotion {
+    /// Lorentz force only
     Lorentz,
+    /// Lorentz force and the classical Landau-Lifshitz radiation-reaction force
     LandauLifshitz,
+    /// As `LandauLifshitz`, but with the radiation-reaction force suppressed
+    /// by the Gaunt factor g(χ), the ratio of the quantum to classical
+    /// radiated powers. This reduces to `LandauLifshitz` for χ ≪ 1.
     ModifiedLandauLifshitz,
 }
 
 impl EquationOfMotion {
-    fn includes_rr(&self) -> bool {
+    /// Returns true if the equation of motion includes a
+    /// radiation-reaction force.
+    pub fn includes_rr(&self) -> bool {
         match self {
             EquationOfMotion::LandauLifshitz | EquationOfMotion::ModifiedLandauLifshitz => true,
             EquationOfMotion::Lorentz => false,
         }
     }
+
+    /// Returns true if the radiation-reaction force is purely classical,
+    /// i.e. not corrected for quantum effects. False if there is no
+    /// radiation reaction at all.
+    #[allow(unused)]
+    pub fn is_classical(&self) -> bool {
+        matches!(self, EquationOfMotion::LandauLifshitz)
+    }
+
+    /// Returns true if the radiation-reaction force includes a
+    /// quantum correction (the Gaunt factor).
+    #[allow(unused)]
+    pub fn is_quantum_corrected(&self) -> bool {
+        matches!(self, EquationOfMotion::ModifiedLandauLifshitz)
+    }
+}
+
+/// Ordering of the position update ("drift") and momentum update ("kick")
+/// in the Lorentz-force particle push
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum PushScheme {
+    /// Drift by half a step, kick using the fields there, and drift
+    /// by another half step with the new momentum. Second-order accurate.
+    /// This is the default, used by `push`.
+    #[default]
+    Leapfrog,
+    /// Kick using the fields at the initial position, then drift
+    /// by a full step with the new momentum. First-order accurate.
+    KickDrift,
+    /// Drift by a full step with the initial momentum, then kick using
+    /// the fields at the new position. First-order accurate.
+    DriftKick,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -81,6 +148,8 @@ pub enum RadiationMode {
 
 #[derive(Copy, Clone)]
 pub struct RadiationEvent {
+    /// The four-position at which the photon is emitted
+    pub r: FourVector,
     /// The normalized momentum of the emitted photon
     pub k: FourVector,
     /// The normalized momentum of the recoiling electron/positron
@@ -94,6 +163,206 @@ pub struct RadiationEvent {
     /// The energy absorbed from the field during the interaction,
     /// in units of the electron rest energy
     pub absorption: f64,
+    /// Whether the energy of the photon was reduced to respect a
+    /// maximum recoil (see [with_recoil_cap](RadiationEvent::with_recoil_cap))
+    pub recoil_capped: bool,
+}
+
+impl RadiationEvent {
+    /// Limits the energy of the emitted photon to at most `max_recoil`
+    /// times the energy of the emitting particle, which had normalized
+    /// momentum `u`, by rescaling the photon momentum at fixed direction.
+    /// The momentum removed from the photon is returned to the recoiling
+    /// particle. A `max_recoil` of unity has no effect.
+    pub fn with_recoil_cap(self, u: FourVector, max_recoil: f64) -> Self {
+        let limit = max_recoil * u[0];
+        if self.k[0] > limit {
+            let k = (limit / self.k[0]) * self.k;
+            RadiationEvent {
+                k,
+                u_prime: self.u_prime + self.k - k,
+                recoil_capped: true,
+                ..self
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Returns the harmonic order of the emission, i.e. the number of
+    /// laser photons absorbed, inferred from the kinematics of the event:
+    /// n = (u'·k) / [κ·(u' + k)], where κ is the normalized wavevector
+    /// of the laser, ħ k_L / (m c). This is only meaningful for emission
+    /// in a monochromatic plane wave, e.g. [PlaneWave].
+    #[allow(unused)]
+    pub fn harmonic(&self, kappa: FourVector) -> f64 {
+        (self.u_prime * self.k) / (kappa * (self.u_prime + self.k))
+    }
+
+    /// Returns the polarization of the emitted photon in the basis where
+    /// e_1 lies in the scattering plane, i.e. the plane spanned by the
+    /// momenta of the photon and its parent, and e_2 is perpendicular to it.
+    /// The parent momentum is reconstructed as `u' + k`, neglecting the
+    /// (small) momentum absorbed from the field.
+    ///
+    /// If the photon is emitted exactly parallel to its parent, the plane
+    /// is undefined and the polarization is returned in the global basis.
+    #[allow(unused)]
+    pub fn pol_in_scattering_plane(&self) -> StokesVector {
+        let n = ThreeVector::from(self.k).normalize();
+        let p = ThreeVector::from(self.u_prime + self.k);
+        // component of parent momentum perpendicular to the photon's
+        let a = p - (p * n) * n;
+        if a.norm_sqr() <= 1.0e-24 * p.norm_sqr() {
+            self.pol
+        } else {
+            self.pol.in_basis(a, n).0
+        }
+    }
+}
+
+/// Selects radiation events by their harmonic order (see [RadiationEvent::harmonic]),
+/// accepting those in the inclusive range `min..=max`.
+#[allow(unused)]
+#[derive(Copy, Clone)]
+pub struct HarmonicFilter {
+    kappa: FourVector,
+    min: i32,
+    max: i32,
+}
+
+impl HarmonicFilter {
+    /// Creates a filter for emission in a laser of the given `wavelength`,
+    /// propagating along +z.
+    #[allow(unused)]
+    pub fn new(wavelength: f64, min: i32, max: i32) -> Self {
+        use crate::constants::{COMPTON_TIME, SPEED_OF_LIGHT};
+        let kappa = SPEED_OF_LIGHT * COMPTON_TIME * (2.0 * std::f64::consts::PI / wavelength) * FourVector::new(1.0, 0.0, 0.0, 1.0);
+        HarmonicFilter { kappa, min, max }
+    }
+
+    #[allow(unused)]
+    pub fn accepts(&self, event: &RadiationEvent) -> bool {
+        let n = event.harmonic(self.kappa).round() as i32;
+        n >= self.min && n <= self.max
+    }
+}
+
+/// Accumulates the mean quantum parameter of the parents of a set of
+/// radiation events, weighted by the energy of the emitted photons,
+/// i.e. ⟨χ⟩ = Σ w ω χ / Σ w ω, where `w` is the weight of the parent.
+/// This emphasises the conditions under which most of the energy is radiated,
+/// rather than those that are most frequently encountered.
+#[allow(unused)]
+#[derive(Copy, Clone, Default)]
+pub struct PowerWeightedChi {
+    energy: f64,
+    energy_chi: f64,
+}
+
+impl PowerWeightedChi {
+    #[allow(unused)]
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Adds an event, emitted by a particle with unit weight.
+    #[allow(unused)]
+    pub fn add(&mut self, event: &RadiationEvent) {
+        self.add_weighted(event, 1.0);
+    }
+
+    /// Adds an event, emitted by a macroparticle of given `weight`.
+    #[allow(unused)]
+    pub fn add_weighted(&mut self, event: &RadiationEvent, weight: f64) {
+        let energy = weight * event.k[0];
+        self.energy += energy;
+        self.energy_chi += energy * event.chi;
+    }
+
+    /// Returns the power-weighted mean χ, or `None` if no energy
+    /// has been radiated.
+    #[allow(unused)]
+    pub fn mean(&self) -> Option<f64> {
+        if self.energy > 0.0 {
+            Some(self.energy_chi / self.energy)
+        } else {
+            None
+        }
+    }
+}
+
+/// Accumulates the longitudinal positions at which photons are emitted,
+/// in `bins` equally sized bins between `z_min` and `z_max`,
+/// so that the emission profile dN/dz can be reconstructed.
+/// Emission outside that range is counted, but not binned.
+#[allow(unused)]
+#[derive(Clone)]
+pub struct EmissionProfile {
+    z_min: f64,
+    z_max: f64,
+    counts: Vec<f64>,
+    outside: f64,
+}
+
+impl EmissionProfile {
+    #[allow(unused)]
+    pub fn new(z_min: f64, z_max: f64, bins: usize) -> Self {
+        EmissionProfile {
+            z_min,
+            z_max,
+            counts: vec![0.0; bins],
+            outside: 0.0,
+        }
+    }
+
+    /// Adds an event, emitted by a particle with unit weight.
+    #[allow(unused)]
+    pub fn add(&mut self, event: &RadiationEvent) {
+        self.add_weighted(event, 1.0);
+    }
+
+    /// Adds an event, emitted by a macroparticle of given `weight`.
+    #[allow(unused)]
+    pub fn add_weighted(&mut self, event: &RadiationEvent, weight: f64) {
+        let z = event.r[3];
+        if z >= self.z_min && z < self.z_max {
+            let bins = self.counts.len();
+            let i = (bins as f64 * (z - self.z_min) / (self.z_max - self.z_min)) as usize;
+            self.counts[i.min(bins - 1)] += weight;
+        } else {
+            self.outside += weight;
+        }
+    }
+
+    /// Combines the events accumulated by `other`, which must have the
+    /// same binning, e.g. as collected by a different thread or task.
+    #[allow(unused)]
+    pub fn merge(&mut self, other: &Self) {
+        assert!(self.z_min == other.z_min && self.z_max == other.z_max && self.counts.len() == other.counts.len());
+        for (a, b) in self.counts.iter_mut().zip(other.counts.iter()) {
+            *a += b;
+        }
+        self.outside += other.outside;
+    }
+
+    /// Returns the total weight of the events that fell outside
+    /// the binned range.
+    #[allow(unused)]
+    pub fn outside(&self) -> f64 {
+        self.outside
+    }
+
+    /// Returns the centres of the bins, in m, and the emission
+    /// profile dN/dz in each, in 1/m.
+    #[allow(unused)]
+    pub fn dn_dz(&self) -> Vec<(f64, f64)> {
+        let dz = (self.z_max - self.z_min) / (self.counts.len() as f64);
+        self.counts.iter()
+            .enumerate()
+            .map(|(i, n)| (self.z_min + (i as f64 + 0.5) * dz, n / dz))
+            .collect()
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -102,7 +371,11 @@ pub struct PairCreationEvent {
     pub u_e: FourVector,
     /// The normalized momentum of the positron
     pub u_p: FourVector,
-    /// The fraction of the photon that has decayed
+    /// The fraction of the photon that has decayed, i.e. the statistical
+    /// weight of the pair relative to the parent photon.
+    /// This is `1 / rate_increase`, so that events that are made more
+    /// frequent by the rate increase are correspondingly less
+    /// important, and the physical yield is unchanged.
     pub frac: f64,
     /// The effective a0 of the interaction
     pub a_eff: f64,
@@ -120,6 +393,102 @@ pub enum Laser {
     FastPlaneWave,
     FocusedLaser,
     FastFocusedLaser,
+    FlyingFocus,
+}
+
+/// The parameters of a laser pulse, from which any [Laser] variant can
+/// be constructed with [Laser::from_params]. Fields are named, rather than
+/// positional, so that they cannot be mixed up; those not of interest
+/// can be left at their defaults, e.g.
+/// ```ignore
+/// let laser = Laser::from_params(LaserParams {
+///     a0: 10.0,
+///     waist: Some(4.0e-6),
+///     ..Default::default()
+/// });
+/// ```
+#[allow(unused)]
+#[derive(Copy, Clone)]
+pub struct LaserParams {
+    /// Peak normalized amplitude
+    pub a0: f64,
+    /// Wavelength of the carrier, in m
+    pub wavelength: f64,
+    /// Waist (1/e^2 radius of the intensity), in m, if the laser is focused,
+    /// or `None` for a plane wave
+    pub waist: Option<f64>,
+    /// Duration, in wavelengths
+    pub n_cycles: f64,
+    pub polarization: Polarization,
+    /// Angle of the polarization axis (if linear) to the x axis, in radians
+    pub pol_angle: f64,
+    pub envelope: Envelope,
+    /// Chirp parameter, which must be zero for a focused laser
+    pub chirp_b: f64,
+    /// Whether the carrier wave is resolved, i.e. [FastPlaneWave] or
+    /// [FastFocusedLaser], or the fields are cycle-averaged,
+    /// i.e. [PlaneWave] or [FocusedLaser]
+    pub resolve_carrier: bool,
+    /// Velocity of the focal point, in units of the speed of light, if the
+    /// laser is a [FlyingFocus], or `None` otherwise. A flying focus
+    /// requires a waist and no chirp; as it is radially polarized, with
+    /// a Gaussian envelope and a resolved carrier, `polarization`,
+    /// `pol_angle`, `envelope` and `resolve_carrier` are ignored.
+    pub focal_velocity: Option<f64>,
+}
+
+impl Default for LaserParams {
+    /// A linearly polarized, cos^2 plane wave with a0 = 1, wavelength 0.8 μm
+    /// and a duration of 10 cycles, which is cycle-averaged
+    fn default() -> Self {
+        LaserParams {
+            a0: 1.0,
+            wavelength: 0.8e-6,
+            waist: None,
+            n_cycles: 10.0,
+            polarization: Polarization::Linear,
+            pol_angle: 0.0,
+            envelope: Envelope::CosSquared,
+            chirp_b: 0.0,
+            resolve_carrier: false,
+            focal_velocity: None,
+        }
+    }
+}
+
+impl Laser {
+    /// Constructs the laser pulse described by `params`.
+    /// Panics if a chirp is requested for a focused laser,
+    /// or if a flying focus is requested without a waist.
+    #[allow(unused)]
+    pub fn from_params(params: LaserParams) -> Self {
+        let LaserParams { a0, wavelength, waist, n_cycles, polarization: pol, pol_angle, envelope, chirp_b, resolve_carrier, focal_velocity } = params;
+
+        if let Some(focal_velocity) = focal_velocity {
+            let waist = waist.expect("A flying focus requires a waist.");
+            assert!(chirp_b == 0.0, "Chirp is not supported for focused lasers (requested chirp_b = {:.3e}).", chirp_b);
+            FlyingFocus::new(a0, wavelength, waist, n_cycles, focal_velocity).into()
+        } else if let Some(waist) = waist {
+            assert!(chirp_b == 0.0, "Chirp is not supported for focused lasers (requested chirp_b = {:.3e}).", chirp_b);
+            if resolve_carrier {
+                FastFocusedLaser::new(a0, wavelength, waist, n_cycles, pol, pol_angle)
+                    .with_envelope(envelope)
+                    .into()
+            } else {
+                FocusedLaser::new(a0, wavelength, waist, n_cycles, pol, pol_angle)
+                    .with_envelope(envelope)
+                    .into()
+            }
+        } else if resolve_carrier {
+            FastPlaneWave::new(a0, wavelength, n_cycles, pol, pol_angle, chirp_b)
+                .with_envelope(envelope)
+                .into()
+        } else {
+            PlaneWave::new(a0, wavelength, n_cycles, pol, pol_angle, chirp_b)
+                .with_envelope(envelope)
+                .into()
+        }
+    }
 }
 
 /// Represents the electromagnetic field in a spatiotemporal domain.
@@ -143,21 +512,81 @@ pub trait Field {
     /// position and momentum, as well as the change in
     /// lab time (which may differ from `dt`)
     /// and the energy absorbed from the background field.
-    #[allow(non_snake_case)]
     fn push(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+        self.push_with_scheme(r, u, rqm, dt, eqn, PushScheme::default())
+    }
+
+    /// As `push`, but with the ordering of the position and momentum
+    /// updates specified by `scheme` (see [PushScheme]).
+    /// Fields that provide their own equations of motion, e.g. the
+    /// ponderomotive solvers, ignore `scheme`.
+    #[allow(non_snake_case)]
+    fn push_with_scheme(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion, scheme: PushScheme) -> (FourVector, FourVector, f64, f64) {
         use crate::constants::SPEED_OF_LIGHT;
-        let r = r + 0.5 * SPEED_OF_LIGHT * u * dt / u[0];
-        let (E, B, _) = self.fields(r);
-        lcf::vay_push(r, u, E, B, rqm, dt, eqn)
+        // neutral particles free-stream, and photons stay on the light cone
+        if rqm == 0.0 {
+            let r = r + SPEED_OF_LIGHT * u * dt / u[0];
+            return (r, u, dt, 0.0);
+        }
+        match scheme {
+            PushScheme::Leapfrog => {
+                let r = r + 0.5 * SPEED_OF_LIGHT * u * dt / u[0];
+                let (E, B, _) = self.fields(r);
+                lcf::vay_push(r, u, E, B, rqm, dt, eqn)
+            },
+            PushScheme::KickDrift => {
+                let (E, B, _) = self.fields(r);
+                let (_, u, dt, work) = lcf::vay_push(r, u, E, B, rqm, dt, eqn);
+                let r = r + SPEED_OF_LIGHT * u * dt / u[0];
+                (r, u, dt, work)
+            },
+            PushScheme::DriftKick => {
+                let r = r + SPEED_OF_LIGHT * u * dt / u[0];
+                let (E, B, _) = self.fields(r);
+                let (_, u, dt, work) = lcf::vay_push(r, u, E, B, rqm, dt, eqn);
+                (r, u, dt, work)
+            },
+        }
+    }
+
+    /// Returns the rate at which the field does work on a particle
+    /// with charge to mass ratio `rqm`, located at position `r` with
+    /// normalized momentum `u`, in units of the particle's own rest energy,
+    /// m c^2, per second. For electrons and positrons, this is the electron
+    /// rest energy; photons, which have `rqm = 0`, absorb nothing.
+    /// Integrated over a step, this is the energy absorbed returned by `push`.
+    #[allow(non_snake_case)]
+    #[allow(unused)]
+    fn power(&self, r: FourVector, u: FourVector, rqm: f64) -> f64 {
+        use crate::constants::SPEED_OF_LIGHT;
+        let (E, _, _) = self.fields(r);
+        rqm * (E * ThreeVector::from(u)) / (u[0] * SPEED_OF_LIGHT)
     }
 
     /// Checks to see whether an electron in the field, located at
     /// position `r` with momentum `u` emits a photon, and if so,
     /// returns information about the event (see [RadiationEvent]).
+    /// The photon carries away no more than `max_recoil` of the electron's
+    /// energy (see [RadiationEvent::with_recoil_cap]): pass unity to
+    /// impose no limit.
     #[allow(non_snake_case)]
-    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode) -> Option<RadiationEvent> {
+    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode, max_recoil: f64) -> Option<RadiationEvent> {
         let (E, B, a) = self.fields(r);
-        lcf::radiate(u, E, B, a, dt, rng, mode)
+        lcf::radiate(r, u, E, B, a, dt, rng, mode, max_recoil)
+    }
+
+    /// Returns the Stokes parameters of a photon with normalized momentum `k`,
+    /// emitted by an unpolarized electron at position `r` with normalized
+    /// momentum `u`, as assigned by the locally constant field implementation
+    /// of `radiate`. The parameters are defined with respect to a basis vector
+    /// in the x-z plane that is perpendicular to the photon momentum.
+    ///
+    /// Fields that override `radiate` may assign the polarization differently.
+    #[allow(non_snake_case)]
+    #[allow(unused)]
+    fn photon_polarization(&self, r: FourVector, u: FourVector, k: FourVector, mode: RadiationMode) -> StokesVector {
+        let (E, B, _) = self.fields(r);
+        lcf::photon_polarization(u, k, E, B, mode)
     }
 
     /// Checks to see if an electron-positron pair is produced by
@@ -169,13 +598,23 @@ pub trait Field {
     /// A non-unity `rate_increase` makes pair creation more probable
     /// by the given factor, increasing the statistics for what would
     /// otherwise be a rare event. The probability returned is *not*
-    /// affected by this increase.
+    /// affected by this increase, but the event's [frac](PairCreationEvent::frac)
+    /// is reduced in proportion. Weighting the pair, and depleting the photon,
+    /// by `frac` recovers the physical yield.
     #[allow(non_snake_case)]
     fn pair_create<R: Rng>(&self, r: FourVector, ell: FourVector, pol: StokesVector, dt: f64, rng: &mut R, rate_increase: f64) -> (f64, StokesVector, Option<PairCreationEvent>) {
         let (E, B, a) = self.fields(r);
         lcf::pair_create(ell, pol, E, B, a, dt, rng, rate_increase)
     }
 
+    /// Is the field cycle-averaged? Such fields provide their own
+    /// `push`, `radiate` and `pair_create`, in terms of the local
+    /// amplitude of the envelope, and return zero from [fields](Field::fields).
+    #[allow(unused)]
+    fn is_cycle_averaged(&self) -> bool {
+        false
+    }
+
     /// Returns a tuple of the electric and magnetic fields, as well
     /// as the normalized amplitude (if applicable), at the
     /// specified four-position `r`.
@@ -184,15 +623,143 @@ pub trait Field {
         ([0.0; 3].into(), [0.0; 3].into(), 0.0)
     }
 
+    /// Returns the local amplitude of the normalized vector potential,
+    /// a = e |E| / (m c ω), at four-position `r`. For fields that resolve
+    /// the carrier wave, this is the third element returned by
+    /// [fields](Field::fields); fields that are cycle-averaged return the
+    /// amplitude of the local envelope, so that this equals the nominal
+    /// a0 at the peak of the pulse in all cases.
+    #[allow(unused)]
+    fn local_a0(&self, r: FourVector) -> f64 {
+        self.fields(r).2
+    }
+
+    /// Returns the quantum parameter χ of a photon with normalized
+    /// momentum `ell` (which must be lightlike) at four-position `r`,
+    /// as used by [pair_create](Field::pair_create). For fields that
+    /// are cycle-averaged, this is evaluated with the rms amplitude
+    /// of the local envelope.
+    #[allow(unused)]
+    #[allow(non_snake_case)]
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        let (E, B, _) = self.fields(r);
+        lcf::photon_quantum_parameter(ell, E, B)
+    }
+
+    /// Returns the Lorentz invariants of the field at four-position `r`,
+    /// (E^2 - c^2 B^2, c E·B), normalized to the square of the critical field.
+    /// Both vanish for a plane wave (a null field).
+    #[allow(non_snake_case)]
+    #[allow(unused)]
+    fn invariants(&self, r: FourVector) -> (f64, f64) {
+        use crate::constants::{SPEED_OF_LIGHT, CRITICAL_FIELD};
+        let (E, B, _) = self.fields(r);
+        let B = SPEED_OF_LIGHT * B;
+        (
+            (E.norm_sqr() - B.norm_sqr()) / CRITICAL_FIELD.powi(2),
+            (E * B) / CRITICAL_FIELD.powi(2),
+        )
+    }
+
+    /// Returns the electric and magnetic fields at four-position `r` in the
+    /// instantaneous rest frame of a particle with normalized momentum `u`.
+    ///
+    /// These are the spatial parts of the four-vectors F^{μν} u_ν and
+    /// its dual, boosted into the rest frame (where their time
+    /// components vanish). The magnitude of the rest-frame electric
+    /// field, in units of the critical field, is the quantum parameter χ
+    /// that drives emission.
+    #[allow(non_snake_case)]
+    #[allow(unused)]
+    fn rest_frame_fields(&self, r: FourVector, u: FourVector) -> (ThreeVector, ThreeVector) {
+        use crate::constants::SPEED_OF_LIGHT;
+        let (E, B, _) = self.fields(r);
+        let cB = SPEED_OF_LIGHT * B;
+        let gamma = u[0];
+        let u_s = ThreeVector::from(u);
+        let e = (gamma * E + u_s.cross(cB)).with_time(u_s * E).boost_by(u);
+        let b = (gamma * cB - u_s.cross(E)).with_time(u_s * cB).boost_by(u);
+        (ThreeVector::from(e), ThreeVector::from(b) / SPEED_OF_LIGHT)
+    }
+
+    /// Returns the electric and magnetic fields, and the local amplitude,
+    /// at spatial position `r`, as if time were frozen at `t` (in seconds).
+    #[allow(unused)]
+    fn fields_at_fixed_time(&self, r: ThreeVector, t: f64) -> (ThreeVector, ThreeVector, f64) {
+        use crate::constants::SPEED_OF_LIGHT;
+        self.fields(r.with_time(SPEED_OF_LIGHT * t))
+    }
+
     /// Returns the total energy of the electromagnetic field and the
     /// units of that energy (`"J"`, `"J/m"`, `"J/m^2"` , `"J/m^3"`, as appropriate).
     /// If the field is infinitely extended in one or more dimensions,
     /// the energy is calculated per unit length in those dimensions.
     fn energy(&self) -> (f64, &'static str);
+
+    /// Returns the angular frequency ω of the carrier wave (in rad/s),
+    /// or `None` if the field does not oscillate.
+    #[allow(unused)]
+    fn carrier_frequency(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns the peak normalized amplitude a0 with which the field was
+    /// defined, or `None` if it has no such parameter. Unlike
+    /// [local_a0](Field::local_a0), this is independent of position and
+    /// of the phase of the carrier.
+    #[allow(unused)]
+    fn nominal_a0(&self) -> Option<f64> {
+        None
+    }
+
+    /// Returns the phase, or light-cone time, η = ω (t - z/c) at
+    /// four-position `r`, for a field with a carrier that propagates
+    /// along +z, or `None` if the field does not oscillate.
+    /// Evaluated along a trajectory, e.g. in the callback of
+    /// [propagate_with](crate::propagate::propagate_with), this allows
+    /// emission to be binned in η.
+    #[allow(unused)]
+    fn light_cone_phase(&self, r: FourVector) -> Option<f64> {
+        use crate::constants::SPEED_OF_LIGHT;
+        self.carrier_frequency().map(|omega| omega * (r[0] - r[3]) / SPEED_OF_LIGHT)
+    }
+
+    /// Returns the number of photons in the field, i.e. its [energy](Field::energy)
+    /// divided by ħω, where ω is the [carrier frequency](Field::carrier_frequency),
+    /// and the units of that number (`"1"`, `"1/m"`, `"1/m^2"`, `"1/m^3"`,
+    /// as appropriate). Returns `None` if the field has no carrier,
+    /// or if its energy is given in units other than these.
+    #[allow(unused)]
+    fn photon_number(&self) -> Option<(f64, &'static str)> {
+        use crate::constants::{ELECTRON_MASS, SPEED_OF_LIGHT_SQD, COMPTON_TIME};
+        let omega = self.carrier_frequency()?;
+        let photon_energy = ELECTRON_MASS * SPEED_OF_LIGHT_SQD * COMPTON_TIME * omega;
+        let (energy, unit) = self.energy();
+        let unit = match unit {
+            "J" => "1",
+            "J/m" => "1/m",
+            "J/m^2" => "1/m^2",
+            "J/m^3" => "1/m^3",
+            _ => return None,
+        };
+        Some((energy / photon_energy, unit))
+    }
+
+    /// Returns the field with the simulation domain restricted to
+    /// a cylinder, of given `radius`, that extends from `z_min` to `z_max`
+    /// (see [CylindricalDomain]).
+    ///
+    /// The wrapped field is always a [Laser], so that this method can be
+    /// forwarded to each of its variants.
+    #[allow(unused)]
+    fn with_domain_cylinder(self, radius: f64, z_min: f64, z_max: f64) -> CylindricalDomain<Laser> where Self: Sized + Into<Laser> {
+        CylindricalDomain::new(self.into(), radius, z_min, z_max)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f64::consts;
     use super::*;
     use crate::constants::*;
 
@@ -229,11 +796,7 @@ mod tests {
         }
         let lorentz = lorentz.1;
 
-        let theory = 2.0 * match envelope {
-            Envelope::CosSquared => 1.13724,
-            Envelope::Flattop => 2.95684,
-            Envelope::Gaussian => 3.22816,
-        };
+        let theory = 2.0 * ponderomotive_deflection_coefficient(envelope);
 
         let pond_angle = 1.0e3 * pond[1].atan2(-pond[3]);
         let lorentz_angle = 1.0e3 * lorentz[1].atan2(-lorentz[3]);
@@ -276,11 +839,7 @@ mod tests {
         }
         let lorentz = lorentz.1;
 
-        let theory = match envelope {
-            Envelope::CosSquared => 1.13724,
-            Envelope::Flattop => 2.95684,
-            Envelope::Gaussian => 3.22816,
-        };
+        let theory = ponderomotive_deflection_coefficient(envelope);
 
         let pond_angle = 1.0e3 * pond[2].atan2(-pond[3]);
         let lorentz_angle = 1.0e3 * lorentz[2].atan2(-lorentz[3]);
@@ -290,4 +849,662 @@ mod tests {
         assert!(error < 1.0e-2);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn deflection_coefficients() {
+        let expected = [
+            (Envelope::CosSquared, 1.13724),
+            (Envelope::Flattop, 2.95684),
+            (Envelope::Gaussian, 3.22816),
+        ];
+        for (envelope, value) in expected.iter() {
+            println!("{:?}: {:.5}", envelope, ponderomotive_deflection_coefficient(*envelope));
+            assert_eq!(ponderomotive_deflection_coefficient(*envelope), *value);
+        }
+    }
+
+    #[test]
+    fn photons_free_stream() {
+        let fast_laser: Laser = FastFocusedLaser::new(100.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0).into();
+        let laser: Laser = FocusedLaser::new(100.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0).into();
+
+        for field in [fast_laser, laser].iter() {
+            let z0 = field.ideal_initial_z();
+            let ell = FourVector::lightlike(10.0, 0.0, -1000.0);
+            let r0 = FourVector::new(-z0, 0.0, 0.0, z0);
+            let dt = field.max_timestep().unwrap();
+
+            let mut r = r0;
+            let mut u = ell;
+            let mut t = 0.0;
+            while field.contains(r) {
+                let new = field.push(r, u, 0.0, dt, EquationOfMotion::Lorentz);
+                r = new.0;
+                u = new.1;
+                t += new.2;
+            }
+
+            let expected = r0 + SPEED_OF_LIGHT * ell * t / ell[0];
+            let error = (ThreeVector::from(r) - ThreeVector::from(expected)).norm_sqr().sqrt();
+            println!("final u = [{:.6e}, {:.6e}, {:.6e}, {:.6e}], u^2 = {:.3e}, deviation from straight line = {:.3e} m", u[0], u[1], u[2], u[3], u * u, error);
+            assert!(u[0] == ell[0] && u[1] == ell[1] && u[2] == ell[2] && u[3] == ell[3]);
+            assert!(error < 1.0e-12);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn gaunt_factor_suppression() {
+        // electron colliding head-on with a constant crossed field, such that chi = 1
+        let gamma = 1000.0;
+        let E_mag = CRITICAL_FIELD / (2.0 * gamma);
+        let E = ThreeVector::new(E_mag, 0.0, 0.0);
+        let B = ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -(gamma * gamma - 1.0).sqrt()).unitize();
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+        let dt = 1.0e-17;
+
+        let lorentz = lcf::vay_push(r, u, E, B, rqm, dt, EquationOfMotion::Lorentz).1;
+        let ll = lcf::vay_push(r, u, E, B, rqm, dt, EquationOfMotion::LandauLifshitz).1;
+        let mod_ll = lcf::vay_push(r, u, E, B, rqm, dt, EquationOfMotion::ModifiedLandauLifshitz).1;
+
+        let ratio = (lorentz[0] - mod_ll[0]) / (lorentz[0] - ll[0]);
+        let chi = 2.0 * gamma * E_mag / CRITICAL_FIELD;
+        let target = crate::lcfa::photon_emission::gaunt_factor(chi);
+        let error = (ratio - target).abs() / target;
+        println!("chi = {:.3}, energy loss [mod. LL / LL] = {:.6e}, g(chi) = {:.6e}, error = {:.3e}", chi, ratio, target, error);
+        assert!(ratio < 1.0);
+        assert!(error < 1.0e-2);
+    }
+
+    /// Uniform, static electric and magnetic fields
+    #[allow(non_snake_case)]
+    struct UniformField {
+        E: ThreeVector,
+        B: ThreeVector,
+    }
+
+    impl Field for UniformField {
+        fn max_timestep(&self) -> Option<f64> {
+            None
+        }
+
+        fn contains(&self, _r: FourVector) -> bool {
+            true
+        }
+
+        fn ideal_initial_z(&self) -> f64 {
+            0.0
+        }
+
+        fn fields(&self, _r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+            (self.E, self.B, 0.0)
+        }
+
+        fn energy(&self) -> (f64, &'static str) {
+            (0.0, "J/m^3")
+        }
+    }
+
+    #[test]
+    fn push_scheme_convergence() {
+        // gyration in a uniform magnetic field, B = B z^
+        let field = UniformField {
+            E: ThreeVector::new(0.0, 0.0, 0.0),
+            B: ThreeVector::new(0.0, 0.0, 1.0),
+        };
+        let rqm = ELECTRON_CHARGE / ELECTRON_MASS;
+        let u = FourVector::new(0.0, 1.0, 0.0, 0.0).unitize();
+        let r0 = FourVector::new(0.0, 0.0, 0.0, 0.0);
+
+        // v = v0 (cos theta, sin theta, 0), with theta = -Omega t
+        let omega = -rqm * field.B[2] / u[0];
+        let v0 = SPEED_OF_LIGHT * u[1] / u[0];
+        let radius = v0 / omega.abs();
+        let duration = 0.5 * consts::PI / omega.abs();
+        let theta = omega * duration;
+        let target = ThreeVector::new(v0 * theta.sin() / omega, v0 * (1.0 - theta.cos()) / omega, 0.0);
+
+        let error = |scheme: PushScheme, n: usize| -> f64 {
+            let dt = duration / (n as f64);
+            let mut r = r0;
+            let mut u = u;
+            for _i in 0..n {
+                let new = field.push_with_scheme(r, u, rqm, dt, EquationOfMotion::Lorentz, scheme);
+                r = new.0;
+                u = new.1;
+            }
+            (ThreeVector::from(r) - target).norm_sqr().sqrt() / radius
+        };
+
+        for (scheme, order) in [(PushScheme::Leapfrog, 2.0), (PushScheme::KickDrift, 1.0), (PushScheme::DriftKick, 1.0)].iter() {
+            let coarse = error(*scheme, 100);
+            let fine = error(*scheme, 200);
+            let converged = error(*scheme, 10_000);
+            let measured_order = (coarse / fine).log2();
+            println!("{:?}: error [n = 100] = {:.3e}, error [n = 200] = {:.3e}, order = {:.3}, error [n = 10000] = {:.3e}", scheme, coarse, fine, measured_order, converged);
+            assert!((measured_order - order).abs() < 0.1);
+            assert!(converged < 1.0e-3);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn chi_from_constant_crossed_field() {
+        let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+        let gamma = 1000.0;
+        let E_mag = 0.3 * SCHWINGER_FIELD / gamma;
+        let E = ThreeVector::new(E_mag, 0.0, 0.0);
+        let B = ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -(gamma * gamma - 1.0).sqrt()).unitize();
+
+        // E + v x B is entirely perpendicular to v
+        let v = SPEED_OF_LIGHT * ThreeVector::from(u) / u[0];
+        let field_perp = (E + v.cross(B)).norm_sqr().sqrt();
+        let chi = chi_from_field(u[0], field_perp);
+
+        // emission is guaranteed if dt is large enough
+        let event = lcf::radiate(r, u, E, B, 0.0, 1.0, &mut rng, RadiationMode::Quantum, 1.0).unwrap();
+        let error = (chi - event.chi).abs() / event.chi;
+        println!("chi = {:.6e}, from radiate = {:.6e}, error = {:.3e}", chi, event.chi, error);
+        assert!(error < 1.0e-12);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn recoil_cap() {
+        let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+        let gamma = 1000.0;
+        // chi ~ 100
+        let E_mag = 50.0 * SCHWINGER_FIELD / gamma;
+        let E = ThreeVector::new(E_mag, 0.0, 0.0);
+        let B = ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -(gamma * gamma - 1.0).sqrt()).unitize();
+
+        let max_recoil = 0.5;
+        let floor = (1.0 - max_recoil) * u[0];
+
+        // without the cap, some emissions take more than half the energy
+        let uncapped = (0..10_000)
+            .filter_map(|_| lcf::radiate(r, u, E, B, 0.0, 1.0, &mut rng, RadiationMode::Quantum, 1.0))
+            .filter(|event| event.u_prime[0] < floor)
+            .count();
+        println!("without cap, {} emissions leave the electron below the floor", uncapped);
+        assert!(uncapped > 0);
+
+        let mut capped = 0;
+        for _ in 0..10_000 {
+            let event = match lcf::radiate(r, u, E, B, 0.0, 1.0, &mut rng, RadiationMode::Quantum, max_recoil) {
+                Some(event) => event,
+                None => continue,
+            };
+            if event.recoil_capped {
+                capped += 1;
+                assert!((event.k[0] - max_recoil * u[0]).abs() < 1.0e-12 * u[0]);
+                assert!(event.k * event.k < 1.0e-9 * event.k[0].powi(2));
+            }
+            assert!(event.u_prime[0] >= (1.0 - 1.0e-12) * floor);
+            // momentum is conserved
+            let du = event.u_prime + event.k - u;
+            let error = du[0].abs() + ThreeVector::from(du).norm_sqr().sqrt();
+            assert!(error < 1.0e-9 * u[0]);
+        }
+        println!("with cap, {} emissions are capped", capped);
+        assert!(capped > 0);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn null_field_invariants() {
+        let wavelength = 0.8e-6;
+        let laser = FastPlaneWave::new(100.0, wavelength, 8.0, Polarization::Circular, 0.0, 0.0);
+
+        for i in 0..100 {
+            let r = FourVector::new(0.04 * wavelength * (i as f64), 1.0e-6, -2.0e-6, 0.3e-6);
+            let (E, _, _) = laser.fields(r);
+            let scale = E.norm_sqr() / CRITICAL_FIELD.powi(2);
+            let (s, p) = laser.invariants(r);
+            println!("|E|^2 = {:.3e}, E^2 - B^2 = {:.3e}, E.B = {:.3e} [normalized]", scale, s, p);
+            assert!(s.abs() < 1.0e-12 * scale.max(1.0e-30));
+            assert!(p.abs() < 1.0e-12 * scale.max(1.0e-30));
+        }
+
+        // constant crossed field
+        let E_mag = 1.0e-3 * CRITICAL_FIELD;
+        let field = UniformField {
+            E: ThreeVector::new(E_mag, 0.0, 0.0),
+            B: ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0),
+        };
+        let (s, p) = field.invariants(FourVector::new(0.0, 0.0, 0.0, 0.0));
+        println!("crossed field: E^2 - B^2 = {:.3e}, E.B = {:.3e} [normalized]", s, p);
+        assert!(s.abs() < 1.0e-12 && p.abs() < 1.0e-12);
+
+        // but not a purely electric field
+        let field = UniformField {
+            E: ThreeVector::new(E_mag, 0.0, 0.0),
+            B: ThreeVector::new(0.0, 0.0, 0.0),
+        };
+        let (s, p) = field.invariants(FourVector::new(0.0, 0.0, 0.0, 0.0));
+        assert!((s - 1.0e-6).abs() < 1.0e-12 && p == 0.0);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn pair_yield_with_rate_increase() {
+        let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+
+        // photon colliding head on with a crossed field, chi = 1
+        let gamma = 1000.0;
+        let E_mag = 0.5 * SCHWINGER_FIELD / gamma;
+        let field = UniformField {
+            E: ThreeVector::new(E_mag, 0.0, 0.0),
+            B: ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0),
+        };
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let ell = FourVector::lightlike(0.0, 0.0, -gamma);
+        let pol = StokesVector::unpolarized();
+
+        // choose dt so that the probability per step is small
+        let (prob, _, _) = field.pair_create(r, ell, pol, 1.0e-18, &mut rng, 1.0);
+        let dt = 1.0e-18 * 5.0e-4 / prob;
+
+        let num = 200_000;
+        let rate_increase = 100.0;
+        let mut expected = 0.0;
+        let (mut count, mut count_boosted) = (0, 0);
+        let mut total_weight = 0.0;
+        for _ in 0..num {
+            let (prob, _, event) = field.pair_create(r, ell, pol, dt, &mut rng, 1.0);
+            expected += prob;
+            count += event.map_or(0, |_| 1);
+
+            let (_, _, event) = field.pair_create(r, ell, pol, dt, &mut rng, rate_increase);
+            if let Some(event) = event {
+                count_boosted += 1;
+                total_weight += event.frac;
+            }
+        }
+
+        let error = (total_weight - expected).abs() / expected;
+        println!(
+            "physical yield = {:.4e}, events = {} [no increase], {} [increase = {:.0}], summed weight = {:.4e}, error = {:.3e}",
+            expected, count, count_boosted, rate_increase, total_weight, error,
+        );
+        assert!(expected * rate_increase / (num as f64) < 0.1);
+        assert!(((count_boosted as f64) - rate_increase * expected).abs() < 0.03 * rate_increase * expected);
+        assert!(((count as f64) - expected).abs() < 0.4 * expected);
+        assert!(error < 0.03);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn photon_polarization_asymmetry() {
+        let mut rng = rand_xoshiro::Xoshiro256StarStar::seed_from_u64(0);
+
+        // electron colliding head on with a crossed field, polarized along x, with chi = 0.01
+        let gamma = 1000.0;
+        let E_mag = 0.005 * SCHWINGER_FIELD / gamma;
+        let field = UniformField {
+            E: ThreeVector::new(E_mag, 0.0, 0.0),
+            B: ThreeVector::new(0.0, E_mag / SPEED_OF_LIGHT, 0.0),
+        };
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -gamma).unitize();
+
+        let mut total = 0.0;
+        let mut sv = [0.0; 3];
+        for _ in 0..20_000 {
+            // emission is guaranteed if dt is large enough
+            if let Some(event) = field.radiate(r, u, 1.0, &mut rng, RadiationMode::Quantum, 1.0) {
+                let pol = field.photon_polarization(r, u, event.k, RadiationMode::Quantum);
+                for i in 0..4 {
+                    assert_eq!(pol[i], event.pol[i]);
+                }
+                // weight by energy
+                total += event.k[0];
+                for (i, s) in sv.iter_mut().enumerate() {
+                    *s += event.k[0] * pol[i as i32 + 1];
+                }
+            }
+        }
+
+        let sv: Vec<f64> = sv.iter().map(|s| s / total).collect();
+        println!("energy-weighted Stokes parameters = [{:.4}, {:.4}, {:.4}], expected [0.75, 0, 0]", sv[0], sv[1], sv[2]);
+
+        // photons are preferentially polarized parallel to the acceleration, i.e. along x
+        assert!((sv[0] - 0.75).abs() < 0.05);
+        assert!(sv[1].abs() < 0.02 && sv[2].abs() < 0.02);
+    }
+
+    #[test]
+    fn scattering_plane_basis() {
+        let (theta, omega) = (0.01_f64, 100.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let pol = StokesVector::new(1.0, 0.6, 0.0, 0.8);
+
+        for phi in [0.0, 0.3, consts::FRAC_PI_4, consts::FRAC_PI_2, 2.0, consts::PI].iter() {
+            let k = FourVector::lightlike(
+                omega * theta.sin() * phi.cos(),
+                omega * theta.sin() * phi.sin(),
+                -omega * theta.cos()
+            );
+            let event = RadiationEvent {
+                r: [0.0; 4].into(),
+                k,
+                u_prime: u - k,
+                pol,
+                a_eff: 0.0,
+                chi: 0.0,
+                absorption: 0.0,
+                recoil_capped: false,
+            };
+
+            // global e_1 is close to -x, whereas the scattering plane
+            // contains (cos phi, sin phi, 0) in the small angle limit,
+            // so the basis is rotated by angle phi, and (S1, S2) by 2 phi
+            let sv = event.pol_in_scattering_plane();
+            let (sin_2phi, cos_2phi) = (2.0 * phi).sin_cos();
+            println!(
+                "phi = {:.3}: S = [{:.3}, {:.6}, {:.6}, {:.3}], expected S1 = {:.6}, S2 = {:.6}",
+                phi, sv[0], sv[1], sv[2], sv[3], 0.6 * cos_2phi, 0.6 * sin_2phi
+            );
+            assert!((sv[0] - 1.0).abs() < 1.0e-12);
+            assert!((sv[1] - 0.6 * cos_2phi).abs() < 1.0e-3);
+            assert!((sv[2] - 0.6 * sin_2phi).abs() < 1.0e-3);
+            assert!((sv[3] - 0.8).abs() < 1.0e-12);
+        }
+
+        // collinear emission leaves the Stokes parameters unchanged
+        let k = FourVector::lightlike(0.0, 0.0, -omega);
+        let event = RadiationEvent { r: [0.0; 4].into(), k, u_prime: u - k, pol, a_eff: 0.0, chi: 0.0, absorption: 0.0, recoil_capped: false };
+        let sv = event.pol_in_scattering_plane();
+        assert!(sv[1] == pol[1] && sv[2] == pol[2]);
+    }
+
+    #[test]
+    fn equation_of_motion_properties() {
+        let cases = [
+            // (eqn, includes_rr, is_classical, is_quantum_corrected)
+            (EquationOfMotion::Lorentz, false, false, false),
+            (EquationOfMotion::LandauLifshitz, true, true, false),
+            (EquationOfMotion::ModifiedLandauLifshitz, true, false, true),
+        ];
+        for (eqn, rr, classical, quantum) in cases.iter() {
+            assert_eq!(eqn.includes_rr(), *rr);
+            assert_eq!(eqn.is_classical(), *classical);
+            assert_eq!(eqn.is_quantum_corrected(), *quantum);
+            // a classical or quantum-corrected force must be present
+            assert_eq!(eqn.includes_rr(), eqn.is_classical() || eqn.is_quantum_corrected());
+        }
+    }
+
+    #[test]
+    fn power_weighted_chi() {
+        let event = |omega: f64, chi: f64| RadiationEvent {
+            r: FourVector::new(0.0, 0.0, 0.0, 0.0),
+            k: FourVector::lightlike(0.0, 0.0, omega),
+            u_prime: FourVector::new(0.0, 0.0, 0.0, 1000.0).unitize(),
+            pol: StokesVector::unpolarized(),
+            a_eff: 0.0,
+            chi,
+            absorption: 0.0,
+            recoil_capped: false,
+        };
+
+        let mut acc = PowerWeightedChi::new();
+        assert!(acc.mean().is_none());
+
+        acc.add(&event(1.0, 0.1));
+        acc.add(&event(3.0, 0.5));
+        acc.add_weighted(&event(2.0, 1.0), 2.0);
+
+        // (1 * 0.1 + 3 * 0.5 + 4 * 1.0) / (1 + 3 + 4)
+        let target = 5.6 / 8.0;
+        let mean = acc.mean().unwrap();
+        println!("power-weighted chi = {:.6}, expected {:.6}", mean, target);
+        assert!((mean - target).abs() < 1.0e-12);
+    }
+
+    #[test]
+    fn emission_profile() {
+        use rand_xoshiro::*;
+
+        let laser = FocusedLaser::new(10.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Linear, 0.0)
+            .with_envelope(Envelope::Gaussian);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let u0 = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // electrons meet the peak of the pulse at z = 0, and do not recoil,
+        // so that the profile should follow the pulse envelope
+        let (z_max, bins) = (8.0e-6, 16);
+        let mut profile = EmissionProfile::new(-z_max, z_max, bins);
+        for _i in 0..1000 {
+            let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+            let mut u = u0;
+            let mut subtotal = EmissionProfile::new(-z_max, z_max, bins);
+            while laser.contains(r) {
+                let (r_new, u_new, dt_actual, _) = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                r = r_new;
+                u = u_new;
+                if let Some(event) = laser.radiate(r, u, dt_actual, &mut rng, RadiationMode::Quantum, 1.0) {
+                    subtotal.add(&event);
+                }
+            }
+            profile.merge(&subtotal);
+        }
+
+        let dn_dz = profile.dn_dz();
+        let total: f64 = dn_dz.iter().map(|(_, n)| n).sum();
+        let (peak, _) = dn_dz.iter().cloned().fold((0.0, 0.0), |(z0, n0), (z, n)| if n > n0 {(z, n)} else {(z0, n0)});
+        let upstream: f64 = dn_dz[..bins/2].iter().map(|(_, n)| n).sum();
+        let downstream: f64 = dn_dz[bins/2..].iter().map(|(_, n)| n).sum();
+        let mean_z = dn_dz.iter().map(|(z, n)| z * n).sum::<f64>() / total;
+        let asymmetry = (upstream - downstream) / total;
+
+        // statistical error, in terms of number of events
+        let dz = 2.0 * z_max / (bins as f64);
+        let num = total * dz;
+        let error = 1.0 / num.sqrt();
+
+        for (z, n) in dn_dz.iter() {
+            println!("z = {:>+9.3e} m: dN/dz = {:.3e} /m", z, n);
+        }
+        println!("{:.0} events, {:.0} outside range: peak at {:.3e} m, mean z = {:.3e} m, asymmetry = {:.3e} [expected error {:.3e}]", num, profile.outside(), peak, mean_z, asymmetry, error);
+        assert!(num > 1000.0);
+        assert!(profile.outside() < 0.01 * num);
+        assert!(peak.abs() < 2.0 * dz);
+        assert!(asymmetry.abs() < 3.0 * error);
+        assert!(mean_z.abs() < 3.0 * error * z_max);
+    }
+
+    #[test]
+    fn laser_from_params() {
+        let (a0, wavelength, waist, n_cycles) = (10.0, 0.8e-6, 4.0e-6, 8.0);
+        let pol_angle = 0.3;
+        let envelope = Envelope::Gaussian;
+
+        let pairs: Vec<(Laser, Laser)> = vec![
+            (
+                PlaneWave::new(a0, wavelength, n_cycles, Polarization::Linear, pol_angle, 1.0e-3).with_envelope(envelope).into(),
+                Laser::from_params(LaserParams { a0, wavelength, waist: None, n_cycles, polarization: Polarization::Linear, pol_angle, envelope, chirp_b: 1.0e-3, resolve_carrier: false, focal_velocity: None }),
+            ),
+            (
+                FastPlaneWave::new(a0, wavelength, n_cycles, Polarization::Circular, 0.0, 0.0).with_envelope(envelope).into(),
+                Laser::from_params(LaserParams { a0, wavelength, n_cycles, polarization: Polarization::Circular, envelope, resolve_carrier: true, ..Default::default() }),
+            ),
+            (
+                FocusedLaser::new(a0, wavelength, waist, n_cycles, Polarization::Linear, pol_angle).with_envelope(envelope).into(),
+                Laser::from_params(LaserParams { a0, wavelength, waist: Some(waist), n_cycles, pol_angle, envelope, ..Default::default() }),
+            ),
+            (
+                FastFocusedLaser::new(a0, wavelength, waist, n_cycles, Polarization::Linear, pol_angle).with_envelope(envelope).into(),
+                Laser::from_params(LaserParams { a0, wavelength, waist: Some(waist), n_cycles, pol_angle, envelope, resolve_carrier: true, ..Default::default() }),
+            ),
+            (
+                FlyingFocus::new(a0, wavelength, waist, n_cycles, 0.5).into(),
+                Laser::from_params(LaserParams { a0, wavelength, waist: Some(waist), n_cycles, focal_velocity: Some(0.5), ..Default::default() }),
+            ),
+        ];
+
+        let points = [
+            FourVector::new(0.0, 0.0, 0.0, 0.0),
+            FourVector::new(1.0e-6, 2.0e-6, -1.0e-6, 0.5e-6),
+            FourVector::new(-3.0e-6, 0.0, 1.5e-6, 2.0e-6),
+        ];
+
+        for (positional, from_params) in pairs.iter() {
+            let same_variant = match (positional, from_params) {
+                (Laser::PlaneWave(_), Laser::PlaneWave(_)) => true,
+                (Laser::FastPlaneWave(_), Laser::FastPlaneWave(_)) => true,
+                (Laser::FocusedLaser(_), Laser::FocusedLaser(_)) => true,
+                (Laser::FastFocusedLaser(_), Laser::FastFocusedLaser(_)) => true,
+                (Laser::FlyingFocus(_), Laser::FlyingFocus(_)) => true,
+                _ => false,
+            };
+            assert!(same_variant);
+            assert_eq!(positional.max_timestep(), from_params.max_timestep());
+            assert_eq!(positional.ideal_initial_z(), from_params.ideal_initial_z());
+            assert_eq!(positional.energy(), from_params.energy());
+
+            // cycle-averaged lasers return zero from fields(), so compare
+            // the local amplitude and the result of a push instead
+            let u = FourVector::new(0.0, 0.0, 0.0, -100.0).unitize();
+            let dt = positional.max_timestep().unwrap();
+            let (mut nonzero_a, mut deflected) = (false, false);
+            for r in points.iter() {
+                let (a1, a2) = (positional.local_a0(*r), from_params.local_a0(*r));
+                println!("a = {:.6e} [positional], {:.6e} [from params]", a1, a2);
+                nonzero_a |= a1 > 0.0;
+                assert_eq!(a1, a2);
+                let (r1, u1, dt1, w1) = positional.push(*r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                let (r2, u2, dt2, w2) = from_params.push(*r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+                assert_eq!(u1, u2);
+                assert_eq!(r1, r2);
+                assert_eq!(dt1, dt2);
+                assert_eq!(w1, w2);
+                deflected |= u1 != u;
+            }
+            // so that the comparisons above are not trivial
+            assert!(nonzero_a && deflected);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn photon_chi() {
+        let omega = 1.0e4; // photon energy, in units of the electron mass
+        let ell = FourVector::lightlike(0.0, 0.0, -omega);
+
+        // for a head-on collision with a plane wave, E_perp = 2 |E|
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.1e-6);
+        let (E, _, _) = laser.fields(r);
+        let target = omega * 2.0 * E.norm_sqr().sqrt() / CRITICAL_FIELD;
+        let chi = laser.photon_quantum_parameter(r, ell);
+        let error = (chi - target).abs() / target;
+        println!("fast plane wave: chi = {:.6e}, expected {:.6e}, error = {:.3e}", chi, target, error);
+        assert!(error < 1.0e-6);
+
+        // or 2 |E_rms| for a cycle-averaged one
+        let laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let target = omega * 2.0 * laser.peak_field_v_per_m() / (2_f64.sqrt() * CRITICAL_FIELD);
+        let chi = laser.photon_quantum_parameter(r, ell);
+        let error = (chi - target).abs() / target;
+        println!("plane wave: chi = {:.6e}, expected {:.6e}, error = {:.3e}", chi, target, error);
+        assert!(error < 1.0e-4);
+    }
+
+    #[test]
+    fn photon_number() {
+        let wavelength = 0.8e-6;
+        let laser: Laser = FocusedLaser::new(10.0, wavelength, 4.0e-6, 10.0, Polarization::Linear, 0.0).into();
+        let (energy, unit) = laser.energy();
+        let (number, number_unit) = laser.photon_number().unwrap();
+
+        // hbar omega = 2 pi hbar c / lambda, with hbar in J s
+        let hbar = 1.054572e-34;
+        let target = energy * wavelength / (2.0 * consts::PI * hbar * SPEED_OF_LIGHT);
+        let error = (number - target).abs() / target;
+        println!("energy = {:.4e} {}, photon number = {:.4e} {}, expected {:.4e}, error = {:.3e}", energy, unit, number, number_unit, target, error);
+        assert_eq!(number_unit, "1");
+        assert!(error < 1.0e-5);
+
+        // per unit area for a plane wave
+        let laser: Laser = FastPlaneWave::new(10.0, wavelength, 10.0, Polarization::Linear, 0.0, 0.0).into();
+        let (_, unit) = laser.photon_number().unwrap();
+        assert_eq!(unit, "1/m^2");
+
+        // and undefined without a carrier
+        let field = UniformField {
+            E: ThreeVector::new(1.0e12, 0.0, 0.0),
+            B: ThreeVector::new(0.0, 0.0, 0.0),
+        };
+        assert!(field.photon_number().is_none());
+    }
+
+    #[test]
+    fn local_a0_at_focus() {
+        let a0 = 10.0;
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let off_axis = FourVector::new(0.0, 4.0e-6, 0.0, 0.0);
+
+        for pol in [Polarization::Linear, Polarization::Circular].iter() {
+            let laser: Laser = FocusedLaser::new(a0, 0.8e-6, 4.0e-6, 10.0, *pol, 0.0).into();
+            let at_focus = laser.local_a0(r);
+            let at_waist = laser.local_a0(off_axis);
+            println!("local a0 = {:.6e} at focus, {:.6e} at the waist radius", at_focus, at_waist);
+            assert!((at_focus - a0).abs() < 1.0e-12 * a0);
+            assert!((at_waist - a0 * (-1_f64).exp()).abs() < 1.0e-12 * a0);
+
+            // and wrapped fields are consistent
+            let scaled = laser.scaled(0.5);
+            assert!((scaled.local_a0(r) - 0.5 * a0).abs() < 1.0e-12 * a0);
+        }
+
+        // which agrees with fields() for a field that resolves the carrier
+        let laser: Laser = FastFocusedLaser::new(a0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0).into();
+        let (_, _, a) = laser.fields(r);
+        println!("fast CP: local a0 = {:.6e}, fields().2 = {:.6e}", laser.local_a0(r), a);
+        assert_eq!(laser.local_a0(r), a);
+        assert!((a - a0).abs() < 1.0e-2 * a0);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn rest_frame_fields() {
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.1e-6);
+        let (E, _, _) = laser.fields(r);
+
+        for gamma in [10.0, 1000.0].iter() {
+            let u = FourVector::new(0.0, 0.0, 0.0, -(gamma * gamma - 1.0_f64).sqrt()).unitize();
+            let (E_rf, B_rf) = laser.rest_frame_fields(r, u);
+
+            // head-on collision with a plane wave: E' = gamma (1 + beta) E
+            let beta = u[3].abs() / u[0];
+            let target = gamma * (1.0 + beta) * E.norm_sqr().sqrt();
+            let magnitude = E_rf.norm_sqr().sqrt();
+            let error = (magnitude - target).abs() / target;
+
+            // still a null field
+            let cB_rf = SPEED_OF_LIGHT * B_rf.norm_sqr().sqrt();
+            let null_error = (cB_rf - magnitude).abs() / magnitude;
+
+            // and chi is Lorentz invariant
+            let chi = *gamma * (E + SPEED_OF_LIGHT * ThreeVector::from(u).cross(laser.fields(r).1) / u[0]).norm_sqr().sqrt() / CRITICAL_FIELD;
+
+            println!(
+                "gamma = {:.1e}: |E'| = {:.6e} V/m, expected {:.6e}, error = {:.3e}; |cB'| / |E'| - 1 = {:.3e}; chi = {:.6e} [lab], {:.6e} [rest frame]",
+                gamma, magnitude, target, error, null_error, chi, magnitude / CRITICAL_FIELD,
+            );
+            assert!(error < 1.0e-9);
+            assert!(null_error < 1.0e-9);
+            assert!((magnitude / CRITICAL_FIELD - chi).abs() < 1.0e-9 * chi);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Random fluctuations of the electromagnetic field
+
+use rand::prelude::*;
+use rand_distr::StandardNormal;
+use rand_xoshiro::Xoshiro256StarStar;
+use crate::geometry::{FourVector, ThreeVector};
+use super::{Field, Laser};
+
+/// Wraps a field, adding normally distributed fluctuations, with
+/// standard deviations `sigma_e` (in V/m) and `sigma_b` (in T), to each
+/// component of the electric and magnetic fields returned by `fields()`.
+///
+/// The fluctuations are a deterministic function of the four-position
+/// and the `seed`, so repeated evaluations at the same point agree.
+/// The normalized amplitude is that of the inner field.
+/// Cycle-averaged fields have no instantaneous E and B to perturb,
+/// so wrapping one panics.
+pub struct NoisyField<F: Field> {
+    inner: F,
+    sigma_e: f64,
+    sigma_b: f64,
+    seed: u64,
+}
+
+impl<F: Field> NoisyField<F> {
+    #[allow(unused)]
+    pub fn new(inner: F, sigma_e: f64, sigma_b: f64, seed: u64) -> Self {
+        assert!(!inner.is_cycle_averaged(), "cannot add noise to a cycle-averaged field");
+        NoisyField {
+            inner,
+            sigma_e,
+            sigma_b,
+            seed,
+        }
+    }
+
+    /// Returns a generator seeded by the position `r`
+    fn rng_at(&self, r: FourVector) -> Xoshiro256StarStar {
+        // splitmix64 finalizer, applied to each coordinate in turn
+        let mix = |x: u64| -> u64 {
+            let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+            let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+            x ^ (x >> 31)
+        };
+        let seed = (0..4).fold(mix(self.seed), |h, i| mix(h ^ r[i].to_bits()));
+        Xoshiro256StarStar::seed_from_u64(seed)
+    }
+}
+
+impl Laser {
+    /// Returns the laser with Gaussian noise, of standard deviation
+    /// `sigma_e` (in V/m) and `sigma_b` (in T), added to each component
+    /// of its electric and magnetic fields.
+    #[allow(unused)]
+    pub fn with_field_noise(self, sigma_e: f64, sigma_b: f64, seed: u64) -> NoisyField<Laser> {
+        NoisyField::new(self, sigma_e, sigma_b, seed)
+    }
+}
+
+impl<F: Field> Field for NoisyField<F> {
+    fn max_timestep(&self) -> Option<f64> {
+        self.inner.max_timestep()
+    }
+
+    fn contains(&self, r: FourVector) -> bool {
+        self.inner.contains(r)
+    }
+
+    fn ideal_initial_z(&self) -> f64 {
+        self.inner.ideal_initial_z()
+    }
+
+    #[allow(non_snake_case)]
+    fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+        let (E, B, a) = self.inner.fields(r);
+        let mut rng = self.rng_at(r);
+        let mut noise = |sigma: f64| -> ThreeVector {
+            let n: [f64; 3] = [rng.sample(StandardNormal), rng.sample(StandardNormal), rng.sample(StandardNormal)];
+            sigma * ThreeVector::from(n)
+        };
+        let dE = noise(self.sigma_e);
+        let dB = noise(self.sigma_b);
+        (E + dE, B + dB, a)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        self.inner.carrier_frequency()
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        self.inner.nominal_a0()
+    }
+
+    fn energy(&self) -> (f64, &'static str) {
+        self.inner.energy()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::*;
+    use crate::field::{FastPlaneWave, PlaneWave, Polarization};
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn noise_statistics() {
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let (E0, _, _) = laser.fields(FourVector::new(0.0, 0.0, 0.0, 0.0));
+        let sigma_e = 0.01 * E0.norm_sqr().sqrt();
+        let sigma_b = sigma_e / SPEED_OF_LIGHT;
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let noisy = laser.with_field_noise(sigma_e, sigma_b, 0);
+
+        // deviations from the clean field, over many (distinct) points
+        let n = 100_000;
+        let (mut mean_e, mut mean_b, mut var_e, mut var_b) = (0.0, 0.0, 0.0, 0.0);
+        for i in 0..n {
+            let r = FourVector::new(0.0, 1.0e-9 * (i as f64), 0.0, 1.0e-9 * (i as f64));
+            let (E, B, _) = noisy.inner.fields(r);
+            let (E_n, B_n, _) = noisy.fields(r);
+            let (dE, dB) = (E_n - E, B_n - B);
+            mean_e += dE[0] + dE[1] + dE[2];
+            mean_b += dB[0] + dB[1] + dB[2];
+            var_e += dE.norm_sqr();
+            var_b += dB.norm_sqr();
+        }
+        let count = (3 * n) as f64;
+        let (mean_e, mean_b) = (mean_e / count, mean_b / count);
+        let (rms_e, rms_b) = ((var_e / count).sqrt(), (var_b / count).sqrt());
+
+        println!("<dE> = {:.3e}, rms dE = {:.4e} [expected {:.4e}]; <dB> = {:.3e}, rms dB = {:.4e} [expected {:.4e}]", mean_e, rms_e, sigma_e, mean_b, rms_b, sigma_b);
+        assert!(mean_e.abs() < 0.01 * sigma_e);
+        assert!(mean_b.abs() < 0.01 * sigma_b);
+        assert!((rms_e - sigma_e).abs() < 0.01 * sigma_e);
+        assert!((rms_b - sigma_b).abs() < 0.01 * sigma_b);
+
+        // reproducible at a given point
+        let r = FourVector::new(1.0e-7, 2.0e-7, 0.0, -1.0e-7);
+        assert!(noisy.fields(r).0 == noisy.fields(r).0);
+
+        // photon quantum parameter is evaluated with the noisy fields,
+        // as used by pair_create
+        let ell = FourVector::lightlike(0.0, 0.0, -1.0e4);
+        let (E_n, B_n, _) = noisy.fields(r);
+        let (E, B, _) = noisy.inner.fields(r);
+        let chi = noisy.photon_quantum_parameter(r, ell);
+        println!("chi = {:.6e} [noisy], {:.6e} [clean]", chi, crate::field::lcf::photon_quantum_parameter(ell, E, B));
+        assert_eq!(chi, crate::field::lcf::photon_quantum_parameter(ell, E_n, B_n));
+    }
+
+    #[test]
+    #[should_panic]
+    fn noisy_averaged_laser() {
+        let laser: Laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let _ = laser.with_field_noise(1.0e9, 1.0, 0);
+    }
+}
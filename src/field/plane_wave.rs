@@ -8,7 +8,7 @@ use crate::geometry::{FourVector, StokesVector};
 use crate::nonlinear_compton;
 use crate::pair_creation;
 
-use super::{RadiationMode, EquationOfMotion, RadiationEvent, Envelope};
+use super::{RadiationMode, EquationOfMotion, PushScheme, RadiationEvent, Envelope};
 
 /// Represents the envelope of a plane-wave laser pulse, i.e.
 /// the field after cycle averaging
@@ -21,6 +21,9 @@ pub struct PlaneWave {
     chirp_b: f64,
     bandwidth: f64,
     envelope: Envelope,
+    padding: f64,
+    gaussian_n_sigma: Option<f64>,
+    beat: (f64, f64),
 }
 
 impl PlaneWave {
@@ -36,6 +39,9 @@ impl PlaneWave {
             chirp_b,
             bandwidth: 0.0,
             envelope: Envelope::CosSquared,
+            padding: 1.0,
+            gaussian_n_sigma: None,
+            beat: (0.0, 0.0),
         }
     }
 
@@ -45,6 +51,69 @@ impl PlaneWave {
         cpy
     }
 
+    /// Extends the simulation domain, i.e. the region where
+    /// `contains()` is true and the distance `ideal_initial_z()`,
+    /// by the given `factor` (the default is unity).
+    #[allow(unused)]
+    pub fn with_domain_padding(self, factor: f64) -> Self {
+        let mut cpy = self;
+        cpy.padding = factor;
+        cpy
+    }
+
+    /// For a Gaussian envelope, truncates the simulation domain at
+    /// `n_sigma` standard deviations of the intensity profile either
+    /// side of its peak. By default, the domain extends from about
+    /// 9.4 sigma ahead of the peak to 7.1 sigma behind it.
+    /// Has no effect on other envelopes.
+    #[allow(unused)]
+    pub fn with_gaussian_truncation(self, n_sigma: f64) -> Self {
+        let mut cpy = self;
+        cpy.gaussian_n_sigma = Some(n_sigma);
+        cpy
+    }
+
+    /// Returns the phase at which the domain is truncated, if
+    /// set by [with_gaussian_truncation](Self::with_gaussian_truncation).
+    fn gaussian_extent(&self) -> Option<f64> {
+        // intensity envelope is exp[-phi^2 / (2 sigma^2)]
+        let sigma = consts::PI * self.n_cycles / (2.0 * consts::LN_2).sqrt();
+        self.gaussian_n_sigma.map(|n| n * sigma)
+    }
+
+    /// Rotates the polarization axis of a linearly polarized laser
+    /// by `psi` (in radians) around the laser propagation axis,
+    /// replacing the angle given at construction.
+    /// Circularly polarized lasers are unaffected.
+    #[allow(unused)]
+    pub fn with_polarization_angle(self, psi: f64) -> Self {
+        let mut cpy = self;
+        cpy.pol_angle = match cpy.pol {
+            Polarization::Circular => 0.0,
+            Polarization::Linear => psi,
+        };
+        cpy
+    }
+
+    /// Modulates the amplitude envelope of the pulse by a factor
+    /// `1 + depth cos(2π beat_freq t)`, where `t` is the time relative
+    /// to the peak of the pulse and `beat_freq` is given in Hz.
+    #[allow(unused)]
+    pub fn with_beat_modulation(self, depth: f64, beat_freq: f64) -> Self {
+        let mut cpy = self;
+        cpy.beat = (depth, beat_freq);
+        cpy
+    }
+
+    /// Returns the beat modulation of the amplitude envelope
+    /// and its derivative with respect to phase
+    fn beat_modulation(&self, phase: f64) -> (f64, f64) {
+        let (depth, beat_freq) = self.beat;
+        let ratio = beat_freq / (SPEED_OF_LIGHT * self.wavevector[0]);
+        let arg = 2.0 * consts::PI * ratio * phase;
+        (1.0 + depth * arg.cos(), -2.0 * consts::PI * ratio * depth * arg.sin())
+    }
+
     pub fn with_finite_bandwidth(self, on: bool) -> Self {
         let mut cpy = self;
         let n_fwhm = match cpy.envelope {
@@ -65,14 +134,40 @@ impl PlaneWave {
         self.wavevector
     }
 
+    /// Returns the phase, or light-cone time, η = k·r at four-position `r`.
+    /// η is zero at the peak of the pulse and increases by 2π per
+    /// optical cycle at fixed position.
+    #[allow(unused)]
+    pub fn phase(&self, r: FourVector) -> f64 {
+        self.wavevector * r
+    }
+
+    /// Returns the normalized amplitude a0 of a laser with the given
+    /// peak electric field `e_peak` (in V/m) and `wavelength` (in m).
+    #[allow(unused)]
+    pub fn a0_from_field(e_peak: f64, wavelength: f64) -> f64 {
+        ELEMENTARY_CHARGE * e_peak * wavelength / (2.0 * consts::PI * ELECTRON_MASS * SPEED_OF_LIGHT_SQD)
+    }
+
+    /// Returns the peak electric field of the laser, in V/m.
+    #[allow(unused)]
+    pub fn peak_field_v_per_m(&self) -> f64 {
+        ELECTRON_MASS * SPEED_OF_LIGHT_SQD * self.wavevector[0] * self.a0 / ELEMENTARY_CHARGE
+    }
+
     pub fn a_sqd(&self, r: FourVector) -> f64 {
+        let phase = self.wavevector * r;
+        let (m, _) = self.beat_modulation(phase);
+        m * m * self.unmodulated_a_sqd(phase)
+    }
+
+    /// Returns ⟨a^2⟩ at the given phase, neglecting any beat modulation
+    fn unmodulated_a_sqd(&self, phase: f64) -> f64 {
         let norm = match self.pol {
             Polarization::Linear => 0.5,
             Polarization::Circular => 1.0,
         };
 
-        let phase = self.wavevector * r;
-
         match self.envelope {
             // a = a0 {sin(phi), cos(phi)} cos[phi/(2n)]^2, |phi| < pi n
             Envelope::CosSquared => {
@@ -141,6 +236,10 @@ impl PlaneWave {
             }
         };
 
+        // include beat modulation
+        let (m, dm_dphi) = self.beat_modulation(phase);
+        let grad = m * m * grad - 2.0 * self.wavevector[0] * m * dm_dphi * self.unmodulated_a_sqd(phase);
+
         FourVector::new(
             -grad,
             0.0,
@@ -172,11 +271,20 @@ impl PlaneWave {
 }
 
 impl Field for PlaneWave {
+    fn is_cycle_averaged(&self) -> bool {
+        true
+    }
+
     fn max_timestep(&self) -> Option<f64> {
         let dt = match self.envelope {
             Envelope::CosSquared | Envelope::Gaussian => 1.0 / (SPEED_OF_LIGHT * self.wavevector[0]),
             Envelope::Flattop => 0.2 / (SPEED_OF_LIGHT * self.wavevector[0]),
         };
+        let dt = if self.beat.0 != 0.0 {
+            dt.min(0.05 / self.beat.1.abs()) // resolve the beat
+        } else {
+            dt
+        };
         Some(dt)
     }
 
@@ -185,15 +293,21 @@ impl Field for PlaneWave {
         let max_phase = match self.envelope {
             Envelope::CosSquared => consts::PI * self.n_cycles,
             Envelope::Flattop => consts::PI * (self.n_cycles + 1.0),
-            Envelope::Gaussian => 6.0 * consts::PI * self.n_cycles, // = 3 omega tau
+            Envelope::Gaussian => self.gaussian_extent().unwrap_or(6.0 * consts::PI * self.n_cycles), // = 3 omega tau
         };
-        phase < max_phase
+        phase < self.padding * max_phase
     }
 
     /// Advances particle position and momentum using a leapfrog method
     /// in proper time. As a consequence, the change in the time may not
     /// be identical to the requested `dt`.
     fn push(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+        // neutral particles are unaffected by the field
+        if rqm == 0.0 {
+            let r = r + SPEED_OF_LIGHT * u * dt / u[0];
+            return (r, u, dt, 0.0);
+        }
+
         // equations of motion are:
         //   du/dtau = c grad<a^2>(r) / 2 = f(r)
         //   dr/dtau = c u
@@ -248,7 +362,20 @@ impl Field for PlaneWave {
         (r, u, dt_actual, dwork)
     }
 
-    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode) -> Option<RadiationEvent> {
+    /// The ponderomotive solver is always a leapfrog in proper time,
+    /// so `scheme` is ignored.
+    fn push_with_scheme(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion, _scheme: PushScheme) -> (FourVector, FourVector, f64, f64) {
+        self.push(r, u, rqm, dt, eqn)
+    }
+
+    /// Returns the cycle-averaged rate at which the field does work,
+    /// neglecting radiation reaction.
+    fn power(&self, r: FourVector, u: FourVector, rqm: f64) -> f64 {
+        let scale = (rqm / (ELECTRON_CHARGE / ELECTRON_MASS)).powi(2);
+        0.5 * SPEED_OF_LIGHT * scale * self.grad_a_sqd(r)[0] / u[0]
+    }
+
+    fn radiate<R: Rng>(&self, r: FourVector, u: FourVector, dt: f64, rng: &mut R, mode: RadiationMode, max_recoil: f64) -> Option<RadiationEvent> {
         let a = self.a_sqd(r).sqrt();
         let phase = self.wavevector * r;
         let chirp = if cfg!(feature = "compensating-chirp") {
@@ -268,14 +395,16 @@ impl Field for PlaneWave {
             let (n, k, pol) = nonlinear_compton::generate(kappa, u, self.pol, self.pol_angle, mode, rng);
             // u' is ignored if recoil is disabled, so we may as well calculate it
             let event = RadiationEvent {
+                r,
                 k,
                 u_prime: u + (n as f64) * kappa - k,
                 pol,
                 a_eff: a,
                 chi: a * (u * kappa),
                 absorption: (n as f64) * kappa[0],
+                recoil_capped: false,
             };
-            Some(event)
+            Some(event.with_recoil_cap(u, max_recoil))
         } else {
             None
         }
@@ -317,11 +446,40 @@ impl Field for PlaneWave {
 
     fn ideal_initial_z(&self) -> f64 {
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
-        match self.envelope {
+        let z0 = match self.envelope {
             Envelope::CosSquared => 0.5 * wavelength * self.n_cycles,
             Envelope::Flattop => 0.5 * wavelength * (self.n_cycles + 1.0),
-            Envelope::Gaussian => 2.0 * wavelength * self.n_cycles,
-        }
+            Envelope::Gaussian => self.gaussian_extent().map_or(2.0 * wavelength * self.n_cycles, |phase| 0.5 * phase / self.wavevector[0]),
+        };
+        self.padding * z0
+    }
+
+    fn local_a0(&self, r: FourVector) -> f64 {
+        // a_sqd is the cycle-average, a0^2 / 2 for LP
+        let norm = match self.pol {
+            Polarization::Linear => 2.0,
+            Polarization::Circular => 1.0,
+        };
+        (norm * self.a_sqd(r)).sqrt()
+    }
+
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        let a = self.a_sqd(r).sqrt();
+        let chirp = if cfg!(feature = "compensating-chirp") {
+            1.0 + self.chirp_b * a * a
+        } else {
+            1.0 + 2.0 * self.chirp_b * (self.wavevector * r)
+        };
+        let kappa = SPEED_OF_LIGHT * COMPTON_TIME * self.wavevector * chirp;
+        a * (ell * kappa)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        Some(SPEED_OF_LIGHT * self.wavevector[0])
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        Some(self.a0)
     }
 
     fn energy(&self) -> (f64, &'static str) {
@@ -329,12 +487,14 @@ impl Field for PlaneWave {
         let wavelength = 2.0 * consts::PI / self.wavevector[0];
         FastPlaneWave::new(self.a0, wavelength, self.n_cycles, self.pol, 0.0, self.chirp_b)
             .with_envelope(self.envelope)
+            .with_beat_modulation(self.beat.0, self.beat.1)
             .energy()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand_xoshiro::*;
     use super::*;
 
     #[test]
@@ -415,4 +575,161 @@ mod tests {
 
         assert!(error < 1.0e-3);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn beat_modulation() {
+        let wavelength = 0.8e-6;
+        let omega = 2.0 * consts::PI * SPEED_OF_LIGHT / wavelength;
+        let depth = 0.2;
+        let beat_freq = omega / (2.0 * consts::PI * 4.0); // period of four cycles
+        let laser = PlaneWave::new(10.0, wavelength, 20.0, Polarization::Circular, 0.0, 0.0)
+            .with_envelope(Envelope::Flattop)
+            .with_beat_modulation(depth, beat_freq);
+        let k0 = laser.k()[0];
+
+        // scan over the flat-top region
+        let (mut min, mut max) = (std::f64::INFINITY, 0.0_f64);
+        for i in 0..=2000 {
+            let phase = -8.0 * consts::PI + 16.0 * consts::PI * (i as f64) / 2000.0;
+            let r = FourVector::new(phase / k0, 0.0, 0.0, 0.0);
+            let a_sqd = laser.a_sqd(r);
+            min = min.min(a_sqd);
+            max = max.max(a_sqd);
+        }
+
+        let expected = ((1.0 + depth) / (1.0 - depth)).powi(2);
+        let error = (max / min - expected).abs() / expected;
+        println!("max/min intensity = {:.6e}, expected {:.6e}, error = {:.3e}", max / min, expected, error);
+        assert!(error < 1.0e-3);
+
+        // gradient must be consistent with the modulated intensity
+        let r = FourVector::new(0.3 / k0, 0.0, 0.0, 0.0);
+        let h = 1.0e-4 / k0;
+        let numerical = (laser.a_sqd(r + FourVector::new(h, 0.0, 0.0, 0.0)) - laser.a_sqd(r - FourVector::new(h, 0.0, 0.0, 0.0))) / (2.0 * h);
+        let analytical = laser.grad_a_sqd(r)[0];
+        let error = (numerical - analytical).abs() / analytical.abs();
+        println!("d<a^2>/dt = {:.6e} [numerical], {:.6e} [analytical], error = {:.3e}", numerical, analytical, error);
+        assert!(error < 1.0e-4);
+    }
+
+    #[test]
+    fn a0_field_round_trip() {
+        let wavelength = 0.8e-6;
+
+        // a0 = 1 at 0.8 um corresponds to E = 4.013 TV/m
+        let laser = PlaneWave::new(1.0, wavelength, 8.0, Polarization::Linear, 0.0, 0.0);
+        let e_peak = laser.peak_field_v_per_m();
+        let error = (e_peak - 4.013e12).abs() / 4.013e12;
+        println!("a0 = 1 => E = {:.4e} V/m, error = {:.3e}", e_peak, error);
+        assert!(error < 1.0e-3);
+
+        for a0 in [0.1, 1.0, 10.0, 100.0].iter() {
+            let laser = PlaneWave::new(*a0, wavelength, 8.0, Polarization::Circular, 0.0, 0.0);
+            let e_peak = laser.peak_field_v_per_m();
+            let a0_again = PlaneWave::a0_from_field(e_peak, wavelength);
+            let error = (a0_again - a0).abs() / a0;
+            println!("a0 = {:.3e} => E = {:.4e} V/m => a0 = {:.3e}, error = {:.3e}", a0, e_peak, a0_again, error);
+            assert!(error < 1.0e-12);
+        }
+    }
+
+    #[test]
+    fn harmonic_filter() {
+        use crate::field::HarmonicFilter;
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let wavelength = 0.8e-6;
+        let a0 = 0.5;
+        let laser = PlaneWave::new(a0, wavelength, 100.0, Polarization::Circular, 0.0, 0.0)
+            .with_envelope(Envelope::Flattop);
+        let kappa = SPEED_OF_LIGHT * COMPTON_TIME * laser.k();
+
+        // quasimomentum at the centre of the pulse
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).with_sqr(1.0 + laser.a_sqd(r));
+        let eta = kappa * u;
+
+        // a very large dt guarantees emission
+        let events: Vec<RadiationEvent> = (0..10_000)
+            .filter_map(|_| laser.radiate(r, u, 1.0, &mut rng, RadiationMode::Quantum, 1.0))
+            .collect();
+        assert_eq!(events.len(), 10_000);
+
+        let filter = HarmonicFilter::new(wavelength, 1, 1);
+        let mut accepted = 0;
+        let mut counts = [0; 4];
+        for event in events.iter() {
+            // kinematic harmonic agrees with the number of photons absorbed
+            let n = event.harmonic(kappa);
+            let n_true = event.absorption / kappa[0];
+            assert!((n - n_true).abs() < 1.0e-6);
+
+            // photons lie below the kinematic edge of their harmonic
+            let s = (kappa * event.k) / eta;
+            let s_max = 2.0 * n_true * eta / (1.0 + laser.a_sqd(r) + 2.0 * n_true * eta);
+            assert!(s <= s_max * (1.0 + 1.0e-9));
+
+            let n = n_true.round() as usize;
+            if n < counts.len() {
+                counts[n] += 1;
+            }
+            if filter.accepts(event) {
+                accepted += 1;
+            }
+        }
+
+        println!("harmonic counts = {:?}, accepted by n = 1 filter = {}", counts, accepted);
+        assert_eq!(accepted, counts[1]);
+        assert!(counts[1] > counts[2] && counts[2] > 0);
+    }
+
+    #[test]
+    fn light_cone_time() {
+        let wavelength = 0.8e-6;
+        let laser = PlaneWave::new(10.0, wavelength, 8.0, Polarization::Circular, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let period = wavelength / SPEED_OF_LIGHT;
+
+        // at fixed position, η advances by 2π per optical cycle
+        let r = FourVector::new(0.0, 1.0e-6, 0.0, 0.3e-6);
+        let r_next = r + FourVector::new(SPEED_OF_LIGHT * period, 0.0, 0.0, 0.0);
+        let delta = laser.phase(r_next) - laser.phase(r);
+        println!("over one period, eta advances by 2 pi x {:.6}", delta / (2.0 * consts::PI));
+        assert!((delta - 2.0 * consts::PI).abs() < 1.0e-9);
+        assert!((laser.light_cone_phase(r).unwrap() - laser.phase(r)).abs() < 1.0e-9);
+
+        // and monotonically along the trajectory of a counterpropagating electron,
+        // at a rate set by the light-front momentum
+        let mut u = FourVector::new(0.0, 0.0, 0.0, -100.0).unitize();
+        let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+        let mut eta = laser.phase(r);
+        let mut steps = 0;
+        assert!(eta < -2.0 * consts::PI);
+        while laser.contains(r) {
+            let (r_new, u_new, dt_actual, _) = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            let eta_new = laser.phase(r_new);
+            // dη/dt = ω (1 - v_z / c), where u^- = γ - u_z is conserved
+            let expected = 2.0 * consts::PI * dt_actual * (u[0] - u[3]) / (period * u[0]);
+            assert!(eta_new > eta);
+            assert!(((eta_new - eta) - expected).abs() < 1.0e-2 * expected);
+            r = r_new;
+            u = u_new;
+            eta = eta_new;
+            steps += 1;
+        }
+
+        println!("after {} steps, eta = 2 pi x {:.3}", steps, eta / (2.0 * consts::PI));
+        assert!(steps > 0);
+        assert!(eta > 0.0);
+    }
+
+    #[test]
+    fn circular_polarization_angle() {
+        let laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Circular, 0.0, 0.0)
+            .with_polarization_angle(0.3);
+        assert_eq!(laser.pol_angle, 0.0);
+        let laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0)
+            .with_polarization_angle(0.3);
+        assert_eq!(laser.pol_angle, 0.3);
+    }
+}
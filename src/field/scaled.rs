@@ -0,0 +1,125 @@
+//! Uniform rescaling of the amplitude of a field
+
+use crate::geometry::{FourVector, ThreeVector};
+use super::{Field, Laser};
+
+/// Wraps a field, multiplying its electric and magnetic fields
+/// (and therefore its normalized amplitude) by a constant factor.
+///
+/// Only the locally constant field defaults of `push`, `radiate`
+/// and `pair_create` are affected, so the inner field must define
+/// `fields()`, e.g. `FastPlaneWave` and `FastFocusedLaser`. Wrapping a
+/// cycle-averaged field (see [Field::is_cycle_averaged]) panics.
+pub struct ScaledField<F: Field> {
+    inner: F,
+    factor: f64,
+}
+
+impl<F: Field> ScaledField<F> {
+    #[allow(unused)]
+    pub fn new(inner: F, factor: f64) -> Self {
+        assert!(!inner.is_cycle_averaged(), "cannot rescale a cycle-averaged field");
+        ScaledField {
+            inner,
+            factor,
+        }
+    }
+
+    #[allow(unused)]
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    pub(super) fn set_factor(&mut self, factor: f64) {
+        self.factor = factor;
+    }
+}
+
+impl Laser {
+    /// Returns the laser with all field amplitudes multiplied by `factor`,
+    /// and therefore its energy by `factor^2`.
+    #[allow(unused)]
+    pub fn scaled(self, factor: f64) -> ScaledField<Laser> {
+        ScaledField::new(self, factor)
+    }
+}
+
+impl<F: Field> Field for ScaledField<F> {
+    fn max_timestep(&self) -> Option<f64> {
+        // stronger fields need to be resolved more finely
+        self.inner.max_timestep().map(|dt| dt / self.factor.abs().max(1.0))
+    }
+
+    fn contains(&self, r: FourVector) -> bool {
+        self.inner.contains(r)
+    }
+
+    fn ideal_initial_z(&self) -> f64 {
+        self.inner.ideal_initial_z()
+    }
+
+    #[allow(non_snake_case)]
+    fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+        let (E, B, a) = self.inner.fields(r);
+        (self.factor * E, self.factor * B, self.factor.abs() * a)
+    }
+
+    fn local_a0(&self, r: FourVector) -> f64 {
+        self.factor.abs() * self.inner.local_a0(r)
+    }
+
+    fn photon_quantum_parameter(&self, r: FourVector, ell: FourVector) -> f64 {
+        self.factor.abs() * self.inner.photon_quantum_parameter(r, ell)
+    }
+
+    fn carrier_frequency(&self) -> Option<f64> {
+        self.inner.carrier_frequency()
+    }
+
+    fn nominal_a0(&self) -> Option<f64> {
+        self.inner.nominal_a0().map(|a0| self.factor.abs() * a0)
+    }
+
+    fn energy(&self) -> (f64, &'static str) {
+        let (energy, unit) = self.inner.energy();
+        (self.factor.powi(2) * energy, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::field::{FastPlaneWave, PlaneWave, Polarization};
+    use super::*;
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn scaled_plane_wave() {
+        let factor = 2.5;
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let (energy, _) = laser.energy();
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.1e-6);
+        let (E, _, a) = laser.fields(r);
+
+        let scaled = laser.scaled(factor);
+        let (scaled_energy, _) = scaled.energy();
+        let (scaled_E, _, scaled_a) = scaled.fields(r);
+
+        let field_error = (scaled_E[0] / E[0] - factor).abs() / factor;
+        let amplitude_error = (scaled_a / a - factor).abs() / factor;
+        let energy_error = (scaled_energy / energy - factor.powi(2)).abs() / factor.powi(2);
+        println!(
+            "E_x ratio = {:.6e}, a ratio = {:.6e}, energy ratio = {:.6e}, expected {:.6e} and {:.6e}",
+            scaled_E[0] / E[0], scaled_a / a, scaled_energy / energy, factor, factor.powi(2)
+        );
+        assert!(field_error < 1.0e-12);
+        assert!(amplitude_error < 1.0e-12);
+        assert!(energy_error < 1.0e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn scaled_averaged_laser() {
+        let laser: Laser = PlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let _ = laser.scaled(2.5);
+    }
+}
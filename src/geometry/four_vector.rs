@@ -60,6 +60,12 @@ impl FourVector {
         self * self
     }
 
+    /// Returns the spatial components of the four-vector
+    #[allow(unused)]
+    pub fn spatial(self) -> ThreeVector {
+        ThreeVector::from(self)
+    }
+
     /// Returns the equivalent four vector in a new inertial frame,
     /// which is travelling with four-velocity `u` with respect to
     /// the current frame.
@@ -84,6 +90,21 @@ impl FourVector {
         FourVector {0: self.0, 1: -self.1, 2: -self.2, 3: -self.3}
     }
 
+    /// Returns the invariant interval between `self` and `other`, i.e.
+    /// (Δt)^2 - |Δx|^2, which is positive for timelike separations,
+    /// negative for spacelike separations, and zero for lightlike separations.
+    #[allow(unused)]
+    pub fn invariant_interval(&self, other: FourVector) -> f64 {
+        (*self - other).norm_sqr()
+    }
+
+    /// Returns true if `self`, treated as a four-position, lies on or within
+    /// the future light cone of `other`, i.e. if `other` can causally influence it.
+    #[allow(unused)]
+    pub fn is_future_of(&self, other: FourVector) -> bool {
+        self.0 > other.0 && self.invariant_interval(other) >= 0.0
+    }
+
     /// Rotates the spatial components of `self` around the y-axis by `theta`
     /// and returns the result.
     pub fn rotate_around_y(self, theta: f64) -> Self {
@@ -256,4 +277,30 @@ mod tests {
         let a = FourVector::lightlike(1.0, -17.0, 2.6);
         assert!(a.norm_sqr().abs() < 1.0e-10);
     }
+
+    #[test]
+    fn causal_ordering() {
+        let origin = FourVector::new(0.0, 0.0, 0.0, 0.0);
+
+        // timelike
+        let a = FourVector::new(5.0, 1.0, 2.0, 0.0);
+        assert!(a.invariant_interval(origin) > 0.0);
+        assert!(a.is_future_of(origin) && !origin.is_future_of(a));
+
+        // spacelike: neither can influence the other
+        let b = FourVector::new(1.0, 3.0, 0.0, -2.0);
+        assert!(b.invariant_interval(origin) < 0.0);
+        assert!(!b.is_future_of(origin) && !origin.is_future_of(b));
+
+        // lightlike, on the cone
+        let c = FourVector::new(5.0, 3.0, 4.0, 0.0);
+        assert_eq!(c.invariant_interval(origin), 0.0);
+        assert!(c.is_future_of(origin) && !origin.is_future_of(c));
+
+        // the interval is symmetric and translation invariant
+        let d = FourVector::new(-2.0, 1.0, 0.5, 7.0);
+        assert_eq!(a.invariant_interval(b), b.invariant_interval(a));
+        assert!((a.invariant_interval(b) - (a + d).invariant_interval(b + d)).abs() < 1.0e-12);
+        assert!(!(a + d).is_future_of(a + d));
+    }
 }
@@ -238,6 +238,18 @@ impl std::ops::Add for StokesVector {
     }
 }
 
+impl std::ops::Mul<StokesVector> for f64 {
+    type Output = StokesVector;
+    fn mul(self, other: StokesVector) -> StokesVector {
+        StokesVector {
+            i: self * other.i,
+            q: self * other.q,
+            u: self * other.u,
+            v: self * other.v,
+        }
+    }
+}
+
 impl std::ops::Div<f64> for StokesVector {
     type Output = Self;
     fn div(self, other: f64) -> Self {
@@ -229,4 +229,32 @@ mod tests {
         let target = ThreeVector::new(0.0, 0.0, 1.0);
         assert!((v - target).norm_sqr().sqrt() < 1.0e-10);
     }
+
+    #[test]
+    fn cross_product_identities() {
+        let a = FourVector::new(7.0, 1.0, -2.0, 0.5).spatial();
+        let b = FourVector::new(-3.0, 0.3, 4.0, -1.5).spatial();
+        let c = ThreeVector::new(2.0, 0.7, -0.2);
+        assert_eq!(a, ThreeVector::new(1.0, -2.0, 0.5));
+
+        // a x b is orthogonal to both a and b
+        let axb = a.cross(b);
+        assert!((axb * a).abs() < 1.0e-12 && (axb * b).abs() < 1.0e-12);
+
+        // anticommutativity, and a x a = 0
+        assert!((axb + b.cross(a)).norm_sqr() < 1.0e-24);
+        assert!(a.cross(a).norm_sqr() == 0.0);
+
+        // a x (b x c) = b (a.c) - c (a.b)
+        let lhs = a.cross(b.cross(c));
+        let rhs = (a * c) * b - (a * b) * c;
+        assert!((lhs - rhs).norm_sqr().sqrt() < 1.0e-12);
+
+        // scalar triple product is cyclic
+        let abc = a * b.cross(c);
+        assert!((abc - b * c.cross(a)).abs() < 1.0e-12 && (abc - c * a.cross(b)).abs() < 1.0e-12);
+
+        // Lagrange's identity, |a x b|^2 = |a|^2 |b|^2 - (a.b)^2
+        assert!((axb.norm_sqr() - (a.norm_sqr() * b.norm_sqr() - (a * b).powi(2))).abs() < 1.0e-12);
+    }
 }
@@ -23,11 +23,9 @@ use rand_xoshiro::*;
 #[cfg(feature = "hdf5-output")]
 use hdf5_writer;
 #[cfg(feature = "hdf5-output")]
-unzip_n::unzip_n!(pub 7);
+use hdf5_writer::GroupHolder;
 #[cfg(feature = "hdf5-output")]
-unzip_n::unzip_n!(pub 9);
-#[cfg(feature = "hdf5-output")]
-unzip_n::unzip_n!(pub 10);
+unzip_n::unzip_n!(pub 11);
 
 mod constants;
 mod field;
@@ -41,6 +39,9 @@ mod output;
 mod input;
 mod pwmci;
 mod quadrature;
+mod propagate;
+mod simulation;
+mod recording_rng;
 
 use constants::*;
 use field::*;
@@ -67,6 +68,33 @@ enum StopAt {
     Automatic,
 }
 
+/// Upper bound on the quantum parameter χ, beyond which the
+/// simulation is likely to be unphysical
+#[derive(Copy, Clone, PartialEq)]
+enum ChiLimit {
+    /// No bound is applied.
+    Unlimited,
+    /// Warn at the end of the run if χ exceeded the given value.
+    Warn(f64),
+    /// Stop tracking and abort the run as soon as χ exceeds the given value.
+    Abort(f64),
+}
+
+impl ChiLimit {
+    /// Returns true if `chi` exceeds the bound.
+    fn exceeded_by(&self, chi: f64) -> bool {
+        match self {
+            ChiLimit::Unlimited => false,
+            ChiLimit::Warn(max) | ChiLimit::Abort(max) => chi > *max,
+        }
+    }
+
+    /// Returns true if `chi` exceeds the bound and the run should be aborted.
+    fn aborts_at(&self, chi: f64) -> bool {
+        matches!(self, ChiLimit::Abort(_)) && self.exceeded_by(chi)
+    }
+}
+
 /// Wrapper around detailed optional arguments to [collide](collide)
 #[derive(Copy, Clone)]
 struct CollideOptions {
@@ -94,6 +122,22 @@ struct CollideOptions {
     classical: bool,
     /// Correct classical spectrum using Gaunt factor
     gaunt_factor: bool,
+    /// Check χ against an upper bound at every emission or pair creation event
+    chi_limit: ChiLimit,
+    /// Limit the fraction of its energy that an electron or positron
+    /// can lose to a single photon
+    max_recoil: f64,
+    /// Do not record or track photons with less than this fraction of the
+    /// energy of the emitting particle (recoil is unaffected)
+    min_photon_fraction: f64,
+    /// Discard primary electrons and positrons whose momentum has changed
+    /// by less than this fraction of its initial magnitude
+    min_momentum_change: f64,
+    /// Equation of motion for each species (indexed by `Species as usize`),
+    /// overriding the one that follows from `rr` and `classical`
+    species_eqn: [Option<EquationOfMotion>; 3],
+    /// Reflect particles that cross this plane, rather than letting them pass
+    reflecting_plane: Option<propagate::ReflectingPlane>,
 }
 
 /// Type of diagnostic message that can be issued
@@ -120,9 +164,237 @@ macro_rules! report {
 
 pub(crate) use report;
 
+/// Returns the PRNG for the task with given `id`, seeded by `rng_seed`.
+/// Different tasks use non-overlapping subsequences.
+fn task_rng(rng_seed: usize, id: i32) -> Xoshiro256StarStar {
+    let seed = 0x8658b90036b165ebu64 + ((rng_seed as u64) * 0x32f55cddaebae910u64);
+    let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
+    for _i in 0..id {
+        rng.jump();
+    }
+    rng
+}
+
+/// Returns the PRNG used to sample the beam for the task with given `id`,
+/// seeded by `beam_rng_seed`. This is long-jumped ahead of the sequence
+/// returned by [task_rng](task_rng), so that the beam and the QED processes
+/// are uncorrelated even if the two seeds are equal.
+fn beam_task_rng(beam_rng_seed: usize, id: i32) -> Xoshiro256StarStar {
+    let mut rng = task_rng(beam_rng_seed, id);
+    rng.long_jump();
+    rng
+}
+
+/// Records the energy of the laser pulse, and its photon number
+/// (if defined), as attributes of the given group or file.
+/// Must be called by all tasks.
+#[cfg(feature = "hdf5-output")]
+fn write_laser_metadata<C, G, F>(group: &G, laser: &F) -> Result<(), hdf5_writer::OutputError>
+where C: Communicator, G: hdf5_writer::GroupHolder<C>, F: Field {
+    let (energy, unit) = laser.energy();
+    group.write_attribute("laser_energy", &energy)?
+        .write_string_attribute("laser_energy_unit", unit)?;
+
+    if let Some((number, unit)) = laser.photon_number() {
+        group.write_attribute("laser_photon_number", &number)?
+            .write_string_attribute("laser_photon_number_unit", unit)?;
+    }
+
+    Ok(())
+}
+
+/// The datasets that hold the final- (or intermediate-) state particles of
+/// a particular species, which are extended as each chunk of primaries is completed.
+#[cfg(feature = "hdf5-output")]
+struct SpeciesDatasets<'a, C> where C: Communicator {
+    weight: hdf5_writer::AppendableDataset<'a, f64, C>,
+    a0_at_creation: Option<hdf5_writer::AppendableDataset<'a, f64, C>>,
+    parent_chi: Option<hdf5_writer::AppendableDataset<'a, f64, C>>,
+    interaction_count: hdf5_writer::AppendableDataset<'a, f64, C>,
+    id: hdf5_writer::AppendableDataset<'a, u64, C>,
+    parent_id: hdf5_writer::AppendableDataset<'a, u64, C>,
+    polarization: Option<hdf5_writer::AppendableDataset<'a, StokesVector, C>>,
+    absorption: Option<hdf5_writer::AppendableDataset<'a, f64, C>>,
+    position: hdf5_writer::AppendableDataset<'a, FourVector, C>,
+    position_at_creation: Option<hdf5_writer::AppendableDataset<'a, FourVector, C>>,
+    momentum: hdf5_writer::AppendableDataset<'a, FourVector, C>,
+}
+
+#[cfg(feature = "hdf5-output")]
+impl<'a, C> SpeciesDatasets<'a, C> where C: Communicator {
+    /// Number of particles stored in each chunk of the HDF5 datasets
+    const CHUNK_SIZE: usize = 8192;
+
+    /// Creates empty datasets, for particles of the given species, in `group`.
+    /// Must be called by all tasks.
+    fn create_in<G>(group: &'a G, species: Species, single_precision: bool, units: &UnitSystem) -> Result<Self, hdf5_writer::OutputError>
+    where G: GroupHolder<C> {
+        let name = species.to_string();
+        let event = if species == Species::Photon { "emission" } else { "creation" };
+        let created = species != Species::Electron;
+
+        let weight = group.new_dataset("weight")?
+            .with_single_precision(single_precision)
+            .with_unit("1")?
+            .with_desc(&format!("number of real {}s each macro{} represents", name, name))?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        let a0_at_creation = if created {
+            let ds = group.new_dataset("a0_at_creation")?
+                .with_single_precision(single_precision)
+                .with_unit("1")?
+                .with_desc(&format!("normalized amplitude (RMS under LMA) at point of {}", event))?
+                .with_alias("xi")?
+                .appendable(Self::CHUNK_SIZE)?;
+            Some(ds)
+        } else {
+            None
+        };
+
+        let parent_chi = if created {
+            let ds = group.new_dataset("parent_chi")?
+                .with_single_precision(single_precision)
+                .with_unit("1")?
+                .with_desc(&format!("quantum parameter (RMS under LMA) of parent at point of {}", event))?
+                .appendable(Self::CHUNK_SIZE)?;
+            Some(ds)
+        } else {
+            None
+        };
+
+        let interaction_count = if species == Species::Photon {
+            group.new_dataset("n_pos")?
+                .with_desc("total probability of pair creation for the photon")?
+        } else {
+            group.new_dataset("n_gamma")?
+                .with_desc(&format!("total number of photons emitted by the {}", name))?
+        };
+
+        let interaction_count = interaction_count
+            .with_single_precision(single_precision)
+            .with_unit("1")?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        let id = group.new_dataset("id")?
+            .with_desc(&format!("unique ID of the {}", name))?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        let parent_id = group.new_dataset("parent_id")?
+            .with_desc(&format!("ID of the particle that created the {} (for primary particles, parent_id = id)", name))?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        let polarization = if species == Species::Photon {
+            let ds = group.new_dataset("polarization")?
+                .with_single_precision(single_precision)
+                .with_desc("Stokes parameters of the photon: I, Q, U, V")?
+                .with_unit("1")?
+                .with_alias("polarisation")?
+                .appendable(Self::CHUNK_SIZE)?;
+            Some(ds)
+        } else {
+            None
+        };
+
+        let absorption = if species != Species::Photon {
+            let ds = group.new_dataset("absorption")?
+                .with_single_precision(single_precision)
+                .with_unit(units.energy.name())?
+                .with_desc("energy absorbed from the laser")?
+                .appendable(Self::CHUNK_SIZE)?;
+            Some(ds)
+        } else {
+            None
+        };
+
+        let position = group.new_dataset("position")?
+            .with_single_precision(single_precision)
+            .with_unit(units.length.name())?
+            .with_desc(&format!("four-position of the {}", name))?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        let position_at_creation = if species == Species::Positron {
+            let ds = group.new_dataset("position_at_creation")?
+                .with_single_precision(single_precision)
+                .with_unit(units.length.name())?
+                .with_desc("four-position at which the positron was created")?
+                .appendable(Self::CHUNK_SIZE)?;
+            Some(ds)
+        } else {
+            None
+        };
+
+        let momentum = group.new_dataset("momentum")?
+            .with_single_precision(single_precision)
+            .with_unit(units.momentum.name())?
+            .with_desc(&format!("four-momentum of the {}", name))?
+            .appendable(Self::CHUNK_SIZE)?;
+
+        Ok(Self {
+            weight,
+            a0_at_creation,
+            parent_chi,
+            interaction_count,
+            id,
+            parent_id,
+            polarization,
+            absorption,
+            position,
+            position_at_creation,
+            momentum,
+        })
+    }
+
+    /// Appends the data of the given particles to the datasets.
+    /// Must be called by all tasks, even if they have no particles to write.
+    fn append(&mut self, particles: &[Particle], units: &UnitSystem) -> Result<(), hdf5_writer::OutputError> {
+        let (x, x0, p, pol, w, a, chi, n, abs, id, pid) = particles
+            .iter()
+            .map(|pt| (
+                pt.position().convert(&units.length),
+                pt.was_created_at().convert(&units.length),
+                pt.momentum().convert(&units.momentum),
+                pt.polarization(),
+                pt.weight(),
+                pt.payload(),
+                pt.parent_chi(),
+                pt.interaction_count(),
+                pt.absorbed_energy().convert(&units.energy),
+                pt.id(),
+                pt.parent_id(),
+            ))
+            .unzip_n_vec();
+
+        self.weight.append(&w)?;
+        if let Some(ds) = self.a0_at_creation.as_mut() {
+            ds.append(&a)?;
+        }
+        if let Some(ds) = self.parent_chi.as_mut() {
+            ds.append(&chi)?;
+        }
+        self.interaction_count.append(&n)?;
+        self.id.append(&id)?;
+        self.parent_id.append(&pid)?;
+        if let Some(ds) = self.polarization.as_mut() {
+            ds.append(&pol)?;
+        }
+        if let Some(ds) = self.absorption.as_mut() {
+            ds.append(&abs)?;
+        }
+        self.position.append(&x)?;
+        if let Some(ds) = self.position_at_creation.as_mut() {
+            ds.append(&x0)?;
+        }
+        self.momentum.append(&p)?;
+
+        Ok(())
+    }
+}
+
 /// Propagates a single particle through a region of EM field, returning a Shower containing
 /// the primary and any secondary particles generated.
 /// `current_id` is incremented every time a new particle is generated.
+/// If `options.chi_limit` is set to abort, tracking stops as soon as any
+/// particle in the shower exceeds it, and the incomplete shower is returned.
 fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current_id: &mut u64, options: CollideOptions) -> Shower {
     let mut primaries = vec![incident];
     let mut secondaries: Vec<Particle> = Vec::new();
@@ -132,7 +404,7 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
     let dt = dt * options.dt_multiplier;
     let primary_id = incident.id();
 
-    let eqn = if options.classical && options.rr {
+    let default_eqn = if options.classical && options.rr {
         if options.gaunt_factor {
             EquationOfMotion::ModifiedLandauLifshitz
         } else {
@@ -149,10 +421,13 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
     };
 
     let electron_recoils = !options.classical && options.rr;
+    let mut max_chi: f64 = 0.0;
+    let mut capped_emissions = 0;
 
     while let Some(mut pt) = primaries.pop() {
         match pt.species() {
             Species::Electron | Species::Positron => {
+                let eqn = options.species_eqn[pt.species() as usize].unwrap_or(default_eqn);
                 while field.contains(pt.position()) && pt.time() < options.t_stop {
                     let (r, mut u, dt_actual, work_done) = field.push(
                         pt.position(),
@@ -162,18 +437,17 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                         eqn,
                     );
 
-                    if let Some(event) = field.radiate(r, u, dt_actual, rng, mode) {
-                        let id = *current_id;
-                        *current_id = *current_id + 1;
-                        let photon = Particle::create(Species::Photon, r)
-                            .with_payload(event.a_eff)
-                            .with_parent_chi(event.chi)
-                            .with_weight(pt.weight())
-                            .with_id(id)
-                            .with_parent_id(pt.id())
-                            .with_polarization(event.pol)
-                            .with_normalized_momentum(event.k);
-                        primaries.push(photon);
+                    if let Some(event) = field.radiate(r, u, dt_actual, rng, mode, options.max_recoil) {
+                        max_chi = max_chi.max(event.chi);
+                        if event.recoil_capped {
+                            capped_emissions += 1;
+                        }
+                        if event.k[0] >= options.min_photon_fraction * u[0] {
+                            let id = *current_id;
+                            *current_id = *current_id + 1;
+                            let photon = propagate::photon_from_event(&event, &pt).with_id(id);
+                            primaries.push(photon);
+                        }
 
                         if electron_recoils {
                             u = event.u_prime;
@@ -189,9 +463,26 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                     pt.with_position(r);
                     pt.with_normalized_momentum(u);
                     pt.update_absorbed_energy(work_done);
+
+                    if let Some(plane) = options.reflecting_plane.as_ref() {
+                        plane.reflect(&mut pt);
+                    }
+
+                    if options.chi_limit.aborts_at(max_chi) {
+                        break;
+                    }
                 }
 
-                if pt.id() != primary_id || !options.discard_bg_e || pt.interaction_count() > 0.0 {
+                let interacted = if pt.id() != primary_id {
+                    true
+                } else {
+                    let u0 = ThreeVector::from(incident.normalized_momentum());
+                    let du = ThreeVector::from(pt.normalized_momentum()) - u0;
+                    (!options.discard_bg_e || pt.interaction_count() > 0.0)
+                        && du.norm_sqr() >= options.min_momentum_change.powi(2) * u0.norm_sqr()
+                };
+
+                if interacted {
                     secondaries.push(pt);
                 }
             },
@@ -206,9 +497,10 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                     let (prob, pol_new, event) = field.pair_create(r, ell, pol, dt, rng, options.rate_increase);
 
                     if let Some(event) = event {
+                        max_chi = max_chi.max(event.chi);
                         let id = *current_id;
                         *current_id = *current_id + 2;
-                        let electron = Particle::create(Species::Electron, r)
+                        let mut electron = Particle::create(Species::Electron, r)
                             .with_weight(event.frac * pt.weight())
                             .with_id(id)
                             .with_payload(event.a_eff)
@@ -216,7 +508,7 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                             .with_parent_id(pt.id())
                             .update_absorbed_energy(0.5 * event.absorption)
                             .with_normalized_momentum(event.u_e);
-                        let positron = Particle::create(Species::Positron, r)
+                        let mut positron = Particle::create(Species::Positron, r)
                             .with_weight(event.frac * pt.weight())
                             .with_id(id + 1)
                             .with_payload(event.a_eff)
@@ -224,6 +516,10 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                             .with_parent_id(pt.id())
                             .update_absorbed_energy(0.5 * event.absorption)
                             .with_normalized_momentum(event.u_p);
+                        if pt.is_probe() {
+                            electron.as_probe();
+                            positron.as_probe();
+                        }
                         primaries.push(electron);
                         primaries.push(positron);
                         pt.with_weight(pt.weight() * (1.0 - event.frac));
@@ -238,6 +534,14 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
 
                     pt.update_interaction_count(prob);
                     pt.with_position(r);
+
+                    if let Some(plane) = options.reflecting_plane.as_ref() {
+                        plane.reflect(&mut pt);
+                    }
+
+                    if options.chi_limit.aborts_at(max_chi) {
+                        break;
+                    }
                 }
 
                 if !has_decayed && (pt.id() != primary_id || !options.discard_bg_ph) {
@@ -249,12 +553,18 @@ fn collide<F: Field, R: Rng>(field: &F, incident: Particle, rng: &mut R, current
                 }
             }
         }
+
+        if options.chi_limit.aborts_at(max_chi) {
+            break;
+        }
     }
 
     Shower {
         primary: incident,
         secondaries,
         intermediates,
+        max_chi,
+        capped_emissions,
     }
 }
 
@@ -310,6 +620,7 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
     let multiplicity: Option<usize> = input.read("control:select_multiplicity").ok();
     let using_lcfa = input.read("control:lcfa").unwrap_or(false);
     let rng_seed = input.read("control:rng_seed").unwrap_or(0usize);
+    let beam_rng_seed: Option<usize> = input.read("control:beam_rng_seed").ok();
     let finite_bandwidth = input.read("control:bandwidth_correction").unwrap_or(false);
     let rr = input.read("control:radiation_reaction").unwrap_or(true);
 
@@ -356,6 +667,57 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
         })
         ?;
 
+    // the equation of motion can be chosen for electrons and positrons separately
+    let mut species_eqn: [Option<EquationOfMotion>; 3] = [None; 3];
+    for species in [Species::Electron, Species::Positron].iter() {
+        let path = format!("control:equation_of_motion:{}", species);
+        species_eqn[*species as usize] = input.read::<String, _>(&path)
+            .and_then(|s| match s.as_str() {
+                "lorentz" => Ok(EquationOfMotion::Lorentz),
+                "landau_lifshitz" => Ok(EquationOfMotion::LandauLifshitz),
+                "modified_landau_lifshitz" => Ok(EquationOfMotion::ModifiedLandauLifshitz),
+                _ => {
+                    report!(Diagnostic::Error, id == 0, "{} must be one of 'lorentz', 'landau_lifshitz' or 'modified_landau_lifshitz'.", path);
+                    Err(InputError::conversion(&path, "equation_of_motion"))
+                }
+            })
+            .map(Some)
+            .or_else(|e| match e.kind() {
+                InputErrorKind::Location => Ok(None),
+                _ => Err(e),
+            })
+            ?;
+    }
+
+    // particles may be confined by a reflecting plane, given by its normal
+    // (pointing into the allowed region) and a point that lies on it
+    let reflecting_plane = {
+        let read_vector = |path: &str, name: &str| {
+            input.read::<Vec<f64>, _>(path)
+                .and_then(|v| match v.len() {
+                    3 => Ok(ThreeVector::new(v[0], v[1], v[2])),
+                    _ => {
+                        report!(Diagnostic::Error, id == 0, "{} must be expressed as a three-vector [x, y, z].", path);
+                        Err(InputError::conversion(path, name))
+                    }
+                })
+        };
+        match read_vector("control:reflecting_plane:normal", "normal") {
+            Ok(normal) if normal.norm_sqr() > 0.0 => {
+                let point = read_vector("control:reflecting_plane:point", "point")?;
+                Some(propagate::ReflectingPlane::new(normal, point))
+            },
+            Ok(_) => {
+                report!(Diagnostic::Error, id == 0, "control:reflecting_plane:normal must be non-zero.");
+                return Err(InputError::conversion("control:reflecting_plane:normal", "normal").into());
+            },
+            Err(e) => match e.kind() {
+                InputErrorKind::Location => None,
+                _ => return Err(e.into()),
+            },
+        }
+    };
+
     // pair creation is enabled by default, unless classical = true
     let tracking_photons = input.read("control:pair_creation").unwrap_or(!classical);
     let pol_resolved = input.read("control:pol_resolved").unwrap_or(false);
@@ -365,6 +727,42 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
             val
         });
 
+    let chi_limit = match input.read::<f64, _>("control:max_chi") {
+        Ok(max_chi) => input.read::<String, _>("control:max_chi_action")
+            .and_then(|s| match s.as_str() {
+                "abort" => Ok(ChiLimit::Abort(max_chi)),
+                "warn" => Ok(ChiLimit::Warn(max_chi)),
+                _ => {
+                    report!(Diagnostic::Error, id == 0, "control:max_chi_action must be one of 'abort' or 'warn'.");
+                    Err(InputError::conversion("control:max_chi_action", "max_chi_action"))
+                }
+            })
+            .or_else(|e| match e.kind() {
+                InputErrorKind::Conversion => Err(e),
+                _ => Ok(ChiLimit::Abort(max_chi)),
+            })?,
+        Err(e) => match e.kind() {
+            InputErrorKind::Conversion => return Err(e.into()),
+            _ => ChiLimit::Unlimited,
+        },
+    };
+
+    let max_recoil = input.read::<f64, _>("control:max_recoil")
+        .and_then(|f| if f > 0.0 && f <= 1.0 {
+            Ok(f)
+        } else {
+            report!(Diagnostic::Error, id == 0, "control:max_recoil must lie in the interval (0, 1].");
+            Err(InputError::conversion("control:max_recoil", "max_recoil"))
+        })
+        .or_else(|e| match e.kind() {
+            InputErrorKind::Conversion => Err(e),
+            _ => Ok(1.0),
+        })?;
+
+    let min_photon_fraction: f64 = input
+        .read("control:min_photon_fraction")
+        .unwrap_or(0.0);
+
     let a0_values: Vec<f64> = input.read_loop("laser:a0")?;
     let wavelength: f64 = input
         .read("laser:wavelength")
@@ -524,6 +922,19 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
             .with_min_energy(min_energy)
             .with_max_angle(max_angle);
 
+        // a missing path is fine, but a malformed one is not
+        let pol_path = input.read::<String, _>("beam:from_hdf5:polarization")
+            .map(Some)
+            .or_else(|e| match e.kind() {
+                InputErrorKind::Location => Ok(None),
+                _ => Err(e),
+            })?;
+
+        let loader = match pol_path {
+            Some(path) => loader.with_polarization_path(&path),
+            None => loader,
+        };
+
         BeamParameters::FromHdf5 { loader }
         }
     } else {
@@ -601,6 +1012,26 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                 }
             })?;
 
+        // alternatively, a normal distribution may be truncated independently in x and y
+        let max_xy = input.read::<Vec<f64>,_>("beam:max_xy")
+            .map(Some)
+            .or_else(|e| match e.kind() {
+                InputErrorKind::Location => Ok(None),
+                _ => Err(e),
+            })
+            .and_then(|v| match v {
+                Some(v) if v.len() != 2 => {
+                    report!(Diagnostic::Error, id == 0, "beam:max_xy must be specified as [x_max, y_max].");
+                    Err(InputError::conversion("beam:max_xy", "max_xy"))
+                },
+                Some(_) if !normally_distributed || max_radius.is_some() => {
+                    report!(Diagnostic::Error, id == 0, "beam:max_xy applies only to a normally distributed beam without a maximum radius.");
+                    Err(InputError::conversion("beam:max_xy", "max_xy"))
+                },
+                Some(v) => Ok(Some((v[0], v[1]))),
+                None => Ok(None),
+            })?;
+
         let energy_chirp = input.read::<f64, _>("beam:energy_chirp")
             .or_else(|e| match e.kind() {
                 InputErrorKind::Location => Ok(0.0),
@@ -664,7 +1095,9 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
 
         let builder = if normally_distributed {
             if let Some(r_max) = max_radius {
-                builder.with_trunc_normally_distributed_xy(radius, radius, r_max, r_max)
+                builder.with_radially_trunc_normally_distributed_xy(radius, r_max)
+            } else if let Some((x_max, y_max)) = max_xy {
+                builder.with_trunc_normally_distributed_xy(radius, radius, x_max, y_max)
             } else {
                 builder.with_normally_distributed_xy(radius, radius)
             }
@@ -725,6 +1158,10 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
     let keep_decayed_photons = input.read::<bool, _>("output:dump_decayed_photons")
         .unwrap_or(false);
 
+    #[cfg(feature = "hdf5-output")]
+    let single_precision = input.read::<bool, _>("output:single_precision")
+        .unwrap_or(false);
+
     let laser_defines_z = match input.read::<String,_>("output:coordinate_system") {
         Ok(s) if s == "beam" => false,
         _ => true,
@@ -749,6 +1186,14 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
         .read("output:max_angle")
         .unwrap_or(consts::PI);
 
+    let min_momentum_change: f64 = input
+        .read("output:min_momentum_change")
+        .unwrap_or(0.0);
+
+    let laser_metadata: bool = input
+        .read("output:laser_metadata")
+        .unwrap_or(false);
+
     let eospec: Vec<String> = input.read("output:electron")
         .or_else(|e| match e.kind() {InputErrorKind::Location => Ok(vec![]), _ => Err(e)})?;
     let eospec: Vec<DistributionFunction> = eospec
@@ -883,11 +1328,9 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                 Ok(r)
             })?;
 
-        let seed = 0x8658b90036b165ebu64 + ((rng_seed as u64) * 0x32f55cddaebae910u64);
-        let mut rng = Xoshiro256StarStar::seed_from_u64(seed);
-        for _i in 0..id {
-            rng.jump();
-        }
+        let mut rng = task_rng(rng_seed, id);
+        // if given, the beam is sampled independently of the QED processes
+        let mut beam_rng = beam_rng_seed.map(|seed| beam_task_rng(seed, id));
 
         let laser: Laser = if focusing && !using_lcfa {
             FocusedLaser::new(a0, wavelength, waist, n_cycles, pol, pol_angle)
@@ -912,12 +1355,35 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
         let primaries = match beam {
             BeamParameters::FromRng { builder } => {
                 let initial_z = laser.ideal_initial_z() + 3.0 * builder.sigma_z;
-                builder.with_initial_z(initial_z).build(&mut rng)
+                builder.with_initial_z(initial_z).build(beam_rng.as_mut().unwrap_or(&mut rng))
             },
             #[cfg(feature = "hdf5-output")]
             BeamParameters::FromHdf5 { ref loader } => {
                 let initial_z = laser.ideal_initial_z();
-                loader.clone().with_initial_z(initial_z).build(&world)?
+                let mut unmoved: usize = 0;
+                let primaries: Vec<Particle> = loader.clone()
+                    .with_initial_z(initial_z)
+                    .build(&world)?
+                    .into_iter()
+                    // loaded particles are not guaranteed to start outside the field
+                    .map(|pt| propagate::move_to_field_free_start(&laser, pt).unwrap_or_else(|| {
+                        unmoved += 1;
+                        pt
+                    }))
+                    .collect();
+
+                let unmoved_local = unmoved;
+                world.all_reduce_into(&unmoved_local, &mut unmoved, SystemOperation::sum());
+                if unmoved > 0 {
+                    report!(
+                        Diagnostic::Warning, id == 0,
+                        concat!("{} imported particle(s) start inside the laser pulse and co-propagate with it at the speed of light.\n",
+                        "         These could not be moved outside the pulse and are tracked from their initial positions."),
+                        unmoved
+                    );
+                }
+
+                primaries
             }
         };
 
@@ -961,120 +1427,6 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
             }
         }
 
-        let runtime = std::time::Instant::now();
-
-        let options = CollideOptions {
-            dt_multiplier,
-            rate_increase: pair_rate_increase,
-            t_stop: match t_stop {
-                StopAt::SameTime | StopAt::Automatic => std::f64::INFINITY,
-                StopAt::GivenTime(t) => t
-            },
-            discard_bg_e,
-            discard_bg_ph,
-            rr,
-            tracking_photons,
-            keep_decayed_photons,
-            pol_resolved,
-            rotate_stokes_pars,
-            classical,
-            gaunt_factor,
-        };
-
-        let (mut electrons, mut photons, mut positrons, mut decayed_photons) = primaries
-            .chunks((num / 20).max(1))
-            .enumerate()
-            .map(|(i, chk)| {
-                let tmp = chk.iter()
-                    .map(|pt| collide(&laser, *pt, &mut rng, &mut current_id, options))
-                    .fold((Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new()), merge);
-                if id == 0 {
-                    println!(
-                        "Done {: >12} of {: >12} primaries, RT = {}, ETTC = {}...",
-                        (i+1) * chk.len(), num,
-                        PrettyDuration::from(runtime.elapsed()),
-                        PrettyDuration::from(ettc(runtime, i+1, 20))
-                    );
-                }
-                tmp
-            })
-            .fold(
-                (Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new()),
-                |a, b| ([a.0,b.0].concat(), [a.1,b.1].concat(), [a.2,b.2].concat(), [a.3,b.3].concat())
-            );
-
-        // Particle/parent ids are only unique within a single parallel process
-        let mut absorption = 0.0;
-        let mut id_offsets = vec![0u64; world.size() as usize];
-        #[cfg(feature = "with-mpi")]
-        world.all_gather_into(&current_id, &mut id_offsets[..]);
-        id_offsets.iter_mut().fold(0, |mut total, n| {total += *n; *n = total - *n; total});
-        // task n adds id_offsets[n] to each particle/parent id
-        for pt in electrons.iter_mut().chain(photons.iter_mut()).chain(positrons.iter_mut()).chain(decayed_photons.iter_mut()) {
-            pt.with_id(pt.id() + id_offsets[id as usize]);
-            pt.with_parent_id(pt.parent_id() + id_offsets[id as usize]);
-            absorption += pt.weight() * pt.absorbed_energy();
-        }
-
-        // Fix time coordinates, if necessary
-        match t_stop {
-            StopAt::SameTime => {
-                // which particle got furthest?
-                let mut ct_local = std::f64::NEG_INFINITY;
-                for pt in electrons.iter().chain(photons.iter()).chain(positrons.iter()).chain(decayed_photons.iter()) {
-                    let ct = pt.position()[0];
-                    if ct > ct_local { ct_local = ct; }
-                }
-
-                // across all MPI tasks
-                let mut ct_global = ct_local;
-                world.all_reduce_into(&ct_local, &mut ct_global, SystemOperation::max());
-
-                // update all positions
-                for pt in electrons.iter_mut().chain(photons.iter_mut()).chain(positrons.iter_mut()).chain(decayed_photons.iter_mut()) {
-                    let r = pt.position();
-                    let p = pt.normalized_momentum();
-                    let r = r + p * (ct_global - r[0]) / p[0];
-                    pt.with_position(r);
-                }
-            },
-            StopAt::GivenTime(t) => {
-                let ct_global = t * SPEED_OF_LIGHT;
-                for pt in electrons.iter_mut().chain(photons.iter_mut()).chain(positrons.iter_mut()).chain(decayed_photons.iter_mut()) {
-                    let r = pt.position();
-                    let p = pt.normalized_momentum();
-                    let r = r + p * (ct_global - r[0]) / p[0];
-                    pt.with_position(r);
-                }
-            },
-            _ => {},
-        }
-
-        let mut total_absorption = 0.0;
-        world.all_reduce_into(&absorption, &mut total_absorption, SystemOperation::sum());
-        let total_absorption = total_absorption * 1.0e6 * ELEMENTARY_CHARGE; // in J
-
-        if !laser_defines_z {
-            for pt in electrons.iter_mut().chain(photons.iter_mut()).chain(positrons.iter_mut()).chain(decayed_photons.iter_mut()) {
-                *pt = pt.to_beam_coordinate_basis(angle, angle2);
-            }
-        }
-
-        #[cfg(feature = "hdf5-output")]
-        let (energy, energy_unit) = laser.energy();
-        #[cfg(not(feature = "hdf5-output"))]
-        let (energy, _) = laser.energy();
-
-        let f_abs = total_absorption / energy;
-        if f_abs > 0.1 {
-            report!(
-                Diagnostic::Warning, id == 0,
-                concat!("obtained laser energy depletion of {}.\n",
-                "         Background field approximation likely to be invalid."),
-                format!("{:.2}%", 100.0 * f_abs).bold()
-            );
-        }
-
         // Updating 'ident' in case of a0 looping
         let current_ident: String = if a0_values.len() > 1 {
             format!("{}{}a0_{:.3}", ident, if ident.is_empty() {""} else {"_"}, a0)
@@ -1083,93 +1435,39 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
             ident.to_owned()
         };
 
-        if id == 0 {
-            println!("{} distribution output...", "Generating".bold().cyan());
-        }
-
-        for dstr in &eospec {
-            let prefix = format!("{}{}{}{}electron", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
-            dstr.write(&world, &electrons, &units, &prefix, file_format)?;
-        }
+        #[cfg(feature = "hdf5-output")]
+        let file = match output_mode {
+            OutputMode::Hdf5 => {
+                let filename = format!("{}{}{}{}particles.h5", output_dir, if output_dir.is_empty() {""} else {"/"}, 
+                                                               current_ident, if current_ident.is_empty() {""} else {"_"});
+                let file = hdf5_writer::ParallelFile::create(&world, &filename)?;
 
-        for dstr in &gospec {
-            let prefix = format!("{}{}{}{}photon", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
-            dstr.write(&world, &photons, &units, &prefix, file_format)?;
-        }
+                if id == 0 {
+                    println!("{} HDF5 output to {}...", "Writing".bold().cyan(), filename.bold().blue());
+                }
 
-        for dstr in &pospec {
-            let prefix = format!("{}{}{}{}positron", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
-            dstr.write(&world, &positrons, &units, &prefix, file_format)?;
-        }
+                // Build info
+                file.new_group("build")?
+                    .only_task(0)
+                    .new_dataset("version")?
+                        .write(env!("CARGO_PKG_VERSION"))?
+                    .new_dataset("branch")?
+                        .write(env!("VERGEN_GIT_BRANCH"))?
+                    .new_dataset("commit-hash")?
+                        .write(env!("VERGEN_GIT_SHA"))?
+                    .new_dataset("features")?
+                        .write(env!("PTARMIGAN_ACTIVE_FEATURES"))?;
 
-        for stat in estats.iter_mut() {
-            stat.evaluate(&world, &electrons, "electron");
-        }
+                // Top-level run information
+                let conf = file.new_group("config")?
+                    .only_task(0);
 
-        for stat in gstats.iter_mut() {
-            stat.evaluate(&world, &photons, "photon");
-        }
+                conf.new_dataset("mpi-tasks")?.write(&ntasks)?
+                    .new_dataset("input-file")?.write(raw_input.as_str())?;
 
-        for stat in pstats.iter_mut() {
-            stat.evaluate(&world, &positrons, "positron");
-        }
-
-        if id == 0 {
-            if !estats.is_empty() || !gstats.is_empty() || !pstats.is_empty() || !statsexpr.is_empty() {
-                use std::fs::File;
-                use std::io::Write;
-                let filename = format!("{}{}{}{}stats.txt", output_dir, if output_dir.is_empty() {""} else {"/"}, 
-                                                                      current_ident, if current_ident.is_empty() {""} else {"_"});
-                let mut file = File::create(filename)?;
-                for stat in &estats {
-                    writeln!(file, "{}", stat)?;
-                }
-                for stat in &pstats {
-                    writeln!(file, "{}", stat)?;
-                }
-                for stat in &gstats {
-                    writeln!(file, "{}", stat)?;
-                }
-                for stat in &statsexpr {
-                    writeln!(file, "{}", stat)?;
-                }
-            }
-        }
-
-        match output_mode {
-            #[cfg(feature = "hdf5-output")]
-            OutputMode::Hdf5 => {
-                use hdf5_writer::GroupHolder;
-                let filename = format!("{}{}{}{}particles.h5", output_dir, if output_dir.is_empty() {""} else {"/"}, 
-                                                               current_ident, if current_ident.is_empty() {""} else {"_"});
-                let file = hdf5_writer::ParallelFile::create(&world, &filename)?;
-
-                if id == 0 {
-                    println!("{} HDF5 output to {}...", "Writing".bold().cyan(), filename.bold().blue());
-                }
-
-                // Build info
-                file.new_group("build")?
-                    .only_task(0)
-                    .new_dataset("version")?
-                        .write(env!("CARGO_PKG_VERSION"))?
-                    .new_dataset("branch")?
-                        .write(env!("VERGEN_GIT_BRANCH"))?
-                    .new_dataset("commit-hash")?
-                        .write(env!("VERGEN_GIT_SHA"))?
-                    .new_dataset("features")?
-                        .write(env!("PTARMIGAN_ACTIVE_FEATURES"))?;
-
-                // Top-level run information
-                let conf = file.new_group("config")?
-                    .only_task(0);
-
-                conf.new_dataset("mpi-tasks")?.write(&ntasks)?
-                    .new_dataset("input-file")?.write(raw_input.as_str())?;
-
-                conf.new_group("unit")?
-                    .new_dataset("position")?.write(units.length.name())?
-                    .new_dataset("momentum")?.write(units.momentum.name())?;
+                conf.new_group("unit")?
+                    .new_dataset("position")?.write(units.length.name())?
+                    .new_dataset("momentum")?.write(units.momentum.name())?;
 
                 // Parsed input configuration
                 conf.new_group("control")?
@@ -1180,7 +1478,10 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                     .new_dataset("pair_creation_is_pol_resolved")?.write(&pol_resolved)?
                     .new_dataset("lcfa")?.write(&using_lcfa)?
                     .new_dataset("rng_seed")?.write(&rng_seed)?
+                    .new_dataset("beam_rng_seed")?.write(&beam_rng_seed.unwrap_or(rng_seed))?
                     .new_dataset("increase_pair_rate_by")?.write(&pair_rate_increase)?
+                    .new_dataset("max_recoil")?.write(&max_recoil)?
+                    .new_dataset("min_photon_fraction")?.write(&min_photon_fraction)?
                     .new_dataset("bandwidth_correction")?.write(&finite_bandwidth)?
                     .new_dataset("select_multiplicity")?.with_condition(|| multiplicity.is_some()).write(&multiplicity.unwrap_or(0))?
                     .new_dataset("select_multiplicity")?.with_condition(|| multiplicity.is_none()).write(&false)?;
@@ -1262,7 +1563,8 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                             Species::Photon => 0.0,
                         };
 
-                        let (radius, r_max) = builder.radius();
+                        // transverse size is cylindrically symmetric, but the cutoff may not be
+                        let ((radius, _), (x_max, y_max), radius_angle) = builder.radius();
 
                         group.new_dataset("imported_from_file")?
                                 .write(&false)?
@@ -1278,8 +1580,18 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                             .new_dataset("radius")?.with_unit(units.length.name())?.write(&radius.convert(&units.length))?
                             .new_dataset("radius_max")?
                                 .with_unit(units.length.name())?
-                                .with_desc("density distribution is cut off at this perpendicular distance from the beam axis")?
-                                .write(&r_max.convert(&units.length))?
+                                .with_desc("density distribution is cut off at this distance from the beam axis (if radial), or along x (otherwise)")?
+                                .write(&x_max.convert(&units.length))?
+                            .new_dataset("radius_max_y")?
+                                .with_unit(units.length.name())?
+                                .with_desc("density distribution is cut off at this distance from the beam axis along y")?
+                                .with_condition(|| !builder.transverse_cutoff_is_radial())
+                                .write(&y_max.convert(&units.length))?
+                            .new_dataset("transverse_cutoff_is_radial")?.write(&builder.transverse_cutoff_is_radial())?
+                            .new_dataset("radius_angle")?
+                                .with_unit("rad")?
+                                .with_desc("principal axes of the transverse distribution are rotated by this angle around the beam axis")?
+                                .write(&radius_angle)?
                             .new_dataset("length")?.with_unit(units.length.name())?.write(&builder.sigma_z.convert(&units.length))?
                             .new_dataset("rms_divergence")?.with_unit("rad")?.write(&builder.rms_div)?
                             .new_dataset("polarization")?
@@ -1311,234 +1623,349 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                 conf.new_group("output")?
                     .new_dataset("laser_defines_positive_z")?.write(&laser_defines_z)?
                     .new_dataset("beam_defines_positive_z")?.write(&!laser_defines_z)?
+                    .new_dataset("single_precision")?.write(&single_precision)?
                     .new_dataset("discard_background_e")?.write(&discard_bg_e)?
                     .new_dataset("discard_background_ph")?.write(&discard_bg_ph)?
-                    .new_dataset("min_energy")?.with_unit(units.energy.name())?.write(&min_energy.convert(&units.energy))?;
-
-                // Write particle data
-                let fs = file.new_group("final-state")?;
-
-                let (x, p, pol, w, a, chi, n, id, pid) = photons
-                    .iter()
-                    .map(|pt| (
-                        pt.position().convert(&units.length),
-                        pt.momentum().convert(&units.momentum),
-                        pt.polarization(),
-                        pt.weight(),
-                        pt.payload(),
-                        pt.parent_chi(),
-                        pt.interaction_count(),
-                        pt.id(),
-                        pt.parent_id()
-                    ))
-                    .unzip_n_vec();
-
-                drop(photons);
-
-                fs.new_group("photon")?
-                    .new_dataset("weight")?
-                        .with_unit("1")?
-                        .with_desc("number of real photons each macrophoton represents")?
-                        .write(&w[..])?
-                    .new_dataset("a0_at_creation")?
-                        .with_unit("1")?
-                        .with_desc("normalized amplitude (RMS under LMA) at point of emission")?
-                        .with_alias("xi")?
-                        .write(&a[..])?
-                    .new_dataset("parent_chi")?
-                        .with_unit("1")?
-                        .with_desc("quantum parameter (RMS under LMA) of parent at point of emission")?
-                        .write(&chi[..])?
-                    .new_dataset("n_pos")?
-                        .with_unit("1")?
-                        .with_desc("total probability of pair creation for the photon")?
-                        .write(&n[..])?
-                    .new_dataset("id")?
-                        .with_desc("unique ID of the photon")?
-                        .write(&id[..])?
-                    .new_dataset("parent_id")?
-                        .with_desc("ID of the particle that created the photon (for primary particles, parent_id = id")?
-                        .write(&pid[..])?
-                    .new_dataset("polarization")?
-                        .with_desc("Stokes parameters of the photon: I, Q, U, V")?
-                        .with_unit("1")?
-                        .with_alias("polarisation")?
-                        .write(&pol[..])?
-                    .new_dataset("position")?
-                        .with_unit(units.length.name())?
-                        .with_desc("four-position of the photon")?
-                        .write(&x[..])?
-                    .new_dataset("momentum")?
-                        .with_unit(units.momentum.name())?
-                        .with_desc("four-momentum of the photon")?
-                        .write(&p[..])?;
-
-                let (x, p, w, n, abs, id, pid) = electrons
-                    .iter()
-                    .map(|pt| (
-                        pt.position().convert(&units.length),
-                        pt.momentum().convert(&units.momentum),
-                        pt.weight(),
-                        pt.interaction_count(),
-                        pt.absorbed_energy().convert(&units.energy),
-                        pt.id(),
-                        pt.parent_id()
-                    ))
-                    .unzip_n_vec();
-
-                drop(electrons);
-
-                fs.new_group("electron")?
-                    .new_dataset("weight")?
-                        .with_unit("1")?
-                        .with_desc("number of real electrons each macroelectron represents")?
-                        .write(&w[..])?
-                    .new_dataset("n_gamma")?
-                        .with_unit("1")?
-                        .with_desc("total number of photons emitted by the electron")?
-                        .write(&n[..])?
-                    .new_dataset("id")?
-                        .with_desc("unique ID of the electron")?
-                        .write(&id[..])?
-                    .new_dataset("parent_id")?
-                        .with_desc("ID of the particle that created the electron (for primary particles, parent_id = id)")?
-                        .write(&pid[..])?
-                    .new_dataset("absorption")?
-                        .with_unit(units.energy.name())?
-                        .with_desc("energy absorbed from the laser")?
-                        .write(&abs[..])?
-                    .new_dataset("position")?
-                        .with_unit(units.length.name())?
-                        .with_desc("four-position of the electron")?
-                        .write(&x[..])?
-                    .new_dataset("momentum")?
-                        .with_unit(units.momentum.name())?
-                        .with_desc("four-momentum of the electron")?
-                        .write(&p[..])?;
-
-                let (x, x0, p, w, n, abs, id, pid, a, chi) = positrons
-                    .iter()
-                    .map(|pt| (
-                        pt.position().convert(&units.length),
-                        pt.was_created_at().convert(&units.length),
-                        pt.momentum().convert(&units.momentum),
-                        pt.weight(),
-                        pt.interaction_count(),
-                        pt.absorbed_energy().convert(&units.energy),
-                        pt.id(),
-                        pt.parent_id(),
-                        pt.payload(),
-                        pt.parent_chi(),
-                    ))
-                    .unzip_n_vec();
-
-                drop(positrons);
-
-                fs.new_group("positron")?
-                    .new_dataset("weight")?
-                        .with_unit("1")?
-                        .with_desc("number of real positrons each macropositron represents")?
-                        .write(&w[..])?
-                    .new_dataset("a0_at_creation")?
-                        .with_unit("1")?
-                        .with_desc("normalized amplitude (RMS under LMA) at point of creation")?
-                        .with_alias("xi")?
-                        .write(&a[..])?
-                    .new_dataset("parent_chi")?
-                        .with_unit("1")?
-                        .with_desc("quantum parameter (RMS under LMA) of parent at point of creation")?
-                        .write(&chi[..])?
-                    .new_dataset("n_gamma")?
-                        .with_unit("1")?
-                        .with_desc("total number of photons emitted by the positron")?
-                        .write(&n[..])?
-                    .new_dataset("id")?
-                        .with_desc("unique ID of the positron")?
-                        .write(&id[..])?
-                    .new_dataset("parent_id")?
-                        .with_desc("ID of the particle that created the positron (for primary particles, parent_id = id)")?
-                        .write(&pid[..])?
-                    .new_dataset("absorption")?
-                        .with_unit(units.energy.name())?
-                        .with_desc("energy absorbed from the laser")?
-                        .write(&abs[..])?
-                    .new_dataset("position")?
-                        .with_unit(units.length.name())?
-                        .with_desc("four-position of the positron")?
-                        .write(&x[..])?
-                    .new_dataset("position_at_creation")?
-                        .with_unit(units.length.name())?
-                        .with_desc("four-position at which the positron was created")?
-                        .write(&x0[..])?
-                    .new_dataset("momentum")?
-                        .with_unit(units.momentum.name())?
-                        .with_desc("four-momentum of the positron")?
-                        .write(&p[..])?;
-
-                if keep_decayed_photons {
-                    let is = file.new_group("intermediate-state")?;
-
-                    let (x, p, pol, w, a, chi, n, id, pid) = decayed_photons
-                        .iter()
-                        .map(|pt| (
-                            pt.position().convert(&units.length),
-                            // pt.was_created_at().convert(&units.length),
-                            pt.momentum().convert(&units.momentum),
-                            pt.polarization(),
-                            pt.weight(),
-                            pt.payload(),
-                            pt.parent_chi(),
-                            pt.interaction_count(),
-                            pt.id(),
-                            pt.parent_id()
-                        ))
-                        .unzip_n_vec();
-
-                    drop(decayed_photons);
-
-                    is.new_group("photon")?
-                        .new_dataset("weight")?
-                            .with_unit("1")?
-                            .with_desc("number of real photons each macrophoton represents")?
-                            .write(&w[..])?
-                        .new_dataset("a0_at_creation")?
-                            .with_unit("1")?
-                            .with_desc("normalized amplitude (RMS under LMA) at point of emission")?
-                            .with_alias("xi")?
-                            .write(&a[..])?
-                        .new_dataset("parent_chi")?
-                            .with_unit("1")?
-                            .with_desc("quantum parameter (RMS under LMA) of parent at point of emission")?
-                            .write(&chi[..])?
-                        .new_dataset("n_pos")?
-                            .with_unit("1")?
-                            .with_desc("total probability of pair creation for the photon")?
-                            .write(&n[..])?
-                        .new_dataset("id")?
-                            .with_desc("unique ID of the photon")?
-                            .write(&id[..])?
-                        .new_dataset("parent_id")?
-                            .with_desc("ID of the particle that created the photon (for primary particles, parent_id = id")?
-                            .write(&pid[..])?
-                        .new_dataset("polarization")?
-                            .with_desc("Stokes parameters of the photon: I, Q, U, V")?
-                            .with_unit("1")?
-                            .with_alias("polarisation")?
-                            .write(&pol[..])?
-                        .new_dataset("position")?
-                            .with_unit(units.length.name())?
-                            .with_desc("four-position of the photon")?
-                            .write(&x[..])?
-                        // .new_dataset("position_at_creation")?
-                        //     .with_unit(units.length.name())?
-                        //     .with_desc("four-position at which the photon was emitted")?
-                        //     .write(&x0[..])?
-                        .new_dataset("momentum")?
-                            .with_unit(units.momentum.name())?
-                            .with_desc("four-momentum of the photon")?
-                            .write(&p[..])?;
+                    .new_dataset("min_energy")?.with_unit(units.energy.name())?.write(&min_energy.convert(&units.energy))?
+                    .new_dataset("min_momentum_change")?.write(&min_momentum_change)?
+                    .new_dataset("laser_metadata")?.write(&laser_metadata)?;
+
+                if laser_metadata {
+                    write_laser_metadata(&file, &laser)?;
+                }
+
+                Some(file)
+            },
+            OutputMode::None => None,
+        };
+
+        // Particle data is written to datasets that are extended as each
+        // chunk of primaries is completed
+        #[cfg(feature = "hdf5-output")]
+        let fs = match file.as_ref() {
+            Some(file) => Some(file.new_group("final-state")?),
+            None => None,
+        };
+        #[cfg(feature = "hdf5-output")]
+        let fs_groups = match fs.as_ref() {
+            Some(fs) => Some([fs.new_group("electron")?, fs.new_group("photon")?, fs.new_group("positron")?]),
+            None => None,
+        };
+        #[cfg(feature = "hdf5-output")]
+        let mut fs_datasets = match fs_groups.as_ref() {
+            Some([e, g, p]) => Some([
+                SpeciesDatasets::create_in(e, Species::Electron, single_precision, &units)?,
+                SpeciesDatasets::create_in(g, Species::Photon, single_precision, &units)?,
+                SpeciesDatasets::create_in(p, Species::Positron, single_precision, &units)?,
+            ]),
+            None => None,
+        };
+        #[cfg(feature = "hdf5-output")]
+        let is = match file.as_ref() {
+            Some(file) if keep_decayed_photons => Some(file.new_group("intermediate-state")?),
+            _ => None,
+        };
+        #[cfg(feature = "hdf5-output")]
+        let is_group = match is.as_ref() {
+            Some(is) => Some(is.new_group("photon")?),
+            None => None,
+        };
+        #[cfg(feature = "hdf5-output")]
+        let mut is_datasets = match is_group.as_ref() {
+            Some(g) => Some(SpeciesDatasets::create_in(g, Species::Photon, single_precision, &units)?),
+            None => None,
+        };
+
+        let runtime = std::time::Instant::now();
+
+        let options = CollideOptions {
+            dt_multiplier,
+            rate_increase: pair_rate_increase,
+            t_stop: match t_stop {
+                StopAt::SameTime | StopAt::Automatic => std::f64::INFINITY,
+                StopAt::GivenTime(t) => t
+            },
+            discard_bg_e,
+            discard_bg_ph,
+            rr,
+            tracking_photons,
+            keep_decayed_photons,
+            pol_resolved,
+            rotate_stokes_pars,
+            classical,
+            gaunt_factor,
+            chi_limit,
+            max_recoil,
+            min_photon_fraction,
+            min_momentum_change,
+            species_eqn,
+            reflecting_plane,
+        };
+
+        let mut max_chi: f64 = 0.0;
+        let mut capped_emissions: usize = 0;
+
+        // Particle/parent ids are only unique within a single parallel process,
+        // so are offset as each chunk is completed: primaries are numbered first,
+        // followed by the secondaries of each chunk in turn, in rank order
+        let mut nums = vec![0u64; ntasks as usize];
+        world.all_gather_into(&(num as u64), &mut nums[..]);
+        let primary_id_offset: u64 = nums.iter().take(id as usize).sum();
+        let mut next_secondary_id: u64 = nums.iter().sum();
+
+        // Particles are written to file as soon as each chunk is completed,
+        // unless they must first be brought to the same time. They are
+        // otherwise only retained if needed for distribution or stats output
+        let streaming = !matches!(t_stop, StopAt::SameTime);
+        let retaining = !streaming
+            || !eospec.is_empty() || !gospec.is_empty() || !pospec.is_empty()
+            || !estats.is_empty() || !gstats.is_empty() || !pstats.is_empty();
+
+        let mut electrons: Vec<Particle> = Vec::new();
+        let mut photons: Vec<Particle> = Vec::new();
+        let mut positrons: Vec<Particle> = Vec::new();
+        let mut decayed_photons: Vec<Particle> = Vec::new();
+        let mut absorption = 0.0;
+
+        // Moments of the final-state particles, indexed by species,
+        // from which the summary is obtained without retaining them
+        let mut fs_moments = [BeamMoments::default(); 3];
+
+        // Every task must complete the same number of chunks
+        let nchunks = 20;
+
+        for i in 0..nchunks {
+            let chk = &primaries[(i * num / nchunks)..((i + 1) * num / nchunks)];
+            let first_secondary_id = current_id;
+
+            let (mut e, mut g, mut p, mut d) = chk.iter()
+                .filter_map(|pt| {
+                    // skip remaining primaries once the run is to be aborted
+                    if chi_limit.aborts_at(max_chi) {
+                        return None;
+                    }
+                    let shower = collide(&laser, *pt, &mut rng, &mut current_id, options);
+                    max_chi = max_chi.max(shower.max_chi);
+                    capped_emissions += shower.capped_emissions;
+                    Some(shower)
+                })
+                .fold((Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new(), Vec::<Particle>::new()), merge);
+
+            let mut counts = vec![0u64; ntasks as usize];
+            world.all_gather_into(&(current_id - first_secondary_id), &mut counts[..]);
+            let secondary_id_offset = next_secondary_id + counts.iter().take(id as usize).sum::<u64>();
+            next_secondary_id += counts.iter().sum::<u64>();
+
+            let global_id = |n: u64| if n < num as u64 {
+                n + primary_id_offset
+            } else {
+                n - first_secondary_id + secondary_id_offset
+            };
+
+            for pt in e.iter_mut().chain(g.iter_mut()).chain(p.iter_mut()).chain(d.iter_mut()) {
+                pt.with_id(global_id(pt.id()));
+                pt.with_parent_id(global_id(pt.parent_id()));
+                absorption += pt.weight() * pt.absorbed_energy();
+
+                // Fix time coordinates, if necessary
+                if let StopAt::GivenTime(t) = t_stop {
+                    let ct_global = t * SPEED_OF_LIGHT;
+                    let r = pt.position();
+                    let p = pt.normalized_momentum();
+                    let r = r + p * (ct_global - r[0]) / p[0];
+                    pt.with_position(r);
+                }
+
+                if !laser_defines_z {
+                    *pt = pt.to_beam_coordinate_basis(angle, angle2);
+                }
+            }
+
+            if streaming {
+                fs_moments[Species::Electron as usize].add(&e);
+                fs_moments[Species::Positron as usize].add(&p);
+                fs_moments[Species::Photon as usize].add(&g);
+            }
+
+            #[cfg(feature = "hdf5-output")] {
+                if streaming {
+                    if let Some([ds_e, ds_g, ds_p]) = fs_datasets.as_mut() {
+                        ds_e.append(&e, &units)?;
+                        ds_g.append(&g, &units)?;
+                        ds_p.append(&p, &units)?;
+                    }
+                    if let Some(ds) = is_datasets.as_mut() {
+                        ds.append(&d, &units)?;
+                    }
+                }
+            }
+
+            if retaining {
+                electrons.append(&mut e);
+                photons.append(&mut g);
+                positrons.append(&mut p);
+                decayed_photons.append(&mut d);
+            }
+
+            if id == 0 {
+                println!(
+                    "Done {: >12} of {: >12} primaries, RT = {}, ETTC = {}...",
+                    (i + 1) * num / nchunks, num,
+                    PrettyDuration::from(runtime.elapsed()),
+                    PrettyDuration::from(ettc(runtime, i + 1, nchunks))
+                );
+            }
+        }
+
+        let max_chi_local = max_chi;
+        world.all_reduce_into(&max_chi_local, &mut max_chi, SystemOperation::max());
+        if chi_limit.exceeded_by(max_chi) {
+            match chi_limit {
+                ChiLimit::Abort(limit) => {
+                    report!(Diagnostic::Error, id == 0, "quantum parameter reached {:.3e}, exceeding the maximum of {:.3e}.", max_chi, limit);
+                    return Err(format!("run aborted because control:max_chi = {:.3e} was exceeded", limit).into());
+                },
+                ChiLimit::Warn(limit) => {
+                    report!(
+                        Diagnostic::Warning, id == 0,
+                        concat!("quantum parameter reached {:.3e}, exceeding the maximum of {:.3e}.\n",
+                        "         Simulation results may be unphysical."),
+                        max_chi, limit
+                    );
+                },
+                ChiLimit::Unlimited => {},
+            }
+        }
+
+        let capped_emissions_local = capped_emissions;
+        world.all_reduce_into(&capped_emissions_local, &mut capped_emissions, SystemOperation::sum());
+        if capped_emissions > 0 {
+            report!(
+                Diagnostic::Warning, id == 0,
+                concat!("photon energy was capped at {:.3e} of the emitter's in {} emission(s).\n",
+                "         Consider reducing the timestep or the quantum parameter."),
+                max_recoil, capped_emissions
+            );
+        }
+
+        // Fix time coordinates, if necessary
+        if let StopAt::SameTime = t_stop {
+            // which particle got furthest?
+            let mut ct_local = std::f64::NEG_INFINITY;
+            for pt in electrons.iter().chain(photons.iter()).chain(positrons.iter()).chain(decayed_photons.iter()) {
+                let ct = pt.position()[0];
+                if ct > ct_local { ct_local = ct; }
+            }
+
+            // across all MPI tasks
+            let mut ct_global = ct_local;
+            world.all_reduce_into(&ct_local, &mut ct_global, SystemOperation::max());
+
+            // update all positions
+            for pt in electrons.iter_mut().chain(photons.iter_mut()).chain(positrons.iter_mut()).chain(decayed_photons.iter_mut()) {
+                let r = pt.position();
+                let p = pt.normalized_momentum();
+                let r = r + p * (ct_global - r[0]) / p[0];
+                pt.with_position(r);
+            }
+        }
+
+        if !streaming {
+            fs_moments[Species::Electron as usize].add(&electrons);
+            fs_moments[Species::Positron as usize].add(&positrons);
+            fs_moments[Species::Photon as usize].add(&photons);
+        }
+
+        for moments in fs_moments.iter_mut() {
+            let local = *moments;
+            world.all_reduce_into(local.as_slice(), moments.as_mut_slice(), SystemOperation::sum());
+        }
+
+        let mut total_absorption = 0.0;
+        world.all_reduce_into(&absorption, &mut total_absorption, SystemOperation::sum());
+        let total_absorption = total_absorption * 1.0e6 * ELEMENTARY_CHARGE; // in J
+
+        #[cfg(feature = "hdf5-output")]
+        let (energy, energy_unit) = laser.energy();
+        #[cfg(not(feature = "hdf5-output"))]
+        let (energy, _) = laser.energy();
+
+        let f_abs = total_absorption / energy;
+        if f_abs > 0.1 {
+            report!(
+                Diagnostic::Warning, id == 0,
+                concat!("obtained laser energy depletion of {}.\n",
+                "         Background field approximation likely to be invalid."),
+                format!("{:.2}%", 100.0 * f_abs).bold()
+            );
+        }
+
+        if id == 0 {
+            println!("{} distribution output...", "Generating".bold().cyan());
+        }
+
+        for dstr in &eospec {
+            let prefix = format!("{}{}{}{}electron", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
+            dstr.write(&world, &electrons, &units, &prefix, file_format)?;
+        }
+
+        for dstr in &gospec {
+            let prefix = format!("{}{}{}{}photon", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
+            dstr.write(&world, &photons, &units, &prefix, file_format)?;
+        }
+
+        for dstr in &pospec {
+            let prefix = format!("{}{}{}{}positron", output_dir, if output_dir.is_empty() {""} else {"/"}, current_ident, if current_ident.is_empty() {""} else {"_"});
+            dstr.write(&world, &positrons, &units, &prefix, file_format)?;
+        }
+
+        for stat in estats.iter_mut() {
+            stat.evaluate(&world, &electrons, "electron");
+        }
+
+        for stat in gstats.iter_mut() {
+            stat.evaluate(&world, &photons, "photon");
+        }
+
+        for stat in pstats.iter_mut() {
+            stat.evaluate(&world, &positrons, "positron");
+        }
+
+        if id == 0 {
+            if !estats.is_empty() || !gstats.is_empty() || !pstats.is_empty() || !statsexpr.is_empty() {
+                use std::fs::File;
+                use std::io::Write;
+                let filename = format!("{}{}{}{}stats.txt", output_dir, if output_dir.is_empty() {""} else {"/"}, 
+                                                                      current_ident, if current_ident.is_empty() {""} else {"_"});
+                let mut file = File::create(filename)?;
+                for stat in &estats {
+                    writeln!(file, "{}", stat)?;
+                }
+                for stat in &pstats {
+                    writeln!(file, "{}", stat)?;
+                }
+                for stat in &gstats {
+                    writeln!(file, "{}", stat)?;
+                }
+                for stat in &statsexpr {
+                    writeln!(file, "{}", stat)?;
+                }
+            }
+        }
+
+        #[cfg(feature = "hdf5-output")] {
+            if !streaming {
+                if let Some([ds_e, ds_g, ds_p]) = fs_datasets.as_mut() {
+                    ds_e.append(&electrons, &units)?;
+                    ds_g.append(&photons, &units)?;
+                    ds_p.append(&positrons, &units)?;
+                }
+                if let Some(ds) = is_datasets.as_mut() {
+                    ds.append(&decayed_photons, &units)?;
                 }
+            }
 
+            if let Some(fs) = fs.as_ref() {
                 fs.new_group("laser")?
                     .only_task(0)
                     .new_dataset("energy")?
@@ -1555,8 +1982,23 @@ fn ptarmigan_main<C: Communicator>(world: C) -> Result<(), Box<dyn Error>> {
                         .with_unit("J")?
                         .with_desc("energy absorbed from the laser")?
                         .write(&total_absorption)?;
-            },
-            OutputMode::None => {},
+            }
+
+            if output_mode == OutputMode::Hdf5 && id == 0 {
+                use std::fs::File;
+                use std::io::Write;
+                let filename = format!("{}{}{}{}summary.yaml", output_dir, if output_dir.is_empty() {""} else {"/"},
+                                                               current_ident, if current_ident.is_empty() {""} else {"_"});
+                let summary = simulation::yaml_summary(
+                    &laser,
+                    &fs_moments[species as usize],
+                    &fs_moments[Species::Electron as usize],
+                    &fs_moments[Species::Positron as usize],
+                    &fs_moments[Species::Photon as usize],
+                );
+                let mut file = File::create(filename)?;
+                write!(file, "{}", summary)?;
+            }
         }
 
         if id == 0 {
@@ -1600,3 +2042,268 @@ fn main() -> ExitCode {
 
     exit_code
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quantum emission and pair creation, with recoil, photon tracking
+    /// and no limits or cuts, which the tests below override as needed
+    fn test_options() -> CollideOptions {
+        CollideOptions {
+            dt_multiplier: 1.0,
+            rate_increase: 1.0,
+            t_stop: std::f64::INFINITY,
+            discard_bg_e: false,
+            discard_bg_ph: false,
+            rr: true,
+            tracking_photons: true,
+            keep_decayed_photons: false,
+            pol_resolved: false,
+            rotate_stokes_pars: true,
+            classical: false,
+            gaunt_factor: false,
+            chi_limit: ChiLimit::Unlimited,
+            max_recoil: 1.0,
+            min_photon_fraction: 0.0,
+            min_momentum_change: 0.0,
+            species_eqn: [None; 3],
+            reflecting_plane: None,
+        }
+    }
+
+    #[test]
+    fn beam_rng_is_independent() {
+        // even with the same seed, the beam sequence overlaps with
+        // none of the tasks' QED sequences
+        let seed = 7;
+        let beam: Vec<u64> = (0..4).map(|id| beam_task_rng(seed, id).gen()).collect();
+        let qed: Vec<u64> = (0..4).map(|id| task_rng(seed, id).gen()).collect();
+        println!("beam = {:x?}, qed = {:x?}", beam, qed);
+        assert!(beam.iter().all(|x| !qed.contains(x)));
+    }
+
+    #[test]
+    fn chi_limit_aborts_extreme_runs() {
+        let options = CollideOptions {
+            chi_limit: ChiLimit::Abort(10.0),
+            ..test_options()
+        };
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        // The limit is checked only at emission and pair-creation events,
+        // not at every step (see `control:max_chi`), so shower.max_chi is
+        // the largest χ of any event, and this only tests those events.
+        // (a0, gamma), giving chi ~ 1e-2 and ~1e3 respectively
+        for (a0, gamma, should_abort) in [(1.0, 1000.0, false), (1000.0, 2.0e5, true)].iter() {
+            let laser = FastPlaneWave::new(*a0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+            let z0 = laser.ideal_initial_z();
+            let u = FourVector::new(0.0, 0.0, 0.0, -gamma).unitize();
+            let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+                .with_normalized_momentum(u);
+
+            let mut current_id = 1;
+            let shower = collide(&laser, electron, &mut rng, &mut current_id, options);
+            let aborted = options.chi_limit.aborts_at(shower.max_chi);
+            println!(
+                "a0 = {}, gamma = {:.1e}: max chi = {:.3e}, {} secondaries, aborted = {}",
+                a0, gamma, shower.max_chi, shower.secondaries.len(), aborted
+            );
+            assert_eq!(aborted, *should_abort);
+
+            if aborted {
+                // tracking stopped before the electron left the field
+                let electron = shower.secondaries.iter().find(|pt| pt.id() == 0).unwrap();
+                assert!(laser.contains(electron.position()));
+            }
+        }
+    }
+
+    #[test]
+    fn per_species_equation_of_motion() {
+        let laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0);
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -2000.0).unitize();
+
+        // final momentum of an electron and a positron, for given options
+        let run = |options: CollideOptions| -> Vec<FourVector> {
+            [Species::Electron, Species::Positron].iter()
+                .map(|species| {
+                    let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+                    let mut current_id = 1;
+                    let pt = Particle::create(*species, FourVector::new(-z0, 0.0, 0.0, z0))
+                        .with_normalized_momentum(u);
+                    let shower = collide(&laser, pt, &mut rng, &mut current_id, options);
+                    shower.secondaries.iter().find(|pt| pt.id() == 0).unwrap().normalized_momentum()
+                })
+                .collect()
+        };
+
+        // classical emission, so the equation of motion alone determines the recoil
+        let options = CollideOptions {
+            classical: true,
+            tracking_photons: false,
+            ..test_options()
+        };
+
+        let with_rr = run(options);
+        let without_rr = run(CollideOptions { rr: false, ..options });
+
+        let mut species_eqn = [None; 3];
+        species_eqn[Species::Positron as usize] = Some(EquationOfMotion::Lorentz);
+        let mixed = run(CollideOptions { species_eqn, ..options });
+
+        println!("with rr: {:?}\nwithout rr: {:?}\nmixed: {:?}", with_rr, without_rr, mixed);
+        assert!(with_rr[0][0] < 0.99 * without_rr[0][0]);
+        assert_eq!(mixed[0], with_rr[0]);
+        assert_eq!(mixed[1], without_rr[1]);
+    }
+
+    #[test]
+    fn reflecting_plane() {
+        // no field, so particles move in straight lines
+        let laser = FastPlaneWave::new(0.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let z0 = laser.ideal_initial_z();
+
+        // plane at x = 0.1 um, particles allowed at smaller x
+        let x_plane = 0.1e-6;
+        let options = CollideOptions {
+            reflecting_plane: Some(propagate::ReflectingPlane::new(ThreeVector::new(-1.0, 0.0, 0.0), ThreeVector::new(x_plane, 0.0, 0.0))),
+            ..test_options()
+        };
+
+        for species in [Species::Electron, Species::Photon].iter() {
+            let u = match species {
+                Species::Photon => FourVector::lightlike(5.0, 1.0, -100.0),
+                _ => FourVector::new(0.0, 5.0, 1.0, -100.0).unitize(),
+            };
+            let pt = Particle::create(*species, FourVector::new(-z0, 0.0, 0.0, z0))
+                .with_normalized_momentum(u);
+
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut current_id = 1;
+            let shower = collide(&laser, pt, &mut rng, &mut current_id, options);
+            let pt = shower.secondaries.iter().find(|pt| pt.id() == 0).unwrap();
+            let u_final = pt.normalized_momentum();
+            println!("{}: u = {:?} -> {:?}, final x = {:.3e} m", species, u, u_final, pt.position()[1]);
+
+            // normal momentum reversed, tangential momentum and energy preserved
+            assert!(pt.position()[1] < x_plane);
+            assert!((u_final[1] + u[1]).abs() < 1.0e-9 * u[0]);
+            assert!((u_final[2] - u[2]).abs() < 1.0e-9 * u[0]);
+            assert!((u_final[3] - u[3]).abs() < 1.0e-9 * u[0]);
+            assert!((u_final[0] - u[0]).abs() < 1.0e-9 * u[0]);
+        }
+    }
+
+    #[test]
+    fn min_photon_fraction() {
+        let options = CollideOptions {
+            tracking_photons: false,
+            ..test_options()
+        };
+
+        let laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0);
+        let z0 = laser.ideal_initial_z();
+        let gamma = 2000.0;
+        let u = FourVector::new(0.0, 0.0, 0.0, -gamma).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u);
+
+        // photon energies and final electron momentum, for a given cutoff
+        let run = |min_photon_fraction: f64| {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let mut current_id = 1;
+            let options = CollideOptions { min_photon_fraction, ..options };
+            let shower = collide(&laser, electron, &mut rng, &mut current_id, options);
+            let photons: Vec<f64> = shower.secondaries.iter()
+                .filter(|pt| pt.species() == Species::Photon)
+                .map(|pt| pt.normalized_momentum()[0])
+                .collect();
+            let electron = shower.secondaries.iter().find(|pt| pt.species() == Species::Electron).unwrap();
+            (photons, electron.normalized_momentum())
+        };
+
+        let cutoff = 0.01;
+        let (all, u_all) = run(0.0);
+        let (high, u_high) = run(cutoff);
+
+        // the threshold applies to the energy of the electron at emission,
+        // which cannot be much larger than gamma
+        let above: Vec<f64> = all.iter().filter(|&&k| k >= 1.001 * cutoff * gamma).cloned().collect();
+        println!("{} photons recorded without cutoff, {} with, {} above {:.1e} gamma", all.len(), high.len(), above.len(), cutoff);
+        assert!(high.len() < all.len());
+        assert!(!above.is_empty());
+        assert!(high.iter().all(|k| all.contains(k)));
+        assert!(above.iter().all(|k| high.contains(k)));
+        assert_eq!(u_all, u_high);
+    }
+
+    #[test]
+    fn min_momentum_change() {
+        let options = CollideOptions {
+            tracking_photons: false,
+            min_momentum_change: 1.0e-3,
+            ..test_options()
+        };
+
+        // a tightly focused laser, so that electrons more than a few waists
+        // from the axis see no field at all
+        let waist = 2.0e-6;
+        let laser = FastFocusedLaser::new(10.0, 0.8e-6, waist, 8.0, Polarization::Linear, 0.0);
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -2000.0).unitize();
+
+        let offsets = [0.0, 0.2 * waist, 10.0 * waist, 20.0 * waist];
+        let kept: Vec<bool> = offsets.iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+                let mut current_id = offsets.len() as u64;
+                let electron = Particle::create(Species::Electron, FourVector::new(-z0, *x, 0.0, z0))
+                    .with_normalized_momentum(u)
+                    .with_id(i as u64);
+                let shower = collide(&laser, electron, &mut rng, &mut current_id, options);
+                shower.secondaries.iter().any(|pt| pt.id() == i as u64)
+            })
+            .collect();
+
+        println!("offsets = {:?} waists, kept = {:?}", offsets.iter().map(|x| x / waist).collect::<Vec<f64>>(), kept);
+        assert_eq!(kept, vec![true, true, false, false]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "hdf5-output", not(feature = "with-mpi")))]
+    fn laser_metadata() {
+        use hdf5_writer::{GroupHolder, ParallelFile};
+
+        let universe = mpi::initialize().unwrap();
+        let world = universe.world();
+
+        let filename = std::env::temp_dir().join("ptarmigan_laser_metadata_test.h5");
+        let filename = filename.to_str().unwrap();
+        let laser: Laser = FocusedLaser::new(10.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Linear, 0.0).into();
+
+        {
+            let file = ParallelFile::create(&world, filename).unwrap();
+            write_laser_metadata(&file, &laser).unwrap();
+        }
+
+        let file = ParallelFile::open(&world, filename).unwrap();
+        let energy = file.open_attribute("laser_energy").unwrap().read::<f64>().unwrap();
+        let energy_unit = file.open_attribute("laser_energy_unit").unwrap().read::<String>().unwrap();
+        let number = file.open_attribute("laser_photon_number").unwrap().read::<f64>().unwrap();
+        let number_unit = file.open_attribute("laser_photon_number_unit").unwrap().read::<String>().unwrap();
+        drop(file);
+        std::fs::remove_file(filename).unwrap();
+
+        let (target_energy, target_energy_unit) = laser.energy();
+        let (target_number, target_number_unit) = laser.photon_number().unwrap();
+        println!("energy = {:.6e} {}, photon number = {:.6e} {}", energy, energy_unit, number, number_unit);
+        assert_eq!(energy, target_energy);
+        assert_eq!(energy_unit, target_energy_unit);
+        assert_eq!(number, target_number);
+        assert_eq!(number_unit, target_number_unit);
+    }
+}
@@ -137,6 +137,28 @@ pub fn generate<R: Rng>(k: FourVector, q: FourVector, pol: Polarization, pol_ang
     (n, k_prime, sv)
 }
 
+/// Returns the energy (in MeV) of the first-harmonic Compton edge,
+/// i.e. the largest energy of a photon emitted by absorbing a
+/// single laser photon, for an electron with Lorentz factor `gamma`
+/// that collides head-on with a laser pulse of given peak amplitude
+/// `a0`, `wavelength` (in m) and polarization `pol`.
+///
+/// The edge is at (1 + β) γ η m c^2 / (1 + a^2 + 2η), where
+/// η = (1 + β) γ ħω / (m c^2) and a is the rms amplitude
+/// (a0 / √2 for LP, a0 for CP). The denominator accounts for
+/// both the electron recoil and its effective mass in the laser.
+#[allow(unused)]
+pub fn compton_edge_energy(gamma: f64, a0: f64, wavelength: f64, pol: Polarization) -> f64 {
+    let a_sqd = match pol {
+        Polarization::Linear => 0.5 * a0 * a0,
+        Polarization::Circular => a0 * a0,
+    };
+    let omega = 2.0 * consts::PI * SPEED_OF_LIGHT / wavelength;
+    let u_minus = gamma + (gamma * gamma - 1.0).sqrt(); // (1 + β) γ
+    let eta = u_minus * COMPTON_TIME * omega;
+    ELECTRON_MASS_MEV * u_minus * eta / (1.0 + a_sqd + 2.0 * eta)
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs::File;
@@ -189,4 +211,46 @@ mod tests {
             writeln!(file, "{:.6e} {:.6e} {:.6e} {} {:.6e} {:.6e}", v.0, v.1, v.2, v.3, v.4, v.5).unwrap();
         }
     }
+
+    #[test]
+    fn compton_edge() {
+        use rand_xoshiro::*;
+        use crate::field::{Field, PlaneWave};
+
+        let (gamma, a0, wavelength) = (2000.0, 0.5, 0.8e-6);
+
+        // standard formula, for an ultrarelativistic electron
+        let omega_mev = ELECTRON_MASS_MEV * COMPTON_TIME * 2.0 * consts::PI * SPEED_OF_LIGHT / wavelength;
+        let formula = 4.0 * gamma * gamma * omega_mev / (1.0 + 0.5 * a0 * a0 + 4.0 * gamma * omega_mev / ELECTRON_MASS_MEV);
+        let edge = compton_edge_energy(gamma, a0, wavelength, Polarization::Linear);
+        println!("Compton edge = {:.6e} MeV, standard formula = {:.6e} MeV", edge, formula);
+        assert!((edge - formula).abs() < 1.0e-6 * formula);
+
+        // quasimomentum at the centre of the pulse, for an electron
+        // that has gamma outside it
+        let laser = PlaneWave::new(a0, wavelength, 16.0, Polarization::Linear, 0.0, 0.0);
+        let r = FourVector::new(0.0, 0.0, 0.0, 0.0);
+        let kappa = SPEED_OF_LIGHT * COMPTON_TIME * laser.k();
+        let u = FourVector::new(0.0, 0.0, 0.0, -(gamma * gamma - 1.0_f64).sqrt()).unitize();
+        let q = u + (0.25 * a0 * a0 / (kappa * u)) * kappa;
+
+        // with dt this large, every call emits
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let energies: Vec<(f64, f64)> = (0..100_000)
+            .filter_map(|_| laser.radiate(r, q, 1.0e-9, &mut rng, RadiationMode::Quantum, 1.0))
+            .map(|event| ((event.absorption / kappa[0]).round(), ELECTRON_MASS_MEV * event.k[0]))
+            .collect();
+
+        let max = energies.iter()
+            .filter(|(n, _)| *n == 1.0)
+            .fold(0.0_f64, |max, (_, e)| max.max(*e));
+        let error = (max - edge).abs() / edge;
+        println!("{} photons, first-harmonic cutoff = {:.6e} MeV, error = {:.3e}", energies.len(), max, error);
+        assert!(max <= (1.0 + 1.0e-4) * edge);
+        assert!(error < 1.0e-3);
+
+        // the edge shifts with a0, so the linear result is excluded
+        let linear = compton_edge_energy(gamma, 0.0, wavelength, Polarization::Linear);
+        assert!((max - linear).abs() > 0.05 * linear);
+    }
 }
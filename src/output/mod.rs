@@ -126,6 +126,10 @@ impl Filter {
     }
 
     fn accepts(&self, pt: &Particle) -> bool {
+        if pt.is_probe() {
+            return false;
+        }
+
         if let Some(f) = self.func {
             f(pt) >= self.min && f(pt) < self.max
         } else {
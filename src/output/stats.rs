@@ -267,6 +267,10 @@ impl SummaryStatistic {
         let mut subtotal = 0.0;
         let mut count = 0.0;
         for p in pt {
+            if p.is_probe() {
+                continue;
+            }
+
             let within_bounds = if self.filter.is_some() {
                 let f = self.filter.as_ref().unwrap().f;
                 f(p) > self.min && f(p) < self.max
@@ -292,6 +296,10 @@ impl SummaryStatistic {
             let mut total = 0.0;
             let mut count = 0.0;
             for p in pt.iter().take(10) {
+                if p.is_probe() {
+                    continue;
+                }
+
                 let within_bounds = if self.filter.is_some() {
                     let f = self.filter.as_ref().unwrap().f;
                     f(p) > self.min && f(p) < self.max
@@ -319,6 +327,10 @@ impl SummaryStatistic {
         let mut count = 0.0;
 
         for p in pt {
+            if p.is_probe() {
+                continue;
+            }
+
             let within_bounds = if self.filter.is_some() {
                 let f = self.filter.as_ref().unwrap().f;
                 f(p) > self.min && f(p) < self.max
@@ -349,6 +361,10 @@ impl SummaryStatistic {
     fn min(&self, pt: &[Particle]) -> f64 {
         let mut min = std::f64::INFINITY;
         for p in pt {
+            if p.is_probe() {
+                continue;
+            }
+
             let within_bounds = if self.filter.is_some() {
                 let f = self.filter.as_ref().unwrap().f;
                 f(p) > self.min && f(p) < self.max
@@ -367,6 +383,10 @@ impl SummaryStatistic {
     fn max(&self, pt: &[Particle]) -> f64 {
         let mut max = std::f64::NEG_INFINITY;
         for p in pt {
+            if p.is_probe() {
+                continue;
+            }
+
             let within_bounds = if self.filter.is_some() {
                 let f = self.filter.as_ref().unwrap().f;
                 f(p) > self.min && f(p) < self.max
@@ -389,6 +409,10 @@ impl SummaryStatistic {
         let mut ybar = 0.0;
         let mut count = 0.0;
         for p in pt {
+            if p.is_probe() {
+                continue;
+            }
+
             let within_bounds = if self.filter.is_some() {
                 let f = self.filter.as_ref().unwrap().f;
                 f(p) > self.min && f(p) < self.max
@@ -721,4 +745,29 @@ mod tests {
         assert!(spec.unit == "mm");
 
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn probes_excluded() {
+        use crate::geometry::FourVector;
+
+        let parser = |_: &str| -> Option<f64> { None };
+        let stat = SummaryStatistic::load("total energy", &parser).unwrap();
+
+        let photon = Particle::create(Species::Photon, [0.0; 4].into())
+            .with_normalized_momentum(FourVector::lightlike(0.0, 0.0, -1000.0));
+        let probe = Particle::create(Species::Photon, [0.0; 4].into())
+            .with_normalized_momentum(FourVector::lightlike(0.0, 0.0, -2000.0))
+            .as_probe();
+
+        let (_, expected, _) = stat.total(&[photon]);
+        let particles = vec![photon, probe];
+        let (_, total, _) = stat.total(&particles);
+
+        // the probe is still part of the particle output
+        assert!(particles.iter().any(|pt| pt.is_probe()));
+        assert!(!format!("{}", probe).is_empty());
+
+        println!("total energy = {:.3e} [with probe], {:.3e} [without]", total, expected);
+        assert_eq!(total, expected);
+    }
+}
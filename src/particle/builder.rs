@@ -1,14 +1,16 @@
 use rand::prelude::*;
 use rand_distr::StandardNormal;
+use crate::constants::*;
 use crate::geometry::{ThreeVector, FourVector, StokesVector};
-use super::{Species, Particle};
-use super::dstr::RadialDistribution;
+use super::{Species, Particle, BeamSummary};
+use super::dstr::{RadialDistribution, LongitudinalDistribution};
 
 #[derive(Copy,Clone)]
 pub struct BeamBuilder {
     species: Species,
     num: usize,
     pub weight: f64,
+    weight_fn: Option<fn(f64) -> f64>,
     normal_espec: Option<bool>,
     pub gamma: f64,
     pub sigma: f64,
@@ -16,12 +18,21 @@ pub struct BeamBuilder {
     gamma_max: f64,
     radial_dstr: RadialDistribution,
     pub sigma_z: f64,
+    long_dstr: Option<LongitudinalDistribution>,
     energy_chirp: f64,
     angle: f64,
     collision_plane_angle: f64,
     pub rms_div: f64,
+    div_slope: f64,
+    ring_div: f64,
+    cone: Option<f64>,
+    norm_emittance: Option<f64>,
+    waist: Option<(f64, f64, f64)>,
     initial_z: f64,
     offset: ThreeVector,
+    phase_jitter: f64,
+    timing_offset: f64,
+    antithetic: bool,
     pub pol: StokesVector,
 }
 
@@ -31,6 +42,7 @@ impl BeamBuilder {
             species,
             num,
             weight: 1.0,
+            weight_fn: None,
             normal_espec: None,
             gamma: 0.0,
             sigma: 0.0,
@@ -38,16 +50,38 @@ impl BeamBuilder {
             gamma_max: 0.0,
             radial_dstr: RadialDistribution::Uniform {r_max: 0.0},
             sigma_z: 0.0,
+            long_dstr: None,
             energy_chirp: 0.0,
             angle: 0.0,
             collision_plane_angle: 0.0,
             rms_div: 0.0,
+            div_slope: 0.0,
+            ring_div: 0.0,
+            cone: None,
+            norm_emittance: None,
+            waist: None,
             initial_z: 0.0,
             offset: ThreeVector::new(0.0, 0.0, 0.0),
+            phase_jitter: 0.0,
+            timing_offset: 0.0,
+            antithetic: false,
             pol: StokesVector::unpolarized(),
         }
     }
 
+    /// Returns a builder for a pencil beam: all particles have the same
+    /// Lorentz factor `gamma`, zero divergence and zero transverse size.
+    /// Panics if `gamma` is not greater than one, which cannot be sampled.
+    #[allow(unused)]
+    pub fn monoenergetic(species: Species, num: usize, gamma: f64) -> Self {
+        // build() rejects samples with gamma <= 1, so would never terminate
+        assert!(gamma > 1.0, "monoenergetic {} beam requested with gamma = {:.3e}, which must be greater than 1", species, gamma);
+        BeamBuilder::new(species, num)
+            .with_normal_energy_spectrum(gamma, 0.0)
+            .with_divergence(0.0)
+            .with_uniformly_distributed_xy(0.0)
+    }
+
     pub fn with_initial_z(&self, initial_z: f64) -> Self {
         BeamBuilder {
             initial_z,
@@ -62,6 +96,42 @@ impl BeamBuilder {
         }
     }
 
+    /// Multiplies the weight of each macroparticle by `weight_fn(gamma)`,
+    /// where `gamma` is its sampled Lorentz factor. This allows the energy
+    /// spectrum to be importance sampled: if particles are drawn from
+    /// the spectrum f(gamma), but the physical spectrum is g(gamma),
+    /// the weight function should be g(gamma) / f(gamma).
+    #[allow(unused)]
+    pub fn with_weight_fn(&self, weight_fn: fn(f64) -> f64) -> Self {
+        BeamBuilder {
+            weight_fn: Some(weight_fn),
+            ..*self
+        }
+    }
+
+    /// Sets the weight of each macroparticle so that the beam as a whole
+    /// represents the given bunch charge (in C).
+    #[allow(unused)]
+    pub fn with_bunch_charge(&self, charge: f64) -> Self {
+        BeamBuilder {
+            weight: charge.abs() / (ELEMENTARY_CHARGE * self.num as f64),
+            ..*self
+        }
+    }
+
+    /// Changes the number of macroparticles to `num`, scaling the weight
+    /// of each inversely, so that the beam represents the same number of
+    /// physical particles (and the same charge). This allows convergence
+    /// with respect to the number of macroparticles to be tested.
+    #[allow(unused)]
+    pub fn with_macroparticle_count(&self, num: usize) -> Self {
+        BeamBuilder {
+            num,
+            weight: self.weight * (self.num as f64) / (num as f64),
+            ..*self
+        }
+    }
+
     pub fn with_normal_energy_spectrum(&self, gamma: f64, sigma: f64) -> Self {
         BeamBuilder {
             normal_espec: Some(true),
@@ -71,6 +141,18 @@ impl BeamBuilder {
         }
     }
 
+    /// As `with_normal_energy_spectrum`, but with the mean and standard
+    /// deviation given in MeV. For electrons and positrons, `mean_mev` is
+    /// the kinetic energy; for photons, it is the total energy.
+    #[allow(unused)]
+    pub fn with_normal_energy_spectrum_mev(&self, mean_mev: f64, sigma_mev: f64) -> Self {
+        let gamma = match self.species {
+            Species::Electron | Species::Positron => 1.0 + mean_mev / ELECTRON_MASS_MEV,
+            Species::Photon => mean_mev / ELECTRON_MASS_MEV,
+        };
+        self.with_normal_energy_spectrum(gamma, sigma_mev / ELECTRON_MASS_MEV)
+    }
+
     pub fn with_bremsstrahlung_spectrum(&self, gamma_min: f64, gamma_max: f64) -> Self {
         BeamBuilder {
             normal_espec: Some(false),
@@ -87,6 +169,94 @@ impl BeamBuilder {
         }
     }
 
+    /// Makes the divergence energy dependent, so that the RMS divergence
+    /// of a particle with Lorentz factor `gamma` is
+    /// `base_div + slope * (gamma - <gamma>)`.
+    #[allow(unused)]
+    pub fn with_chromatic_divergence(&self, base_div: f64, slope: f64) -> Self {
+        BeamBuilder {
+            rms_div: base_div,
+            div_slope: slope,
+            ..*self
+        }
+    }
+
+    /// Sets the energy spectrum and divergence of an electron beam that
+    /// has undergone betatron oscillations of amplitude `r_beta` in the
+    /// ion channel of a plasma with electron density `n_e` (in m^-3).
+    ///
+    /// The Lorentz factor is normally distributed with mean `gamma`
+    /// and standard deviation `sigma`. The betatron wavenumber
+    /// k_β = k_p / √(2γ) and the strength parameter K = γ k_β r_β,
+    /// so the transverse momentum grows as √γ and the RMS divergence
+    /// θ = K / γ = k_p r_β / √(2γ) falls as γ^(-1/2).
+    /// This dependence is linearized around the mean Lorentz factor
+    /// (see `with_chromatic_divergence`).
+    #[allow(unused)]
+    pub fn with_betatron_phase_space(&self, gamma: f64, sigma: f64, r_beta: f64, n_e: f64) -> Self {
+        let omega_p = (n_e * ELEMENTARY_CHARGE.powi(2) / (VACUUM_PERMITTIVITY * ELECTRON_MASS)).sqrt();
+        let k_p = omega_p / SPEED_OF_LIGHT;
+        let theta = k_p * r_beta / (2.0 * gamma).sqrt();
+        self.with_normal_energy_spectrum(gamma, sigma)
+            .with_chromatic_divergence(theta, -0.5 * theta / gamma)
+    }
+
+    /// Sets the transverse distribution to be normal, with standard deviations
+    /// `sigma_x` and `sigma_y`, and the RMS divergence in each plane to be
+    /// `eps_n / (βγ σ)`, where `eps_n` is the normalized emittance and βγ
+    /// is evaluated at the mean energy of the beam. This overrides `with_divergence`.
+    #[allow(unused)]
+    pub fn with_normalized_emittance(&self, eps_n: f64, sigma_x: f64, sigma_y: f64) -> Self {
+        BeamBuilder {
+            norm_emittance: Some(eps_n),
+            ..self.with_normally_distributed_xy(sigma_x, sigma_y)
+        }
+    }
+
+    /// Places the beam waist a distance `z_waist` upstream of the collision
+    /// point, with beta functions `beta_x` and `beta_y` there. At the waist,
+    /// the beam is normally distributed with size √(εβ) and divergence √(ε/β)
+    /// in each plane, where ε is the geometric emittance; each particle then
+    /// drifts ballistically to the collision point, so the spot size there
+    /// is √(εβ) (1 + z_waist^2 / β^2)^(1/2).
+    ///
+    /// The emittance must already have been set by `with_normalized_emittance`,
+    /// the transverse sizes given to which are overridden.
+    #[allow(unused)]
+    pub fn with_waist_at(&self, z_waist: f64, beta_x: f64, beta_y: f64) -> Self {
+        assert!(self.norm_emittance.is_some(), "beam waist specified without the normalized emittance");
+        BeamBuilder {
+            waist: Some((z_waist, beta_x, beta_y)),
+            ..*self
+        }
+    }
+
+    /// Correlates the pointing angle of each particle with its transverse
+    /// position, so that particles at radius `ring_radius` point radially
+    /// outward (or inward, if `radial_div` is negative) by an angle `radial_div`.
+    /// The `ring_radius` must be positive.
+    #[allow(unused)]
+    pub fn with_divergent_ring(&self, ring_radius: f64, radial_div: f64) -> Self {
+        assert!(ring_radius > 0.0, "ring radius must be positive (requested {:.3e})", ring_radius);
+        BeamBuilder {
+            ring_div: radial_div / ring_radius,
+            ..*self
+        }
+    }
+
+    /// Samples the direction of each particle uniformly in solid angle,
+    /// within a cone of given `half_angle` around the beam axis, rather
+    /// than from a Gaussian. The polar angle θ is therefore distributed
+    /// as sin θ for 0 ≤ θ ≤ `half_angle`. This overrides `with_divergence`,
+    /// `with_normalized_emittance` and `with_divergent_ring`.
+    #[allow(unused)]
+    pub fn with_conical_divergence(&self, half_angle: f64) -> Self {
+        BeamBuilder {
+            cone: Some(half_angle),
+            ..*self
+        }
+    }
+
     pub fn with_collision_angle(&self, angle: f64) -> Self {
         BeamBuilder {
             angle,
@@ -108,9 +278,32 @@ impl BeamBuilder {
         }
     }
 
+    /// As `with_normally_distributed_xy`, but with the distribution truncated
+    /// independently at |x| = `x_max` and |y| = `y_max`.
     pub fn with_trunc_normally_distributed_xy(&self, sigma_x: f64, sigma_y: f64, x_max: f64, y_max: f64) -> Self {
         BeamBuilder {
-            radial_dstr: RadialDistribution::TruncNormal { sigma_x, sigma_y, x_max, y_max },
+            radial_dstr: RadialDistribution::trunc_normal(sigma_x, sigma_y, x_max, y_max),
+            ..*self
+        }
+    }
+
+    /// As [with_trunc_normally_distributed_xy](Self::with_trunc_normally_distributed_xy),
+    /// but with the principal axes of the distribution rotated by `angle`
+    /// (counterclockwise) around the z axis. `sigma_x`, `sigma_y`, `x_max` and
+    /// `y_max` are defined along the principal axes.
+    #[allow(unused)]
+    pub fn with_rotated_trunc_normally_distributed_xy(&self, sigma_x: f64, sigma_y: f64, x_max: f64, y_max: f64, angle: f64) -> Self {
+        BeamBuilder {
+            radial_dstr: RadialDistribution::trunc_normal_rotated(sigma_x, sigma_y, x_max, y_max, angle),
+            ..*self
+        }
+    }
+
+    /// As `with_normally_distributed_xy`, with equal standard deviations `sigma`
+    /// in x and y, but with the distribution truncated at radius `r_max`.
+    pub fn with_radially_trunc_normally_distributed_xy(&self, sigma: f64, r_max: f64) -> Self {
+        BeamBuilder {
+            radial_dstr: RadialDistribution::TruncNormalRadial { sigma, r_max },
             ..*self
         }
     }
@@ -125,6 +318,20 @@ impl BeamBuilder {
     pub fn with_length(&self, sigma_z: f64) -> Self {
         BeamBuilder {
             sigma_z,
+            long_dstr: None,
+            ..*self
+        }
+    }
+
+    /// Samples the longitudinal positions of the particles from
+    /// the given `profile`, rather than a normal distribution.
+    /// This overrides `with_length`; the beam length is set to the
+    /// rms of the profile.
+    #[allow(unused)]
+    pub fn with_longitudinal_profile(&self, profile: LongitudinalDistribution) -> Self {
+        BeamBuilder {
+            sigma_z: profile.rms(),
+            long_dstr: Some(profile),
             ..*self
         }
     }
@@ -136,6 +343,49 @@ impl BeamBuilder {
         }
     }
 
+    /// Displaces the beam transversely, so that its axis passes the laser
+    /// axis at a distance `b` (in m), in the direction at angle `azimuth`
+    /// (in radians) to the collision plane, i.e. to the x axis if the
+    /// collision plane is not rotated. This replaces the transverse
+    /// components of any offset, keeping the longitudinal one. For a
+    /// non-zero collision angle, the displacement is applied perpendicular
+    /// to the beam axis, as for `with_offset`.
+    #[allow(unused)]
+    pub fn with_impact_parameter(&self, b: f64, azimuth: f64) -> Self {
+        let (sin, cos) = azimuth.sin_cos();
+        BeamBuilder {
+            offset: ThreeVector::new(b * cos, b * sin, self.offset[2]),
+            ..*self
+        }
+    }
+
+    /// Smears the phase of the laser at which each particle enters the
+    /// field, for a laser with given `wavelength`, by a normally distributed
+    /// amount with standard deviation `sigma_phase` (in radians).
+    /// This is implemented as an additional longitudinal offset,
+    /// `sigma_phase / k` in size, on top of the beam length.
+    #[allow(unused)]
+    pub fn with_phase_offset_spread(&self, sigma_phase: f64, wavelength: f64) -> Self {
+        BeamBuilder {
+            phase_jitter: sigma_phase * wavelength / (2.0 * std::f64::consts::PI),
+            ..*self
+        }
+    }
+
+    /// Delays the arrival of the beam at the focal plane, relative
+    /// to the peak of the laser pulse, by `dt` seconds (or advances it,
+    /// if `dt` is negative). This is equivalent to an additional
+    /// longitudinal offset `c dt` (see [with_offset](BeamBuilder::with_offset)):
+    /// a late beam starts further away, rather than later, so that
+    /// it is still initialized outside the laser pulse.
+    #[allow(unused)]
+    pub fn with_timing_offset(&self, dt: f64) -> Self {
+        BeamBuilder {
+            timing_offset: dt,
+            ..*self
+        }
+    }
+
     pub fn with_energy_chirp(&self, rho: f64) -> Self {
         BeamBuilder {
             energy_chirp: rho,
@@ -150,9 +400,26 @@ impl BeamBuilder {
         }
     }
 
+    /// Generates the beam in pairs of mirror-image particles, to reduce
+    /// the variance of estimates of mean quantities. Only half the particles
+    /// are sampled: each is partnered by its reflection through the
+    /// centre of the beam, i.e. with the transverse and longitudinal offsets,
+    /// the angles between the momentum and the beam axis, and the deviation
+    /// of the Lorentz factor from its mean (for a normal energy spectrum)
+    /// all reversed.
+    /// This is exact for the symmetric distributions provided by the builder.
+    /// If the number of particles is odd, the last is unpaired.
+    #[allow(unused)]
+    pub fn with_antithetic_sampling(&self) -> Self {
+        BeamBuilder {
+            antithetic: true,
+            ..*self
+        }
+    }
+
     #[cfg(feature = "hdf5-output")]
     pub fn transverse_dstr_is_normal(&self) -> bool {
-        matches!(self.radial_dstr, RadialDistribution::Normal {..} | RadialDistribution::TruncNormal {..})
+        matches!(self.radial_dstr, RadialDistribution::Normal {..} | RadialDistribution::TruncNormal {..} | RadialDistribution::TruncNormalRotated {..} | RadialDistribution::TruncNormalRadial {..})
     }
 
     #[cfg(feature = "hdf5-output")]
@@ -160,29 +427,230 @@ impl BeamBuilder {
         self.normal_espec.map(|b| !b).unwrap_or(false)
     }
 
+    /// Returns true if the transverse distribution is cut off at a
+    /// given radius, rather than independently in x and y.
+    #[cfg(feature = "hdf5-output")]
+    pub fn transverse_cutoff_is_radial(&self) -> bool {
+        matches!(self.radial_dstr, RadialDistribution::TruncNormalRadial {..} | RadialDistribution::Uniform {..})
+    }
+
+    /// Returns the transverse size of the beam, and the positions at which
+    /// the distribution is cut off, along its principal axes, as well as
+    /// the angle by which those axes are rotated (counterclockwise) from
+    /// the x and y axes.
     #[cfg(feature = "hdf5-output")]
-    pub fn radius(&self) -> (f64, f64) {
+    pub fn radius(&self) -> ((f64, f64), (f64, f64), f64) {
         match self.radial_dstr {
-            RadialDistribution::Normal { sigma_x, sigma_y: _ } => (sigma_x, std::f64::INFINITY),
-            RadialDistribution::TruncNormal { sigma_x, sigma_y: _, x_max, y_max: _ } => (sigma_x, x_max),
-            RadialDistribution::Uniform { r_max } => (r_max, r_max),
+            RadialDistribution::Normal { sigma_x, sigma_y } => ((sigma_x, sigma_y), (std::f64::INFINITY, std::f64::INFINITY), 0.0),
+            RadialDistribution::TruncNormal { sigma_x, sigma_y, x_max, y_max, .. } => ((sigma_x, sigma_y), (x_max, y_max), 0.0),
+            RadialDistribution::TruncNormalRotated { sigma_x, sigma_y, x_max, y_max, angle, .. } => ((sigma_x, sigma_y), (x_max, y_max), angle),
+            RadialDistribution::TruncNormalRadial { sigma, r_max } => ((sigma, sigma), (r_max, r_max), 0.0),
+            RadialDistribution::Uniform { r_max } => ((r_max, r_max), (r_max, r_max), 0.0),
+        }
+    }
+
+    /// Estimates the luminosity per crossing (in m^-2) of a head-on collision
+    /// between this beam and `other`, using the overlap of their transverse
+    /// distributions and offsets. `samples` sets the number of Monte Carlo samples.
+    #[allow(unused)]
+    pub fn luminosity<R: Rng>(&self, other: &BeamBuilder, rng: &mut R, samples: usize) -> f64 {
+        let dx = other.offset[0] - self.offset[0];
+        let dy = other.offset[1] - self.offset[1];
+        let n1 = self.weight * (self.num as f64);
+        let n2 = other.weight * (other.num as f64);
+        n1 * n2 * self.radial_dstr.overlap(&other.radial_dstr, dx, dy, rng, samples)
+    }
+
+    /// Returns summary statistics of the beam phase space, estimated
+    /// from a sample of `n` particles, without running a simulation.
+    #[allow(unused)]
+    pub fn summary<R: Rng>(&self, rng: &mut R, n: usize) -> BeamSummary {
+        let sample = BeamBuilder { num: n, ..*self }.build(rng);
+        BeamSummary::from_particles(&sample)
+    }
+
+    /// Returns the mean Lorentz factor of the energy spectrum
+    fn mean_gamma(&self, normal_espec: bool) -> f64 {
+        if normal_espec {
+            self.gamma
+        } else {
+            // first moment of the bremsstrahlung spectrum
+            let x_min = self.gamma_min / self.gamma_max;
+            let norm = 4.0 * (1.0 / x_min).ln() / 3.0 - 4.0 * (1.0 - x_min) / 3.0 + 0.5 * (1.0 - x_min.powi(2));
+            let first = 4.0 * (1.0 - x_min) / 3.0 - 2.0 * (1.0 - x_min.powi(2)) / 3.0 + (1.0 - x_min.powi(3)) / 3.0;
+            self.gamma_max * first / norm
         }
     }
 
+    /// Returns a longitudinal offset `dz`, sampled from the specified
+    /// profile, as well as `dz` in units of the beam length,
+    /// for correlating with the particle energy.
+    fn sample_dz<R: Rng>(&self, rng: &mut R) -> (f64, f64) {
+        match self.long_dstr {
+            Some(dstr) => {
+                let dz = dstr.sample(rng);
+                let n0 = if self.sigma_z > 0.0 { dz / self.sigma_z } else { 0.0 };
+                (n0, dz)
+            },
+            None => {
+                let n0 = rng.sample::<f64,_>(StandardNormal);
+                (n0, self.sigma_z * n0)
+            },
+        }
+    }
+
+    /// Returns the RMS divergence in the x and y planes implied by the
+    /// normalized emittance (if specified) and the transverse size of the beam,
+    /// for particles with Lorentz factor `gamma`
+    fn emittance_divergence(&self, gamma: f64) -> Option<(f64, f64)> {
+        let (sigma_x, sigma_y) = match self.radial_dstr {
+            RadialDistribution::Normal { sigma_x, sigma_y } => (sigma_x, sigma_y),
+            RadialDistribution::TruncNormal { sigma_x, sigma_y, .. } => (sigma_x, sigma_y),
+            RadialDistribution::TruncNormalRotated { sigma_x, sigma_y, angle, .. } => {
+                // projections onto the x and y axes
+                let (sin, cos) = angle.sin_cos();
+                ((sigma_x * cos).hypot(sigma_y * sin), (sigma_x * sin).hypot(sigma_y * cos))
+            },
+            RadialDistribution::TruncNormalRadial { sigma, .. } => (sigma, sigma),
+            RadialDistribution::Uniform { r_max } => (0.5 * r_max, 0.5 * r_max),
+        };
+        let beta_gamma = match self.species {
+            Species::Electron | Species::Positron => (gamma * gamma - 1.0).sqrt(),
+            Species::Photon => gamma,
+        };
+        self.norm_emittance.map(|eps_n| (eps_n / (beta_gamma * sigma_x), eps_n / (beta_gamma * sigma_y)))
+    }
+
+    /// Returns the transverse size and RMS divergence of the beam, in the
+    /// x and y planes, at the waist (if specified), for particles with
+    /// Lorentz factor `gamma`
+    fn waist_size_and_divergence(&self, gamma: f64) -> Option<((f64, f64), (f64, f64))> {
+        self.waist.zip(self.norm_emittance).map(|((_, beta_x, beta_y), eps_n)| {
+            let beta_gamma = match self.species {
+                Species::Electron | Species::Positron => (gamma * gamma - 1.0).sqrt(),
+                Species::Photon => gamma,
+            };
+            let eps = eps_n / beta_gamma;
+            (
+                ((eps * beta_x).sqrt(), (eps * beta_y).sqrt()),
+                ((eps / beta_x).sqrt(), (eps / beta_y).sqrt()),
+            )
+        })
+    }
+
+    /// Creates one particle for each of the given `samples`, which are
+    /// tuples of (energy, theta, phi, weight). The energy, in MeV, is the
+    /// total energy of the particle; theta is its polar angle with respect
+    /// to the beam axis (-z) and phi the azimuthal angle, measured from
+    /// the x axis. Only the species, polarization, initial z, offset and
+    /// timing offset of the builder are used: all particles start on the
+    /// beam axis. Panics if the energy of an electron or positron is less
+    /// than its rest energy.
+    #[allow(unused)]
+    pub fn from_energy_angle_samples(&self, samples: &[(f64, f64, f64, f64)]) -> Vec<Particle> {
+        let (t, z) = self.initial_time_and_z(0.0);
+        let r = FourVector::new(t, self.offset[0], self.offset[1], z);
+        samples.iter()
+            .enumerate()
+            .map(|(i, &(energy, theta, phi, weight))| {
+                if self.species != Species::Photon {
+                    assert!(energy >= ELECTRON_MASS_MEV, "{} sampled with energy {:.3e} MeV, which is less than its rest energy", self.species, energy);
+                }
+                let gamma = energy / ELECTRON_MASS_MEV;
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let (sin_phi, cos_phi) = phi.sin_cos();
+                let dir = ThreeVector::new(sin_theta * cos_phi, sin_theta * sin_phi, -cos_theta);
+                let u = match self.species {
+                    Species::Electron | Species::Positron => {
+                        let u = (gamma * gamma - 1.0).sqrt() * dir;
+                        FourVector::new(gamma, u[0], u[1], u[2])
+                    },
+                    Species::Photon => {
+                        let u = gamma * dir;
+                        FourVector::lightlike(u[0], u[1], u[2])
+                    },
+                };
+                Particle::create(self.species, r)
+                    .with_normalized_momentum(u)
+                    .with_polarization(self.pol)
+                    .with_weight(weight)
+                    .with_id(i as u64)
+                    .with_parent_id(i as u64)
+            })
+            .collect()
+    }
+
+    /// Returns the initial time (multiplied by c) and longitudinal position,
+    /// before rotation by the collision angle, of a particle displaced by
+    /// `dz` from the centre of the beam, including the longitudinal and
+    /// timing offsets.
+    fn initial_time_and_z(&self, dz: f64) -> (f64, f64) {
+        let z_offset = self.offset[2] + SPEED_OF_LIGHT * self.timing_offset;
+        if z_offset >= 0.0 {
+            // beam is further away
+            (-self.initial_z, self.initial_z + z_offset + dz)
+        } else {
+            // beam is closer to focal plane, push backwards
+            (-self.initial_z - z_offset.abs(), self.initial_z + dz)
+        }
+    }
+
+    /// Returns the reflection of `pt` through the centre of the beam
+    /// (see [with_antithetic_sampling](Self::with_antithetic_sampling)).
+    fn antithetic_partner(&self, pt: &Particle, normal_espec: bool) -> Particle {
+        // in the frame where the beam propagates along -z
+        let to_beam_frame = |v: ThreeVector| v.rotate_around_z(-self.collision_plane_angle).rotate_around_y(-self.angle);
+        let from_beam_frame = |v: ThreeVector| v.rotate_around_y(self.angle).rotate_around_z(self.collision_plane_angle);
+
+        let r = pt.position();
+        let (_, z_centre) = self.initial_time_and_z(0.0);
+        let centre = ThreeVector::new(self.offset[0], self.offset[1], z_centre);
+        let r_mirror = from_beam_frame(2.0 * centre - to_beam_frame(ThreeVector::from(r)));
+
+        let u = pt.normalized_momentum();
+        let gamma = match (normal_espec, self.species) {
+            (true, Species::Electron) | (true, Species::Positron) if 2.0 * self.gamma - u[0] > 1.0 => 2.0 * self.gamma - u[0],
+            (true, Species::Photon) if 2.0 * self.gamma - u[0] > 0.0 => 2.0 * self.gamma - u[0],
+            _ => u[0],
+        };
+        let n = to_beam_frame(ThreeVector::from(u)).normalize();
+        let n_mirror = from_beam_frame(ThreeVector::new(-n[0], -n[1], n[2]));
+        let u_mirror = match self.species {
+            Species::Electron | Species::Positron => {
+                let u = (gamma * gamma - 1.0).sqrt() * n_mirror;
+                FourVector::new(0.0, u[0], u[1], u[2]).unitize()
+            },
+            Species::Photon => {
+                let u = gamma * n_mirror;
+                FourVector::lightlike(u[0], u[1], u[2])
+            },
+        };
+
+        let weight = self.weight_fn.map_or(self.weight, |f| self.weight * f(gamma));
+
+        let mut partner = *pt;
+        partner.with_position(r_mirror.with_time(r[0]))
+            .with_normalized_momentum(u_mirror)
+            .with_weight(weight)
+    }
+
     pub fn build<R: Rng>(&self, rng: &mut R) -> Vec<Particle> {
         let normal_espec = self.normal_espec.expect("primary energy spectrum not specified");
-        (0..self.num).into_iter()
+        let mean_gamma = self.mean_gamma(normal_espec);
+        let emittance_div = self.emittance_divergence(mean_gamma);
+        let waist = self.waist_size_and_divergence(mean_gamma);
+        let num = if self.antithetic { (self.num + 1) / 2 } else { self.num };
+        let beam: Vec<Particle> = (0..num).into_iter()
             .map(|i| {
                 // Sample gamma from relevant distribution
                 let (gamma, dz) = if normal_espec {
                     loop {
                         // for correlated gamma and z
                         let rho = -self.energy_chirp;
-                        let n0 = rng.sample::<f64,_>(StandardNormal);
+                        let (n0, dz) = self.sample_dz(rng);
                         let n1 = rng.sample::<f64,_>(StandardNormal);
                         let n2 = rho * n0 + (1.0 - rho * rho).sqrt() * n1;
 
-                        let dz = self.sigma_z * n0;
                         let gamma = self.gamma + self.sigma * n2;
                         if gamma > 1.0 {
                             break (gamma, dz);
@@ -200,7 +668,7 @@ impl BeamBuilder {
                         }
                     };
 
-                    let dz = self.sigma_z * rng.sample::<f64,_>(StandardNormal);
+                    let (_, dz) = self.sample_dz(rng);
                     (x * self.gamma_max, dz)
                 };
 
@@ -209,25 +677,50 @@ impl BeamBuilder {
                     Species::Photon => -gamma,
                 };
 
-                let theta_x = self.angle + self.rms_div * rng.sample::<f64,_>(StandardNormal);
-                let theta_y = self.rms_div * rng.sample::<f64,_>(StandardNormal);
+                let rms_div = (self.rms_div + self.div_slope * (gamma - mean_gamma)).max(0.0);
+                let (div_x, div_y) = waist.map(|(_, div)| div).or(emittance_div).unwrap_or((rms_div, rms_div));
+                let theta_x = self.angle + div_x * rng.sample::<f64,_>(StandardNormal);
+                let theta_y = div_y * rng.sample::<f64,_>(StandardNormal);
 
-                let u = ThreeVector::new(u * theta_x.sin() * theta_y.cos(), u * theta_y.sin(), u * theta_x.cos() * theta_y.cos());
+                let (x, y) = match (waist, self.waist) {
+                    (Some(((sigma_x, sigma_y), _)), Some((z_waist, _, _))) => {
+                        // sampled at the waist, then drifted to the collision point:
+                        // as u < 0, positive theta means p_perp is antiparallel to r_perp
+                        let x = sigma_x * rng.sample::<f64,_>(StandardNormal);
+                        let y = sigma_y * rng.sample::<f64,_>(StandardNormal);
+                        (x - (theta_x - self.angle) * z_waist, y - theta_y * z_waist)
+                    },
+                    _ => self.radial_dstr.sample(rng),
+                };
+
+                // sub-cycle timing jitter
+                let dz = if self.phase_jitter > 0.0 {
+                    dz + self.phase_jitter * rng.sample::<f64,_>(StandardNormal)
+                } else {
+                    dz
+                };
+
+                // as u < 0, positive ring_div means p_perp is parallel to r_perp
+                let theta_x = theta_x - self.ring_div * x;
+                let theta_y = theta_y - self.ring_div * y;
+
+                let u = if let Some(half_angle) = self.cone {
+                    // uniform in cos(theta)
+                    let cos_theta = 1.0 - (1.0 - half_angle.cos()) * rng.gen::<f64>();
+                    let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+                    let phi = 2.0 * std::f64::consts::PI * rng.gen::<f64>();
+                    ThreeVector::new(u * sin_theta * phi.cos(), u * sin_theta * phi.sin(), u * cos_theta)
+                        .rotate_around_y(self.angle)
+                } else {
+                    ThreeVector::new(u * theta_x.sin() * theta_y.cos(), u * theta_y.sin(), u * theta_x.cos() * theta_y.cos())
+                };
                 let u = u.rotate_around_z(self.collision_plane_angle);
                 let u = match self.species {
                     Species::Electron | Species::Positron => FourVector::new(0.0, u[0], u[1], u[2]).unitize(),
                     Species::Photon => FourVector::lightlike(u[0], u[1], u[2]),
                 };
 
-                let (t, z) = if self.offset[2] >= 0.0 {
-                    // beam is further away
-                    (-self.initial_z, self.initial_z + self.offset[2] + dz)
-                } else {
-                    // beam is closer to focal plane, push backwards
-                    (-self.initial_z - self.offset[2].abs(), self.initial_z + dz)
-                };
-
-                let (x, y) = self.radial_dstr.sample(rng);
+                let (t, z) = self.initial_time_and_z(dz);
 
                 let (x, y) = (x + self.offset[0], y + self.offset[1]);
                 let r = ThreeVector::new(x, y, z);
@@ -235,13 +728,648 @@ impl BeamBuilder {
                 let r = r.rotate_around_z(self.collision_plane_angle);
                 let r = FourVector::new(t, r[0], r[1], r[2]);
 
+                let weight = self.weight_fn.map_or(self.weight, |f| self.weight * f(gamma));
+
                 Particle::create(self.species, r)
                     .with_normalized_momentum(u)
                     .with_polarization(self.pol)
-                    .with_weight(self.weight)
+                    .with_weight(weight)
                     .with_id(i as u64)
                     .with_parent_id(i as u64)
             })
-        .collect()
+        .collect();
+
+        if self.antithetic {
+            beam.iter()
+                .flat_map(|pt| vec![*pt, self.antithetic_partner(pt, normal_espec)])
+                .take(self.num)
+                .enumerate()
+                .map(|(i, mut pt)| pt.with_id(i as u64).with_parent_id(i as u64))
+                .collect()
+        } else {
+            beam
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_xoshiro::*;
+    use super::*;
+
+    #[test]
+    fn pencil_beam() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let gamma = 1000.0;
+        let beam = BeamBuilder::monoenergetic(Species::Electron, 100, gamma)
+            .build(&mut rng);
+
+        for pt in beam.iter() {
+            let u = pt.normalized_momentum();
+            let r = pt.position();
+            println!("gamma = {:.6e}, u = [{:.3e}, {:.3e}, {:.3e}], r_perp = [{:.3e}, {:.3e}]", u[0], u[1], u[2], u[3], r[1], r[2]);
+            assert!((u[0] - gamma).abs() < 1.0e-9 * gamma);
+            assert!(u[1] == 0.0 && u[2] == 0.0 && u[3] < 0.0);
+            assert!(r[1] == 0.0 && r[2] == 0.0);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn monoenergetic_at_rest() {
+        let _ = BeamBuilder::monoenergetic(Species::Electron, 1, 1.0);
+    }
+
+    #[test]
+    fn luminosity() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (sigma1, sigma2) = (2.0e-6, 3.0e-6);
+        let dx = 2.0e-6;
+        let charge = 100.0e-12;
+        let b1 = BeamBuilder::new(Species::Electron, 1000)
+            .with_normally_distributed_xy(sigma1, sigma1)
+            .with_bunch_charge(charge);
+        let b2 = BeamBuilder::new(Species::Electron, 2000)
+            .with_normally_distributed_xy(sigma2, sigma2)
+            .with_bunch_charge(charge)
+            .with_offset(ThreeVector::new(dx, 0.0, 0.0));
+
+        let n = charge / ELEMENTARY_CHARGE;
+        let sigma_sqd = sigma1 * sigma1 + sigma2 * sigma2;
+        let target = n * n * (-0.5 * dx * dx / sigma_sqd).exp() / (2.0 * std::f64::consts::PI * sigma_sqd);
+        let lumi = b1.luminosity(&b2, &mut rng, 1_000_000);
+        let error = (lumi - target).abs() / target;
+        println!("luminosity = {:.6e} m^-2, expected = {:.6e} m^-2, error = {:.3e}", lumi, target, error);
+        assert!(error < 1.0e-2);
+    }
+
+    #[test]
+    fn chromatic_divergence() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (gamma, sigma) = (1000.0, 100.0);
+        let (base_div, slope) = (1.0e-3, 1.0e-5);
+        let beam = BeamBuilder::new(Species::Electron, 100_000)
+            .with_normal_energy_spectrum(gamma, sigma)
+            .with_chromatic_divergence(base_div, slope)
+            .build(&mut rng);
+
+        // RMS of the vertical angle, for particles above and below the mean energy
+        let rms = |above: bool| {
+            let (sum, count) = beam.iter()
+                .filter(|pt| (pt.normalized_momentum()[0] > gamma) == above)
+                .map(|pt| {
+                    let u = pt.normalized_momentum();
+                    (u[2] / (u[0] * u[0] - 1.0).sqrt()).asin().powi(2)
+                })
+                .fold((0.0, 0), |(s, n), x| (s + x, n + 1));
+            (sum / (count as f64)).sqrt()
+        };
+
+        let (low, high) = (rms(false), rms(true));
+
+        // <(base + slope sigma |n|)^2> for the half-normal distribution
+        let expected = (base_div.powi(2) + 2.0 * base_div * slope * sigma * (2.0 / std::f64::consts::PI).sqrt() + (slope * sigma).powi(2)).sqrt();
+        let error = (high - expected).abs() / expected;
+        println!("rms div = {:.3e} [low energy], {:.3e} [high energy], expected {:.3e} [high energy], error = {:.3e}", low, high, expected, error);
+        assert!(high > 2.0 * low);
+        assert!(error < 2.0e-2);
+    }
+
+    #[test]
+    fn spectrum_in_mev() {
+        let builder = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum_mev(0.511, 0.1 * 0.511);
+        println!("gamma = {:.6e}, sigma = {:.6e}", builder.gamma, builder.sigma);
+        assert!((builder.gamma - 2.0).abs() < 1.0e-5);
+        assert!((builder.sigma - 0.1).abs() < 1.0e-6);
+
+        let builder = BeamBuilder::new(Species::Photon, 1)
+            .with_normal_energy_spectrum_mev(1000.0, 10.0);
+        let expected = 1000.0 / ELECTRON_MASS_MEV;
+        assert!((builder.gamma - expected).abs() < 1.0e-12 * expected);
+    }
+
+    #[test]
+    fn bunch_charge() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let charge = 100.0e-12;
+        let beam = BeamBuilder::monoenergetic(Species::Electron, 1000, 1000.0)
+            .with_bunch_charge(charge)
+            .build(&mut rng);
+        let total: f64 = beam.iter().map(|pt| pt.weight() * ELEMENTARY_CHARGE).sum();
+        let error = (total - charge).abs() / charge;
+        println!("total charge = {:.6e} C, expected {:.6e} C, error = {:.3e}", total, charge, error);
+        assert!(error < 1.0e-12);
+    }
+
+    #[test]
+    fn divergent_ring() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+
+        for radial_div in [1.0e-3, -1.0e-3].iter() {
+            let beam = BeamBuilder::monoenergetic(Species::Electron, 1000, 1000.0)
+                .with_normally_distributed_xy(1.0e-6, 1.0e-6)
+                .with_divergent_ring(1.0e-6, *radial_div)
+                .build(&mut rng);
+
+            // mean cosine of the angle between the transverse position and momentum
+            let correlation = beam.iter()
+                .map(|pt| {
+                    let (r, u) = (pt.position(), pt.normalized_momentum());
+                    (u[2].atan2(u[1]) - r[2].atan2(r[1])).cos()
+                })
+                .sum::<f64>() / (beam.len() as f64);
+
+            // angle at the ring radius itself
+            let pt = beam[0];
+            let (r, u) = (pt.position(), pt.normalized_momentum());
+            let theta = u[1].hypot(u[2]) / u[3].abs();
+            let expected = radial_div.abs() * r[1].hypot(r[2]) / 1.0e-6;
+            let error = (theta - expected).abs() / expected;
+
+            println!("radial_div = {:.1e}: <cos(phi_u - phi_r)> = {:.6e}, theta = {:.6e}, expected {:.6e}", radial_div, correlation, theta, expected);
+            assert!((correlation - radial_div.signum()).abs() < 1.0e-6);
+            assert!(error < 1.0e-3);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn divergent_ring_at_zero_radius() {
+        let _ = BeamBuilder::monoenergetic(Species::Electron, 1, 1000.0)
+            .with_divergent_ring(0.0, 1.0e-3);
+    }
+
+    #[test]
+    fn uniform_longitudinal_profile() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let length = 10.0e-6;
+        let initial_z = 1.0e-3;
+        let num = 100_000;
+        let beam = BeamBuilder::monoenergetic(Species::Electron, num, 1000.0)
+            .with_longitudinal_profile(LongitudinalDistribution::Uniform { length })
+            .with_initial_z(initial_z)
+            .build(&mut rng);
+
+        let dz: Vec<f64> = beam.iter().map(|pt| pt.position()[3] - initial_z).collect();
+
+        // all particles within the full width
+        let (min, max) = dz.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &z| (min.min(z), max.max(z)));
+        println!("dz in [{:.3e}, {:.3e}], full width = {:.3e}", min, max, length);
+        assert!(min >= -0.5 * length && max <= 0.5 * length);
+        assert!(max - min > 0.999 * length);
+
+        // and flat in between
+        let n_bins = 10;
+        let mut counts = vec![0usize; n_bins];
+        for z in dz.iter() {
+            let i = ((z / length + 0.5) * (n_bins as f64)) as usize;
+            counts[i.min(n_bins - 1)] += 1;
+        }
+        let expected = (num / n_bins) as f64;
+        for (i, c) in counts.iter().enumerate() {
+            let error = ((*c as f64) - expected).abs() / expected;
+            println!("bin {}: count = {}, expected = {:.0}, error = {:.3e}", i, c, expected, error);
+            assert!(error < 0.05);
+        }
+    }
+
+    #[test]
+    fn summary_of_normal_beam() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (gamma, sigma) = (1000.0, 10.0);
+        let (sigma_x, sigma_y, sigma_z) = (2.0e-6, 3.0e-6, 5.0e-6);
+        let rms_div = 1.0e-3;
+        let initial_z = 1.0e-3;
+        let summary = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum(gamma, sigma)
+            .with_normally_distributed_xy(sigma_x, sigma_y)
+            .with_length(sigma_z)
+            .with_divergence(rms_div)
+            .with_initial_z(initial_z)
+            .summary(&mut rng, 100_000);
+
+        println!("{:#?}", summary);
+
+        let rel_error = |value: f64, target: f64| (value - target).abs() / target;
+        assert!(rel_error(summary.mean_energy, ELECTRON_MASS_MEV * gamma) < 1.0e-3);
+        assert!(rel_error(summary.rms_energy, ELECTRON_MASS_MEV * sigma) < 0.02);
+        assert!(summary.mean_position[0].abs() < 0.02 * sigma_x);
+        assert!(summary.mean_position[1].abs() < 0.02 * sigma_y);
+        assert!(rel_error(summary.mean_position[2], initial_z) < 1.0e-3);
+        assert!(rel_error(summary.rms_position[0], sigma_x) < 0.02);
+        assert!(rel_error(summary.rms_position[1], sigma_y) < 0.02);
+        assert!(rel_error(summary.rms_position[2], sigma_z) < 0.02);
+        assert!(summary.mean_divergence.0.abs() < 0.02 * rms_div);
+        assert!(rel_error(summary.rms_divergence.0, rms_div) < 0.02);
+        assert!(rel_error(summary.rms_divergence.1, rms_div) < 0.02);
+        assert!(rel_error(summary.emittance.0, sigma_x * rms_div) < 0.03);
+        assert!(rel_error(summary.emittance.1, sigma_y * rms_div) < 0.03);
+    }
+
+    #[test]
+    fn betatron_scaling() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (gamma, sigma) = (1000.0, 50.0);
+        let (r_beta, n_e) = (1.0e-6, 1.0e24);
+        let beam = BeamBuilder::new(Species::Electron, 200_000)
+            .with_betatron_phase_space(gamma, sigma, r_beta, n_e)
+            .build(&mut rng);
+
+        let k_p = (n_e * ELEMENTARY_CHARGE.powi(2) / (VACUUM_PERMITTIVITY * ELECTRON_MASS)).sqrt() / SPEED_OF_LIGHT;
+
+        // rms angle in bins of Lorentz factor, each sigma wide
+        for i in 0..4 {
+            let gamma_min = gamma + sigma * ((i as f64) - 2.0);
+            let gamma_max = gamma_min + sigma;
+            let (sum, count) = beam.iter()
+                .map(|pt| pt.normalized_momentum())
+                .filter(|u| u[0] >= gamma_min && u[0] < gamma_max)
+                .fold((0.0, 0), |(sum, count), u| (sum + (u[1] / u[3]).atan().powi(2), count + 1));
+            let rms = (sum / (count as f64)).sqrt();
+            let target = k_p * r_beta / (gamma_min + gamma_max).sqrt();
+            let error = (rms - target).abs() / target;
+            println!("gamma in [{:.0}, {:.0}): rms angle = {:.4e}, K/gamma = {:.4e}, error = {:.3e}", gamma_min, gamma_max, rms, target, error);
+            assert!(error < 0.03);
+        }
+    }
+
+    #[test]
+    fn normalized_emittance() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let gamma = 1000.0;
+        let eps_n = 1.0e-6;
+        let (sigma_x, sigma_y) = (2.0e-6, 4.0e-6);
+        let summary = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum(gamma, 0.01 * gamma)
+            .with_normalized_emittance(eps_n, sigma_x, sigma_y)
+            .summary(&mut rng, 100_000);
+
+        let beta_gamma = (gamma * gamma - 1.0_f64).sqrt();
+        let target = (eps_n / (beta_gamma * sigma_x), eps_n / (beta_gamma * sigma_y));
+        let error = (
+            (summary.rms_divergence.0 - target.0).abs() / target.0,
+            (summary.rms_divergence.1 - target.1).abs() / target.1,
+        );
+        println!(
+            "rms divergence = ({:.4e}, {:.4e}), expected ({:.4e}, {:.4e}), normalized emittance = ({:.4e}, {:.4e})",
+            summary.rms_divergence.0, summary.rms_divergence.1, target.0, target.1,
+            beta_gamma * summary.emittance.0, beta_gamma * summary.emittance.1,
+        );
+        assert!(error.0 < 0.02 && error.1 < 0.02);
+        assert!((beta_gamma * summary.emittance.0 - eps_n).abs() < 0.03 * eps_n);
+        assert!((beta_gamma * summary.emittance.1 - eps_n).abs() < 0.03 * eps_n);
+    }
+
+    #[test]
+    fn importance_sampled_spectrum() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let num = 200_000;
+
+        // sampled from N(1000, 100^2), but physically N(1000, 50^2)
+        let beam = BeamBuilder::new(Species::Electron, num)
+            .with_normal_energy_spectrum(1000.0, 100.0)
+            .with_weight_fn(|gamma| {
+                let n = (gamma - 1000.0) / 100.0;
+                2.0 * (-1.5 * n * n).exp()
+            })
+            .build(&mut rng);
+
+        let (total, mean, var) = beam.iter()
+            .map(|pt| (pt.weight(), pt.normalized_momentum()[0]))
+            .fold((0.0, 0.0, 0.0), |(w0, w1, w2), (w, gamma)| (w0 + w, w1 + w * gamma, w2 + w * gamma * gamma));
+        let mean = mean / total;
+        let rms = (var / total - mean * mean).sqrt();
+
+        let total = total / (num as f64);
+        println!("total weight / num = {:.4e}, mean gamma = {:.4e}, rms gamma = {:.4e} [expected 1, 1000, 50]", total, mean, rms);
+        assert!((total - 1.0).abs() < 0.01);
+        assert!((mean - 1000.0).abs() < 0.5);
+        assert!((rms - 50.0).abs() < 0.02 * 50.0);
+    }
+
+    #[test]
+    fn phase_offset_spread() {
+        let (wavelength, sigma_phase, z0) = (0.8e-6, 0.5, 10.0e-6);
+        let k = 2.0 * std::f64::consts::PI / wavelength;
+        let builder = BeamBuilder::monoenergetic(Species::Electron, 100_000, 1000.0)
+            .with_length(0.0)
+            .with_initial_z(z0);
+
+        // entry phase k (ct - z) of each particle
+        let phases = |beam: &[Particle]| -> Vec<f64> {
+            beam.iter().map(|pt| { let r = pt.position(); k * (r[0] - r[3]) }).collect()
+        };
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let beam = builder.with_phase_offset_spread(sigma_phase, wavelength).build(&mut rng);
+        let phi = phases(&beam);
+        let n = phi.len() as f64;
+        let mean = phi.iter().sum::<f64>() / n;
+        let std_dev = (phi.iter().map(|p| (p - mean).powi(2)).sum::<f64>() / n).sqrt();
+        println!("entry phase = {:.6e} +/- {:.6e}, expected {:.6e} +/- {:.6e}", mean, std_dev, -2.0 * k * z0, sigma_phase);
+        assert!((std_dev - sigma_phase).abs() < 0.01 * sigma_phase);
+        assert!((mean + 2.0 * k * z0).abs() < 0.01 * sigma_phase);
+
+        // zero spread is identical to the default
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let default = builder.build(&mut rng);
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let zero = builder.with_phase_offset_spread(0.0, wavelength).build(&mut rng);
+        for (a, b) in default.iter().zip(zero.iter()) {
+            assert!(a.position() == b.position() && a.normalized_momentum() == b.normalized_momentum());
+        }
+    }
+
+    #[test]
+    fn timing_offset() {
+        use crate::field::{Field, FastFocusedLaser, EquationOfMotion, Polarization};
+
+        let laser = FastFocusedLaser::new(10.0, 0.8e-6, 2.0e-6, 10.0, Polarization::Linear, 0.0);
+        let z_r = std::f64::consts::PI * 2.0e-6_f64.powi(2) / 0.8e-6;
+        let dt = laser.max_timestep().unwrap();
+        let builder = BeamBuilder::monoenergetic(Species::Electron, 1, 1000.0)
+            .with_initial_z(laser.ideal_initial_z());
+
+        // largest amplitude experienced by the particle
+        let peak_a0 = |builder: BeamBuilder| {
+            let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+            let pt = builder.build(&mut rng)[0];
+            let (mut r, mut u) = (pt.position(), pt.normalized_momentum());
+            let mut a_max: f64 = 0.0;
+            while laser.contains(r) {
+                let (r_new, u_new, _, _) = laser.push(r, u, pt.charge_to_mass_ratio(), dt, EquationOfMotion::Lorentz);
+                r = r_new;
+                u = u_new;
+                a_max = a_max.max(laser.fields(r).2);
+            }
+            a_max
+        };
+
+        let on_time = peak_a0(builder);
+
+        // collision takes place at z = c dt / 2, here 10 Rayleigh ranges from focus
+        let delay = 20.0 * z_r / SPEED_OF_LIGHT;
+        let late = peak_a0(builder.with_timing_offset(delay));
+        let early = peak_a0(builder.with_timing_offset(-delay));
+
+        println!("peak a0 = {:.3} [on time], {:.3} [late], {:.3} [early], expected ~{:.3} if mistimed", on_time, late, early, 10.0 / 101_f64.sqrt());
+        assert!(on_time > 9.0);
+        assert!(late < 0.2 * on_time);
+        assert!(early < 0.2 * on_time);
+    }
+
+    #[test]
+    fn waist_upstream() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let gamma = 1000.0;
+        let eps_n = 1.0e-6;
+        let (beta_x, beta_y) = (1.0e-3, 4.0e-3);
+        let z_waist = 2.0e-3;
+        let builder = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum(gamma, 0.0)
+            .with_normalized_emittance(eps_n, 1.0, 1.0);
+
+        let eps = eps_n / (gamma * gamma - 1.0_f64).sqrt();
+        let projected = |beta: f64, z: f64| (eps * beta).sqrt() * (1.0 + (z / beta).powi(2)).sqrt();
+
+        for z in [0.0, z_waist].iter() {
+            let summary = builder.with_waist_at(*z, beta_x, beta_y).summary(&mut rng, 100_000);
+            let target = (projected(beta_x, *z), projected(beta_y, *z));
+            let error = (
+                (summary.rms_position[0] - target.0).abs() / target.0,
+                (summary.rms_position[1] - target.1).abs() / target.1,
+            );
+            println!(
+                "z_waist = {:.1e}: spot size = ({:.4e}, {:.4e}), expected ({:.4e}, {:.4e}), divergence = ({:.4e}, {:.4e})",
+                z, summary.rms_position[0], summary.rms_position[1], target.0, target.1,
+                summary.rms_divergence.0, summary.rms_divergence.1,
+            );
+            assert!(error.0 < 0.02 && error.1 < 0.02);
+            assert!((summary.rms_divergence.0 - (eps / beta_x).sqrt()).abs() < 0.02 * (eps / beta_x).sqrt());
+            assert!((summary.rms_divergence.1 - (eps / beta_y).sqrt()).abs() < 0.02 * (eps / beta_y).sqrt());
+        }
+
+        // downstream of the waist, the beam is diverging: x and u_x have the same sign
+        let beam = builder.with_waist_at(z_waist, beta_x, beta_y).with_macroparticle_count(100_000).build(&mut rng);
+        let (xu, x2, u2) = beam.iter()
+            .map(|pt| (pt.position()[1], pt.normalized_momentum()[1]))
+            .fold((0.0, 0.0, 0.0), |(xu, x2, u2), (x, u)| (xu + x * u, x2 + x * x, u2 + u * u));
+        let correlation = xu / (x2 * u2).sqrt();
+        let expected = (z_waist / beta_x) / (1.0 + (z_waist / beta_x).powi(2)).sqrt();
+        println!("<x u_x> correlation = {:.3}, expected {:.3}", correlation, expected);
+        assert!(correlation > 0.0);
+        assert!((correlation - expected).abs() < 0.02);
+    }
+
+    #[test]
+    #[should_panic]
+    fn waist_without_emittance() {
+        let _ = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum(1000.0, 0.0)
+            .with_waist_at(2.0e-3, 1.0e-3, 1.0e-3);
+    }
+
+    #[test]
+    fn energy_angle_samples() {
+        let samples = [
+            (1000.0, 0.0, 0.0, 1.0),
+            (2000.0, 1.0e-3, 0.5, 2.0),
+            (10.0, 0.1, -2.0, 0.5),
+        ];
+
+        for species in [Species::Electron, Species::Photon].iter() {
+            let beam = BeamBuilder::new(*species, 0)
+                .with_initial_z(1.0e-3)
+                .from_energy_angle_samples(&samples);
+            assert_eq!(beam.len(), samples.len());
+
+            for (pt, (energy, theta, phi, weight)) in beam.iter().zip(samples.iter()) {
+                let p = pt.momentum();
+                let p_perp = p[1].hypot(p[2]);
+                let theta_actual = p_perp.atan2(-p[3]);
+                let phi_actual = p[2].atan2(p[1]);
+                println!(
+                    "{:?}: energy = {:.6e} MeV, theta = {:.6e}, phi = {:.6e}, weight = {:.3e} [expected {:.6e}, {:.6e}, {:.6e}, {:.3e}]",
+                    species, p[0], theta_actual, phi_actual, pt.weight(), energy, theta, phi, weight,
+                );
+                assert!((p[0] - energy).abs() < 1.0e-9 * energy);
+                assert!((theta_actual - theta).abs() < 1.0e-9);
+                if *theta > 0.0 {
+                    assert!((phi_actual - phi).abs() < 1.0e-9);
+                }
+                assert_eq!(pt.weight(), *weight);
+                assert_eq!(pt.position()[3], 1.0e-3);
+            }
+        }
+
+        // a negative offset moves the beam closer in time, not space, as in build()
+        let dz = -2.0e-6;
+        let builder = BeamBuilder::new(Species::Electron, 1)
+            .with_normal_energy_spectrum(1000.0 / ELECTRON_MASS_MEV, 0.0)
+            .with_initial_z(1.0e-3)
+            .with_offset(ThreeVector::new(0.0, 0.0, dz));
+        let sampled = builder.from_energy_angle_samples(&samples[..1]);
+        let built = builder.build(&mut Xoshiro256StarStar::seed_from_u64(0));
+        println!("r = {} [sampled], {} [built]", sampled[0].position(), built[0].position());
+        assert_eq!(sampled[0].position(), built[0].position());
+    }
+
+    #[test]
+    #[should_panic]
+    fn energy_angle_samples_below_rest_energy() {
+        let _ = BeamBuilder::new(Species::Electron, 0)
+            .from_energy_angle_samples(&[(0.5 * ELECTRON_MASS_MEV, 0.0, 0.0, 1.0)]);
+    }
+
+    #[test]
+    fn conical_divergence() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let half_angle = 0.1;
+        let num = 100_000;
+        let beam = BeamBuilder::monoenergetic(Species::Electron, num, 1000.0)
+            .with_conical_divergence(half_angle)
+            .build(&mut rng);
+
+        let thetas: Vec<f64> = beam.iter()
+            .map(|pt| {
+                let u = pt.normalized_momentum();
+                (-u[3] / u[1].hypot(u[2]).hypot(u[3])).acos()
+            })
+            .collect();
+
+        // P(theta < t) = (1 - cos t) / (1 - cos half_angle)
+        let bins = 10;
+        let cdf = |t: f64| (1.0 - t.cos()) / (1.0 - half_angle.cos());
+        for i in 0..bins {
+            let (lower, upper) = (half_angle * (i as f64) / (bins as f64), half_angle * ((i + 1) as f64) / (bins as f64));
+            let count = thetas.iter().filter(|&&t| t >= lower && t < upper).count() as f64;
+            let expected = (num as f64) * (cdf(upper) - cdf(lower));
+            println!("{:.3e} <= theta < {:.3e}: {} particles, expected {:.1}", lower, upper, count, expected);
+            assert!((count - expected).abs() < 4.0 * expected.sqrt());
+        }
+
+        let max = thetas.iter().cloned().fold(0.0, f64::max);
+        println!("max theta = {:.6e}, half angle = {:.6e}", max, half_angle);
+        assert!(max <= half_angle * (1.0 + 1.0e-9));
+    }
+
+    #[test]
+    fn antithetic_sampling() {
+        let (gamma, sigma) = (1000.0, 100.0);
+        let (sigma_x, sigma_z, rms_div) = (2.0e-6, 10.0e-6, 1.0e-3);
+        let initial_z = 50.0e-6;
+        let builder = BeamBuilder::new(Species::Electron, 10_000)
+            .with_normal_energy_spectrum(gamma, sigma)
+            .with_normally_distributed_xy(sigma_x, sigma_x)
+            .with_length(sigma_z)
+            .with_divergence(rms_div)
+            .with_initial_z(initial_z);
+
+        let centroid = |beam: &[Particle]| -> [f64; 6] {
+            let n = beam.len() as f64;
+            beam.iter().fold([0.0; 6], |mut sum, pt| {
+                let (r, u) = (pt.position(), pt.normalized_momentum());
+                sum[0] += r[1] / (n * sigma_x);
+                sum[1] += r[2] / (n * sigma_x);
+                sum[2] += (r[3] - initial_z) / (n * sigma_z);
+                let p = (u[0] * u[0] - 1.0).sqrt();
+                sum[3] += u[1] / (n * p * rms_div);
+                sum[4] += u[2] / (n * p * rms_div);
+                sum[5] += (u[0] - gamma) / (n * sigma);
+                sum
+            })
+        };
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let beam = builder.build(&mut rng);
+        let plain = centroid(&beam);
+        println!("without antithetic sampling, centroid / width = {:?}", plain);
+        assert!(plain.iter().all(|c| c.abs() > 1.0e-9));
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let beam = builder.with_antithetic_sampling().build(&mut rng);
+        let antithetic = centroid(&beam);
+        println!("with antithetic sampling, centroid / width = {:?}", antithetic);
+        assert_eq!(beam.len(), 10_000);
+        assert!(antithetic.iter().all(|c| c.abs() < 1.0e-12));
+
+        // partners are distinct and have consecutive ids
+        assert!(beam[0].position()[1] != beam[1].position()[1]);
+        assert!(beam.iter().enumerate().all(|(i, pt)| pt.id() == i as u64));
+
+        // the second moments are unaffected
+        let rms_x = (beam.iter().map(|pt| pt.position()[1].powi(2)).sum::<f64>() / 10_000.0).sqrt();
+        println!("rms x = {:.3e} m, expected {:.3e} m", rms_x, sigma_x);
+        assert!((rms_x - sigma_x).abs() < 0.05 * sigma_x);
+    }
+
+    #[test]
+    fn macroparticle_count() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (gamma, sigma) = (1000.0, 100.0);
+        let charge = 100.0e-12;
+        let builder = BeamBuilder::new(Species::Electron, 1_000)
+            .with_normal_energy_spectrum(gamma, sigma)
+            .with_normally_distributed_xy(2.0e-6, 2.0e-6)
+            .with_bunch_charge(charge);
+
+        for num in [10_000, 100_000, 1_000_000].iter() {
+            let beam = builder.with_macroparticle_count(*num).build(&mut rng);
+            let total: f64 = beam.iter().map(|pt| pt.weight()).sum();
+            let mean = beam.iter().map(|pt| pt.weight() * pt.normalized_momentum()[0]).sum::<f64>() / total;
+            let q = ELEMENTARY_CHARGE * total;
+            let error = (mean - gamma).abs() / (sigma / (*num as f64).sqrt());
+            println!("n = {}: charge = {:.6e} C, mean gamma = {:.6e} ({:.3} standard errors from {:.6e})", num, q, mean, error, gamma);
+            assert_eq!(beam.len(), *num);
+            assert!((q - charge).abs() < 1.0e-9 * charge);
+            assert!(error < 4.0);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn impact_parameter() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let b = 3.0e-6;
+
+        for (azimuth, plane_angle) in [(0.0, 0.0), (0.7, 0.0), (2.5, 0.4)].iter() {
+            // antithetic sampling places the centroid exactly on the beam axis
+            let beam = BeamBuilder::new(Species::Electron, 10_000)
+                .with_normal_energy_spectrum(1000.0, 100.0)
+                .with_normally_distributed_xy(1.0e-6, 1.0e-6)
+                .with_divergence(1.0e-3)
+                .with_initial_z(50.0e-6)
+                .with_collision_plane_at(*plane_angle)
+                .with_impact_parameter(b, *azimuth)
+                .with_antithetic_sampling()
+                .build(&mut rng);
+
+            // centroid position and mean direction of motion
+            let n = beam.len() as f64;
+            let (r, dir) = beam.iter().fold(
+                (ThreeVector::new(0.0, 0.0, 0.0), ThreeVector::new(0.0, 0.0, 0.0)),
+                |(r, dir), pt| {
+                    let u = ThreeVector::from(pt.normalized_momentum());
+                    (r + ThreeVector::from(pt.position()) / n, dir + u.normalize() / n)
+                }
+            );
+
+            // closest approach of the centroid to the z axis, as it travels
+            // from z = 50 um to z = -50 um
+            let (distance, x, y) = (0..=1000)
+                .map(|i| {
+                    let s = 100.0e-6 * (i as f64) / 1000.0;
+                    let (x, y) = (r[0] + s * dir[0], r[1] + s * dir[1]);
+                    (x.hypot(y), x, y)
+                })
+                .fold((std::f64::INFINITY, 0.0, 0.0), |a, b| if b.0 < a.0 { b } else { a });
+            let expected_azimuth = azimuth + plane_angle;
+            println!(
+                "azimuth = {:.3}, plane at {:.3}: minimum distance = {:.6e} m (b = {:.6e} m), at azimuth {:.6} (expected {:.6})",
+                azimuth, plane_angle, distance, b, y.atan2(x), expected_azimuth,
+            );
+            assert!((distance - b).abs() < 1.0e-9 * b);
+            assert!((y.atan2(x) - expected_azimuth).abs() < 1.0e-9);
+        }
+    }
+}
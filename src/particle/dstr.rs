@@ -10,11 +10,33 @@ pub(super) enum RadialDistribution {
         sigma_x: f64,
         sigma_y: f64,
     },
+    /// Normal distribution, truncated independently at |x| = `x_max`
+    /// and |y| = `y_max`. `acceptance` is the fraction of the untruncated
+    /// distribution that lies within these bounds: construct with
+    /// [trunc_normal](RadialDistribution::trunc_normal).
     TruncNormal {
         sigma_x: f64,
         sigma_y: f64,
         x_max: f64,
         y_max: f64,
+        acceptance: f64,
+    },
+    /// As `TruncNormal`, but with principal axes rotated by `angle`
+    /// (counterclockwise) around the z axis, with respect to the x and y axes.
+    /// The truncation applies along the principal axes.
+    TruncNormalRotated {
+        sigma_x: f64,
+        sigma_y: f64,
+        x_max: f64,
+        y_max: f64,
+        angle: f64,
+        acceptance: f64,
+    },
+    /// Cylindrically symmetric normal distribution, with standard deviation
+    /// `sigma` in x and y, truncated at radius `r_max`
+    TruncNormalRadial {
+        sigma: f64,
+        r_max: f64,
     },
     Uniform {
         r_max: f64,
@@ -22,6 +44,20 @@ pub(super) enum RadialDistribution {
 }
 
 impl RadialDistribution {
+    /// Returns a normal distribution, truncated independently at
+    /// |x| = `x_max` and |y| = `y_max`
+    pub fn trunc_normal(sigma_x: f64, sigma_y: f64, x_max: f64, y_max: f64) -> Self {
+        let acceptance = normal_acceptance(x_max / sigma_x) * normal_acceptance(y_max / sigma_y);
+        Self::TruncNormal { sigma_x, sigma_y, x_max, y_max, acceptance }
+    }
+
+    /// As [trunc_normal](RadialDistribution::trunc_normal), but with the
+    /// principal axes rotated by `angle` (counterclockwise) around the z axis
+    pub fn trunc_normal_rotated(sigma_x: f64, sigma_y: f64, x_max: f64, y_max: f64, angle: f64) -> Self {
+        let acceptance = normal_acceptance(x_max / sigma_x) * normal_acceptance(y_max / sigma_y);
+        Self::TruncNormalRotated { sigma_x, sigma_y, x_max, y_max, angle, acceptance }
+    }
+
     pub fn sample<R: Rng>(&self, rng: &mut R) -> (f64, f64) {
         match self {
             Self::Normal { sigma_x, sigma_y } => {(
@@ -29,14 +65,25 @@ impl RadialDistribution {
                 sigma_y * rng.sample::<f64,_>(StandardNormal),
             )},
 
-            Self::TruncNormal {sigma_x, sigma_y, x_max, y_max} => {
-                loop {
-                    let x = sigma_x * rng.sample::<f64,_>(StandardNormal);
-                    let y = sigma_y * rng.sample::<f64,_>(StandardNormal);
-                    if x * x / (x_max * x_max) + y * y / (y_max * y_max) <= 1.0 {
-                        return (x, y);
-                    }
-                }
+            Self::TruncNormal {sigma_x, sigma_y, x_max, y_max, ..} => {
+                let x = sample_trunc_normal(*sigma_x, *x_max, rng);
+                let y = sample_trunc_normal(*sigma_y, *y_max, rng);
+                (x, y)
+            },
+
+            Self::TruncNormalRotated {sigma_x, sigma_y, x_max, y_max, angle, ..} => {
+                let x = sample_trunc_normal(*sigma_x, *x_max, rng);
+                let y = sample_trunc_normal(*sigma_y, *y_max, rng);
+                let (sin, cos) = angle.sin_cos();
+                (x * cos - y * sin, x * sin + y * cos)
+            },
+
+            Self::TruncNormalRadial {sigma, r_max} => {
+                // invert the CDF of r, which is proportional to 1 - exp(-r^2 / 2 sigma^2)
+                let acceptance = -(-0.5 * (r_max / sigma).powi(2)).exp_m1();
+                let r = sigma * (-2.0 * (-acceptance * rng.gen::<f64>()).ln_1p()).sqrt();
+                let theta = 2.0 * consts::PI * rng.gen::<f64>();
+                (r * theta.cos(), r * theta.sin())
             },
 
             Self::Uniform {r_max} => {
@@ -46,4 +93,269 @@ impl RadialDistribution {
             },
         }
     }
-}
\ No newline at end of file
+
+    /// Returns the probability density at transverse position (x, y)
+    #[allow(unused)]
+    pub fn density(&self, x: f64, y: f64) -> f64 {
+        match *self {
+            Self::Normal { sigma_x, sigma_y } => {
+                let arg = x * x / (sigma_x * sigma_x) + y * y / (sigma_y * sigma_y);
+                (-0.5 * arg).exp() / (2.0 * consts::PI * sigma_x * sigma_y)
+            },
+
+            Self::TruncNormal { sigma_x, sigma_y, x_max, y_max, acceptance } => {
+                if x.abs() > x_max || y.abs() > y_max {
+                    return 0.0;
+                }
+                let arg = x * x / (sigma_x * sigma_x) + y * y / (sigma_y * sigma_y);
+                (-0.5 * arg).exp() / (2.0 * consts::PI * sigma_x * sigma_y * acceptance)
+            },
+
+            Self::TruncNormalRotated { sigma_x, sigma_y, x_max, y_max, angle, acceptance } => {
+                // rotate back to the principal axes
+                let (sin, cos) = angle.sin_cos();
+                let (x, y) = (x * cos + y * sin, -x * sin + y * cos);
+                Self::TruncNormal { sigma_x, sigma_y, x_max, y_max, acceptance }.density(x, y)
+            },
+
+            Self::TruncNormalRadial { sigma, r_max } => {
+                let r_sqd = x * x + y * y;
+                if r_sqd > r_max * r_max {
+                    return 0.0;
+                }
+                let acceptance = -(-0.5 * (r_max / sigma).powi(2)).exp_m1();
+                (-0.5 * r_sqd / (sigma * sigma)).exp() / (2.0 * consts::PI * sigma * sigma * acceptance)
+            },
+
+            Self::Uniform { r_max } => {
+                if x * x + y * y <= r_max * r_max {
+                    1.0 / (consts::PI * r_max * r_max)
+                } else {
+                    0.0
+                }
+            },
+        }
+    }
+
+    /// Estimates the overlap integral
+    /// ∫ ρ1(x, y) ρ2(x - dx, y - dy) dx dy,
+    /// where ρ1 is `self` and ρ2 is `other`, displaced by (dx, dy),
+    /// by Monte Carlo sampling of `self`.
+    #[allow(unused)]
+    pub fn overlap<R: Rng>(&self, other: &Self, dx: f64, dy: f64, rng: &mut R, samples: usize) -> f64 {
+        let total: f64 = (0..samples)
+            .map(|_| {
+                let (x, y) = self.sample(rng);
+                other.density(x - dx, y - dy)
+            })
+            .sum();
+        total / (samples as f64)
+    }
+}
+
+/// Samples a normally distributed random variable, with zero mean and
+/// standard deviation `sigma`, conditional on it lying within [-max, max].
+fn sample_trunc_normal<R: Rng>(sigma: f64, max: f64, rng: &mut R) -> f64 {
+    loop {
+        let x = sigma * rng.sample::<f64,_>(StandardNormal);
+        if x.abs() <= max {
+            break x;
+        }
+    }
+}
+
+/// Returns the probability that a normally distributed random variable,
+/// with zero mean and unit variance, lies within [-t_max, t_max].
+fn normal_acceptance(t_max: f64) -> f64 {
+    let n = 1000;
+    let dt = t_max / (n as f64);
+    let integral: f64 = (0..n)
+        .map(|i| {
+            let t = (i as f64 + 0.5) * dt;
+            (-0.5 * t * t).exp()
+        })
+        .sum();
+    (2.0 / consts::PI).sqrt() * integral * dt
+}
+
+/// Distribution of the longitudinal offset of a particle
+/// from the centre of the beam
+#[allow(unused)]
+#[derive(Copy, Clone)]
+pub enum LongitudinalDistribution {
+    /// Normal distribution with standard deviation `sigma_z`
+    Gaussian {
+        sigma_z: f64,
+    },
+    /// Uniform distribution with full width `length`
+    Uniform {
+        length: f64,
+    },
+    /// Arcsine distribution with full width `length`, peaked at
+    /// the head and tail, as for an RF-compressed bunch
+    DoubleHorn {
+        length: f64,
+    },
+}
+
+impl LongitudinalDistribution {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> f64 {
+        match self {
+            Self::Gaussian { sigma_z } => sigma_z * rng.sample::<f64,_>(StandardNormal),
+            Self::Uniform { length } => length * (rng.gen::<f64>() - 0.5),
+            Self::DoubleHorn { length } => 0.5 * length * (consts::PI * rng.gen::<f64>()).cos(),
+        }
+    }
+
+    /// Returns the root-mean-square offset
+    pub fn rms(&self) -> f64 {
+        match self {
+            Self::Gaussian { sigma_z } => *sigma_z,
+            Self::Uniform { length } => length / 12_f64.sqrt(),
+            Self::DoubleHorn { length } => length / 8_f64.sqrt(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_xoshiro::*;
+    use super::*;
+
+    #[test]
+    fn gaussian_overlap() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (sigma1, sigma2) = (2.0e-6, 3.0e-6);
+        let b1 = RadialDistribution::Normal { sigma_x: sigma1, sigma_y: sigma1 };
+        let b2 = RadialDistribution::Normal { sigma_x: sigma2, sigma_y: sigma2 };
+
+        let overlap = b1.overlap(&b2, 0.0, 0.0, &mut rng, 1_000_000);
+        let target = 1.0 / (2.0 * consts::PI * (sigma1 * sigma1 + sigma2 * sigma2));
+        let error = (overlap - target).abs() / target;
+        println!("overlap = {:.6e} m^-2, expected = {:.6e} m^-2, error = {:.3e}", overlap, target, error);
+        assert!(error < 1.0e-2);
+    }
+
+    #[test]
+    fn per_axis_truncation() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (sigma_x, sigma_y) = (1.0, 2.0);
+        let (x_max, y_max) = (1.5, 1.0);
+        let dstr = RadialDistribution::trunc_normal(sigma_x, sigma_y, x_max, y_max);
+
+        let num = 200_000;
+        let samples: Vec<(f64, f64)> = (0..num).map(|_| dstr.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|(x, y)| x.abs() <= x_max && y.abs() <= y_max));
+
+        // each axis is an independent, truncated normal distribution with variance
+        // sigma^2 [1 - 2 t phi(t) / (2 Phi(t) - 1)], where t = max / sigma
+        let variance = |sigma: f64, max: f64| {
+            let t = max / sigma;
+            let phi = (-0.5 * t * t).exp() / (2.0 * consts::PI).sqrt();
+            sigma * sigma * (1.0 - 2.0 * t * phi / normal_acceptance(t))
+        };
+
+        let var_x = samples.iter().map(|(x, _)| x * x).sum::<f64>() / (num as f64);
+        let var_y = samples.iter().map(|(_, y)| y * y).sum::<f64>() / (num as f64);
+        let (target_x, target_y) = (variance(sigma_x, x_max), variance(sigma_y, y_max));
+        println!("variance = ({:.4e}, {:.4e}), expected ({:.4e}, {:.4e})", var_x, var_y, target_x, target_y);
+        assert!((var_x - target_x).abs() < 0.01 * target_x);
+        assert!((var_y - target_y).abs() < 0.01 * target_y);
+
+        // the y distribution is nearly flat, as y_max < sigma_y
+        let near_edge = samples.iter().filter(|(_, y)| y.abs() > 0.9 * y_max).count() as f64;
+        let expected = (num as f64) * (-0.5 * (0.95 * y_max / sigma_y).powi(2)).exp() * 0.1 * y_max * 2.0 / (2.0 * consts::PI * sigma_y * sigma_y).sqrt() / normal_acceptance(y_max / sigma_y);
+        println!("{} samples with |y| > 0.9 y_max, expected {:.0}", near_edge, expected);
+        assert!((near_edge - expected).abs() < 0.03 * expected);
+
+        // and the density is normalized
+        let n = 400;
+        let (dx, dy) = (2.0 * x_max / (n as f64), 2.0 * y_max / (n as f64));
+        let total: f64 = (0..n*n)
+            .map(|k| {
+                let x = -x_max + dx * ((k % n) as f64 + 0.5);
+                let y = -y_max + dy * ((k / n) as f64 + 0.5);
+                dstr.density(x, y)
+            })
+            .sum::<f64>() * dx * dy;
+        println!("integrated density = {:.6e}", total);
+        assert!((total - 1.0).abs() < 1.0e-4);
+    }
+
+    #[test]
+    fn rotated_truncation() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (sigma_x, sigma_y) = (1.0, 3.0);
+        let (x_max, y_max) = (1.5, 4.0);
+        let angle = 0.4;
+        let dstr = RadialDistribution::trunc_normal_rotated(sigma_x, sigma_y, x_max, y_max, angle);
+
+        let num = 400_000;
+        let samples: Vec<(f64, f64)> = (0..num).map(|_| dstr.sample(&mut rng)).collect();
+
+        // bounds apply along the principal axes
+        let (sin, cos) = angle.sin_cos();
+        let principal = |(x, y): (f64, f64)| (x * cos + y * sin, -x * sin + y * cos);
+        assert!(samples.iter().map(|&s| principal(s)).all(|(u, v)| u.abs() <= x_max && v.abs() <= y_max));
+
+        // covariance matrix is R diag(var_u, var_v) R^T
+        let variance = |sigma: f64, max: f64| {
+            let t = max / sigma;
+            let phi = (-0.5 * t * t).exp() / (2.0 * consts::PI).sqrt();
+            sigma * sigma * (1.0 - 2.0 * t * phi / normal_acceptance(t))
+        };
+        let (var_u, var_v) = (variance(sigma_x, x_max), variance(sigma_y, y_max));
+        let target = [
+            var_u * cos * cos + var_v * sin * sin,
+            (var_u - var_v) * sin * cos,
+            var_u * sin * sin + var_v * cos * cos,
+        ];
+
+        let n = num as f64;
+        let cov = [
+            samples.iter().map(|(x, _)| x * x).sum::<f64>() / n,
+            samples.iter().map(|(x, y)| x * y).sum::<f64>() / n,
+            samples.iter().map(|(_, y)| y * y).sum::<f64>() / n,
+        ];
+        println!("cov = {:.4e}, {:.4e}, {:.4e}; expected {:.4e}, {:.4e}, {:.4e}", cov[0], cov[1], cov[2], target[0], target[1], target[2]);
+        for (c, t) in cov.iter().zip(target.iter()) {
+            assert!((c - t).abs() < 0.01 * target[0].max(target[2]));
+        }
+
+        // the density is consistent with the unrotated distribution
+        let unrotated = RadialDistribution::trunc_normal(sigma_x, sigma_y, x_max, y_max);
+        let (u, v) = (0.7, -1.2);
+        let (x, y) = (u * cos - v * sin, u * sin + v * cos);
+        assert!((dstr.density(x, y) - unrotated.density(u, v)).abs() < 1.0e-12 * unrotated.density(u, v));
+    }
+
+    #[test]
+    fn radial_truncation() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (sigma, r_max) = (1.0, 1.5);
+        let dstr = RadialDistribution::TruncNormalRadial { sigma, r_max };
+
+        let num = 200_000;
+        let samples: Vec<(f64, f64)> = (0..num).map(|_| dstr.sample(&mut rng)).collect();
+        assert!(samples.iter().all(|(x, y)| x.hypot(*y) <= r_max));
+
+        // fraction within r_max / 2 is [1 - exp(-r^2 / 8 sigma^2)] / [1 - exp(-r^2 / 2 sigma^2)]
+        let inner = samples.iter().filter(|(x, y)| x.hypot(*y) <= 0.5 * r_max).count() as f64 / (num as f64);
+        let expected = (-0.125 * (r_max / sigma).powi(2)).exp_m1() / (-0.5 * (r_max / sigma).powi(2)).exp_m1();
+        println!("fraction within r_max / 2 = {:.4e}, expected {:.4e}", inner, expected);
+        assert!((inner - expected).abs() < 0.01 * expected);
+
+        // the density is normalized
+        let n = 400;
+        let dx = 2.0 * r_max / (n as f64);
+        let total: f64 = (0..n*n)
+            .map(|k| {
+                let x = -r_max + dx * ((k % n) as f64 + 0.5);
+                let y = -r_max + dx * ((k / n) as f64 + 0.5);
+                dstr.density(x, y)
+            })
+            .sum::<f64>() * dx * dx;
+        println!("integrated density = {:.6e}", total);
+        assert!((total - 1.0).abs() < 1.0e-2);
+    }
+}
@@ -99,16 +99,39 @@ impl BeamLoader {
         }
     }
 
+    /// Read per-particle polarizations from the dataset at `path`,
+    /// rather than the default `final-state/{species}/polarization`.
+    /// For photons, the dataset must contain Stokes vectors (I, Q, U, V).
+    /// For electrons and positrons, it may contain either Stokes vectors
+    /// or spin vectors (S_1, S_2, S_3), which are loaded as (1, S_1, S_2, S_3).
+    pub fn with_polarization_path(self, path: &str) -> Self {
+        BeamLoader {
+            polarization_path: path.to_owned(),
+            ..self
+        }
+    }
+
     fn rotation_angle(&self, beam_axis: ThreeVector) -> f64 {
         let new_axis = ThreeVector::new(-self.collision_angle.sin(), 0.0, -self.collision_angle.cos());
         let theta = (new_axis * beam_axis).acos().abs().copysign(beam_axis.cross(new_axis)[1]);
         theta
     }
 
-    fn realign_particle(&self, r: FourVector, p: FourVector, theta: f64) -> (FourVector, FourVector) {
+    fn realign_particle(&self, r: FourVector, p: FourVector, pol: StokesVector, theta: f64) -> (FourVector, FourVector, StokesVector) {
         let p = p.rotate_around_y(theta);
         let r = r.rotate_around_y(theta);
 
+        // Lepton spin vectors are defined in the global frame, so rotate
+        // with the particle. The basis for photon Stokes parameters,
+        // e_1 ∝ y × k, is carried round by the rotation, so they are unchanged.
+        let pol = match self.species {
+            Species::Photon => pol,
+            Species::Electron | Species::Positron => {
+                let s = ThreeVector::new(pol[1], pol[2], pol[3]).rotate_around_y(theta);
+                StokesVector::new(pol[0], s[0], s[1], s[2])
+            },
+        };
+
         // timing/alignment errors
         let r = r + self.offset.rotate_around_y(self.collision_angle).with_time(0.0);
 
@@ -124,7 +147,7 @@ impl BeamLoader {
         let offset = offset - (2.0 * self.initial_z + r[0] - r[3]) * beta / (beta[0] - beta[3]);
         let r = r + offset;
 
-        (r, p)
+        (r, p, pol)
     }
 
     pub fn build<C>(&self, comm: &C) -> Result<Vec<Particle>, OutputError> where C: Communicator {
@@ -220,6 +243,20 @@ impl BeamLoader {
         let polarization = file.open_dataset(&self.polarization_path)
             .and_then(|ds| ds.read::<[StokesVector]>())
             .map(|sd| sd.take())
+            .or_else(|e| match self.species {
+                Species::Photon => Err(e),
+                // leptons may be given a spin vector instead
+                Species::Electron | Species::Positron => {
+                    file.open_dataset(&self.polarization_path)
+                        .and_then(|ds| ds.read::<[ThreeVector]>())
+                        .map(|sd| {
+                            sd.take()
+                                .into_iter()
+                                .map(|s| StokesVector::new(1.0, s[0], s[1], s[2]))
+                                .collect()
+                        })
+                }
+            })
             .ok();
 
         if polarization.is_none() {
@@ -240,7 +277,7 @@ impl BeamLoader {
             let r = position[i].convert_from(&x_unit);
             let p = momentum[i].convert_from(&p_unit) / ELECTRON_MASS_MEV;
 
-            let (r, p) = self.realign_particle(r, p, theta);
+            let (r, p, pol) = self.realign_particle(r, p, pol, theta);
 
             let pt = Particle::create(self.species, r)
                 .with_weight(weight[i])
@@ -288,4 +325,76 @@ mod tests {
             .with_min_energy(1000.0);
         assert!(loader.build(&world).is_ok())
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(not(feature = "with-mpi"))]
+    fn polarization_round_trip() {
+        let universe = mpi::initialize().unwrap();
+        let world = universe.world();
+
+        let filename = std::env::temp_dir().join("ptarmigan_loader_polarization_test.h5");
+        let filename = filename.to_str().unwrap();
+
+        let n = 10;
+        let weight = vec![1.0; n];
+        let position: Vec<FourVector> = (0..n).map(|i| FourVector::new(0.0, 1.0e-6 * (i as f64), 0.0, 0.0)).collect();
+        let momentum: Vec<FourVector> = (0..n).map(|_| FourVector::new(1000.0, 0.0, 0.0, 1000.0)).collect();
+        let stokes: Vec<StokesVector> = (0..n)
+            .map(|i| {
+                let theta = 0.1 * (i as f64);
+                StokesVector::new(1.0, 0.5 * theta.cos(), 0.5 * theta.sin(), -0.5)
+            })
+            .collect();
+        let spin: Vec<ThreeVector> = stokes.iter().map(|s| ThreeVector::new(s[1], s[2], s[3])).collect();
+
+        {
+            let file = ParallelFile::create(&world, filename).unwrap();
+            let conf = file.new_group("config").unwrap();
+            conf.new_group("unit").unwrap()
+                .new_dataset("position").unwrap().write("m").unwrap()
+                .new_dataset("momentum").unwrap().write("MeV/c").unwrap();
+            conf.new_group("output").unwrap()
+                .new_dataset("beam_defines_positive_z").unwrap().write(&true).unwrap();
+            let fs = file.new_group("final-state").unwrap();
+            fs.new_group("photon").unwrap()
+                .new_dataset("weight").unwrap().write(&weight[..]).unwrap()
+                .new_dataset("position").unwrap().write(&position[..]).unwrap()
+                .new_dataset("momentum").unwrap().write(&momentum[..]).unwrap()
+                .new_dataset("polarization").unwrap().write(&stokes[..]).unwrap();
+            fs.new_group("electron").unwrap()
+                .new_dataset("weight").unwrap().write(&weight[..]).unwrap()
+                .new_dataset("position").unwrap().write(&position[..]).unwrap()
+                .new_dataset("momentum").unwrap().write(&momentum[..]).unwrap()
+                .new_dataset("spin").unwrap().write(&spin[..]).unwrap();
+        }
+
+        let photons = BeamLoader::from_file(filename, "photon", 0.0)
+            .build(&world)
+            .unwrap();
+
+        let electrons = BeamLoader::from_file(filename, "electron", 0.0)
+            .with_polarization_path("final-state/electron/spin")
+            .build(&world)
+            .unwrap();
+
+        std::fs::remove_file(filename).unwrap();
+
+        assert_eq!(photons.len(), n);
+        assert_eq!(electrons.len(), n);
+
+        for (pt, sv) in photons.iter().zip(stokes.iter()) {
+            println!("photon: wrote {:?}, read {:?}", sv, pt.polarization());
+            assert_eq!(pt.polarization(), *sv);
+        }
+
+        // the beam is rotated by pi around y, to propagate towards -z,
+        // which carries the spin vector with it
+        for (pt, sv) in electrons.iter().zip(stokes.iter()) {
+            let expected = StokesVector::new(sv[0], -sv[1], sv[2], -sv[3]);
+            let pol = pt.polarization();
+            println!("electron: wrote {:?}, read {:?}, expected {:?}", sv, pol, expected);
+            let error = (0..4).map(|i| (pol[i] - expected[i]).abs()).fold(0.0, f64::max);
+            assert!(error < 1.0e-12);
+        }
+    }
+}
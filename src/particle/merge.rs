@@ -0,0 +1,137 @@
+//! Reducing the number of macrophotons by merging
+
+use std::collections::BTreeMap;
+use crate::geometry::{FourVector, ThreeVector, StokesVector};
+use super::{Particle, Species};
+
+/// Merges photons that are close together in (energy, angle) phase space,
+/// so that each cell of that space is represented by a single macrophoton.
+///
+/// Cells have a fractional width `energy_resolution` in energy and a
+/// width `angular_resolution` (in rad) in each component of the direction
+/// of propagation. The merged photon carries the total weight of the cell,
+/// its mean energy and position, and its mean Stokes vector; it propagates
+/// along the weighted mean direction. Thus the total energy is conserved
+/// exactly and the spectrum is preserved at resolutions coarser than
+/// the cells. Other properties, including the id, are taken from the
+/// photon in the cell with the largest weight.
+///
+/// Particles that are not photons, and probe particles, are returned unchanged.
+#[allow(unused)]
+pub fn merge_photons(pt: &[Particle], energy_resolution: f64, angular_resolution: f64) -> Vec<Particle> {
+    let mut merged = Vec::new();
+    let mut cells: BTreeMap<(i64, i64, i64, i64), Vec<&Particle>> = BTreeMap::new();
+    let log_step = energy_resolution.ln_1p();
+
+    for p in pt.iter() {
+        if p.species() != Species::Photon || p.is_probe() {
+            merged.push(*p);
+            continue;
+        }
+
+        let u = p.normalized_momentum();
+        let n = ThreeVector::from(u).normalize();
+        let key = (
+            (u[0].ln() / log_step).floor() as i64,
+            (n[0] / angular_resolution).floor() as i64,
+            (n[1] / angular_resolution).floor() as i64,
+            (n[2] / angular_resolution).floor() as i64,
+        );
+        cells.entry(key).or_insert_with(Vec::new).push(p);
+    }
+
+    for cell in cells.values() {
+        let weight: f64 = cell.iter().map(|p| p.weight()).sum();
+
+        let (energy, k, r, sv) = cell.iter().fold(
+            (0.0, ThreeVector::new(0.0, 0.0, 0.0), FourVector::new(0.0, 0.0, 0.0, 0.0), StokesVector::new(0.0, 0.0, 0.0, 0.0)),
+            |(energy, k, r, sv), p| {
+                let w = p.weight();
+                let u = p.normalized_momentum();
+                (energy + w * u[0], k + w * ThreeVector::from(u), r + w * p.position(), sv + w * p.polarization())
+            }
+        );
+
+        let energy = energy / weight;
+        let n = k.normalize();
+        let u = FourVector::new(energy, energy * n[0], energy * n[1], energy * n[2]);
+
+        let mut photon = **cell.iter()
+            .max_by(|a, b| a.weight().partial_cmp(&b.weight()).unwrap())
+            .unwrap();
+
+        photon.with_normalized_momentum(u)
+            .with_position(r / weight)
+            .with_polarization(sv / weight)
+            .with_weight(weight);
+
+        merged.push(photon);
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_distr::{Exp1, StandardNormal};
+    use rand_xoshiro::*;
+    use super::*;
+
+    #[test]
+    fn merging_preserves_spectrum() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let num: usize = 100_000;
+        let sigma = 1.0e-3;
+
+        let photons: Vec<Particle> = (0..num)
+            .map(|i| {
+                let energy = 1000.0 * (0.01 + rng.sample::<f64, _>(Exp1));
+                let theta_x = sigma * rng.sample::<f64, _>(StandardNormal);
+                let theta_y = sigma * rng.sample::<f64, _>(StandardNormal);
+                let n = ThreeVector::new(theta_x, theta_y, -1.0).normalize();
+                let u = FourVector::new(energy, energy * n[0], energy * n[1], energy * n[2]);
+                Particle::create(Species::Photon, FourVector::new(0.0, 0.0, 0.0, 0.0))
+                    .with_normalized_momentum(u)
+                    .with_weight(1.0 + rng.gen::<f64>())
+                    .with_id(i as u64)
+            })
+            .collect();
+
+        let (energy_resolution, angular_resolution) = (0.05, 2.5e-4);
+        let merged = merge_photons(&photons, energy_resolution, angular_resolution);
+
+        let total = |pt: &[Particle]| -> (f64, f64) {
+            pt.iter().fold((0.0, 0.0), |(w, e), p| (w + p.weight(), e + p.weight() * p.normalized_momentum()[0]))
+        };
+
+        // histogram in energy and angle, with bins that are ten (four) times
+        // wider in energy (angle) than the merging cells
+        let histogram = |pt: &[Particle]| -> BTreeMap<(i64, i64), f64> {
+            let mut hgram = BTreeMap::new();
+            for p in pt.iter() {
+                let u = p.normalized_momentum();
+                let n = ThreeVector::from(u).normalize();
+                let i = (u[0].ln() / energy_resolution.ln_1p() / 10.0).floor() as i64;
+                let j = (n[0] / angular_resolution / 4.0).floor() as i64;
+                *hgram.entry((i, j)).or_insert(0.0) += p.weight();
+            }
+            hgram
+        };
+
+        let (w0, e0) = total(&photons);
+        let (w1, e1) = total(&merged);
+        println!("merging reduced {} photons to {}", photons.len(), merged.len());
+        assert!(merged.len() < photons.len() / 2);
+        assert!(((w1 - w0) / w0).abs() < 1.0e-12);
+        assert!(((e1 - e0) / e0).abs() < 1.0e-12);
+
+        let after = histogram(&merged);
+        for (bin, before) in histogram(&photons).iter().filter(|(_, w)| **w > 1.0e-3 * w0) {
+            let after = after.get(bin).copied().unwrap_or(0.0);
+            let error = (after - before).abs() / before;
+            println!("bin {:?}: {:.3e} before, {:.3e} after, error = {:.3e}", bin, before, after, error);
+            assert!(error < 1.0e-3);
+        }
+    }
+}
@@ -15,6 +15,13 @@ mod loader;
 pub use loader::BeamLoader;
 
 mod dstr;
+pub use dstr::LongitudinalDistribution;
+
+mod summary;
+pub use summary::*;
+
+mod merge;
+pub use merge::*;
 
 pub enum BeamParameters {
     FromRng {
@@ -26,7 +33,7 @@ pub enum BeamParameters {
     }
 }
 
-#[derive(Copy,Clone,PartialEq,Eq,Debug)]
+#[derive(Copy,Clone,PartialEq,Eq,Hash,Debug)]
 #[repr(u8)]
 pub enum Species {
     Electron,
@@ -71,6 +78,7 @@ pub struct Particle {
     weight: f64,
     id: u64,
     parent_id: u64,
+    probe: bool,
 }
 
 impl fmt::Display for Particle {
@@ -105,6 +113,12 @@ pub struct Shower {
     pub primary: Particle,
     pub secondaries: Vec<Particle>,
     pub intermediates: Vec<Particle>,
+    /// Largest quantum parameter at which any particle
+    /// in the shower emitted a photon or created a pair
+    pub max_chi: f64,
+    /// Number of photon emissions in the shower for which the
+    /// recoil was capped (see [RadiationEvent::with_recoil_cap](crate::field::RadiationEvent::with_recoil_cap))
+    pub capped_emissions: usize,
 }
 
 impl Particle {
@@ -128,6 +142,7 @@ impl Particle {
             weight: 1.0,
             id: 0,
             parent_id: 0,
+            probe: false,
         }
     }
 
@@ -301,6 +316,7 @@ impl Particle {
             weight: self.weight,
             id: self.id,
             parent_id: self.parent_id,
+            probe: self.probe,
         }
     }
 
@@ -326,6 +342,19 @@ impl Particle {
         self.parent_id
     }
 
+    /// Marks the particle as a probe: it is tracked as usual, but
+    /// excluded from distribution functions and summary statistics.
+    /// Any secondaries it produces are also probes.
+    pub fn as_probe(&mut self) -> Self {
+        self.probe = true;
+        *self
+    }
+
+    /// Is this a probe particle?
+    pub fn is_probe(&self) -> bool {
+        self.probe
+    }
+
     /// Photon, electron or positron
     pub fn species(&self) -> Species {
         self.species
@@ -369,6 +398,21 @@ impl Shower {
     }
 }
 
+/// Rotates the positions and momenta of all the particles in `beam`
+/// by angle `theta` around the given `axis`.
+/// Stokes parameters are not transformed.
+#[allow(unused)]
+pub fn rotate_beam(beam: &mut [Particle], axis: ThreeVector, theta: f64) {
+    let axis = axis.normalize();
+    let rotate = |v: FourVector| {
+        ThreeVector::from(v).rotate_around(axis, theta).with_time(v[0])
+    };
+    for pt in beam.iter_mut() {
+        pt.r = [rotate(pt.r[0]), rotate(pt.r[1])];
+        pt.u = [rotate(pt.u[0]), rotate(pt.u[1])];
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::f64::consts;
@@ -442,4 +486,32 @@ mod tests {
             assert!(pol == target || (pol - target).abs() < 1.0e-6);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rotate_and_restore() {
+        use rand::prelude::*;
+        use rand_xoshiro::*;
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let original = BeamBuilder::new(Species::Electron, 100)
+            .with_normal_energy_spectrum(1000.0, 100.0)
+            .with_divergence(1.0e-3)
+            .with_normally_distributed_xy(1.0e-6, 1.0e-6)
+            .build(&mut rng);
+
+        let axis = ThreeVector::new(1.0, 1.0, 0.0);
+        let theta = 0.3;
+        let mut beam = original.clone();
+        rotate_beam(&mut beam, axis, theta);
+        assert!((beam[0].normalized_momentum()[3] - original[0].normalized_momentum()[3]).abs() > 1.0);
+        rotate_beam(&mut beam, axis, -theta);
+
+        for (pt, orig) in beam.iter().zip(original.iter()) {
+            let u = pt.normalized_momentum();
+            let u0 = orig.normalized_momentum();
+            let error = (ThreeVector::from(u) - ThreeVector::from(u0)).norm_sqr().sqrt() / u0[0];
+            assert!(error < 1.0e-12);
+            assert!(u[0] == u0[0]);
+        }
+    }
+}
@@ -0,0 +1,617 @@
+//! Aggregate properties of a set of particles
+
+use crate::constants::*;
+use crate::geometry::{StokesVector, ThreeVector};
+use super::Particle;
+
+/// Returns the weighted average Stokes vector of a set of particles,
+/// normalized such that its first component is unity, and its degree of
+/// polarization. Probe particles are ignored. Returns `None` if there
+/// are no particles, or if their total weight (or intensity) is zero.
+#[allow(unused)]
+pub fn mean_polarization(pt: &[Particle]) -> Option<(StokesVector, f64)> {
+    let (total, weight) = pt.iter()
+        .filter(|p| !p.is_probe())
+        .fold(
+            (StokesVector::new(0.0, 0.0, 0.0, 0.0), 0.0),
+            |(sv, w), p| (sv + p.weight() * p.polarization(), w + p.weight())
+        );
+    if weight <= 0.0 || total[0] <= 0.0 {
+        return None;
+    }
+    let sv = total / total[0];
+    Some((sv, sv.dop()))
+}
+
+/// Returns the normalized rms emittance of a set of particles, in the
+/// x-p_x and y-p_y planes, in m rad, i.e.
+/// ε_n = [<x^2> <u_x^2> - <x u_x>^2]^(1/2), where u = p / (m c) and the
+/// moments are centred and weighted. Unlike the geometric emittance
+/// returned by [BeamSummary], this is unchanged by acceleration, and so
+/// can be compared before and after particles lose energy to radiation.
+/// Probe particles are ignored.
+#[allow(unused)]
+pub fn rms_emittance(pt: &[Particle]) -> (f64, f64) {
+    let pt: Vec<&Particle> = pt.iter().filter(|p| !p.is_probe()).collect();
+    let weight: f64 = pt.iter().map(|p| p.weight()).sum();
+    let mean = |f: &dyn Fn(&Particle) -> f64| -> f64 {
+        pt.iter().map(|p| p.weight() * f(p)).sum::<f64>() / weight
+    };
+
+    let emittance = |i: i32| -> f64 {
+        let x = |p: &Particle| p.position()[i];
+        let u = |p: &Particle| p.normalized_momentum()[i];
+        let (x0, u0) = (mean(&x), mean(&u));
+        let var_x = mean(&|p: &Particle| (x(p) - x0).powi(2));
+        let var_u = mean(&|p: &Particle| (u(p) - u0).powi(2));
+        let cov = mean(&|p: &Particle| (x(p) - x0) * (u(p) - u0));
+        (var_x * var_u - cov * cov).max(0.0).sqrt()
+    };
+
+    (emittance(1), emittance(2))
+}
+
+/// Weighted, empirical cumulative distribution function (CDF)
+/// of an observable, constructed from sorted samples.
+#[allow(unused)]
+#[derive(Clone, Debug)]
+pub struct Cdf {
+    /// Values of the observable, in ascending order
+    values: Vec<f64>,
+    /// Fraction of the total weight carried by samples with values
+    /// less than or equal to the corresponding entry of `values`
+    cumulative: Vec<f64>,
+}
+
+impl Cdf {
+    /// Constructs the CDF of the given `samples`, which are tuples
+    /// of (value, weight). Samples with NaN values are ignored;
+    /// if the remaining samples carry no weight, the CDF is empty.
+    #[allow(unused)]
+    pub fn new(samples: &[(f64, f64)]) -> Self {
+        let mut samples: Vec<(f64, f64)> = samples.iter()
+            .copied()
+            .filter(|s| !s.0.is_nan())
+            .collect();
+        samples.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let total: f64 = samples.iter().map(|s| s.1).sum();
+        if total.is_nan() || total <= 0.0 {
+            samples.clear();
+        }
+        let cumulative = samples.iter()
+            .scan(0.0, |sum, s| {
+                *sum += s.1;
+                Some(*sum / total)
+            })
+            .collect();
+        Cdf {
+            values: samples.iter().map(|s| s.0).collect(),
+            cumulative,
+        }
+    }
+
+    /// Constructs the CDF of the observable `f`, evaluated for each
+    /// of the given particles and weighted accordingly.
+    /// Probe particles are ignored.
+    #[allow(unused)]
+    pub fn of<F: Fn(&Particle) -> f64>(pt: &[Particle], f: F) -> Self {
+        let samples: Vec<(f64, f64)> = pt.iter()
+            .filter(|p| !p.is_probe())
+            .map(|p| (f(p), p.weight()))
+            .collect();
+        Self::new(&samples)
+    }
+
+    /// Returns the CDF of the particle energy, in MeV.
+    #[allow(unused)]
+    pub fn energy(pt: &[Particle]) -> Self {
+        Self::of(pt, |p| p.momentum()[0])
+    }
+
+    /// Returns the CDF of the polar angle, in radians, between the
+    /// particle momentum and the beam axis (-z).
+    #[allow(unused)]
+    pub fn angle(pt: &[Particle]) -> Self {
+        Self::of(pt, |p| {
+            let u = p.normalized_momentum();
+            u[1].hypot(u[2]).atan2(-u[3])
+        })
+    }
+
+    /// Returns the fraction of the total weight with value less than
+    /// or equal to `x`.
+    #[allow(unused)]
+    pub fn at(&self, x: f64) -> f64 {
+        match self.values.partition_point(|v| *v <= x) {
+            0 => 0.0,
+            i => self.cumulative[i - 1],
+        }
+    }
+
+    /// Returns the `q`-th quantile, i.e. the smallest sampled value
+    /// at which the CDF is at least `q`, for 0 ≤ q ≤ 1,
+    /// or None if the CDF is empty.
+    #[allow(unused)]
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let i = self.cumulative.partition_point(|c| *c < q);
+        self.values.get(i.min(self.values.len().saturating_sub(1))).copied()
+    }
+
+    /// Returns the median, i.e. the 0.5 quantile, or None if the CDF
+    /// is empty.
+    #[allow(unused)]
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Returns the sampled values and the CDF at those values,
+    /// in ascending order, e.g. for output.
+    #[allow(unused)]
+    pub fn points(&self) -> Vec<(f64, f64)> {
+        self.values.iter().copied().zip(self.cumulative.iter().copied()).collect()
+    }
+}
+
+/// Weighted means and standard deviations of the phase-space
+/// coordinates of a set of particles. Angles are defined with respect
+/// to the z axis, i.e. x' = p_x / |p_z| and y' = p_y / |p_z|.
+#[allow(unused)]
+#[derive(Copy, Clone, Debug)]
+pub struct BeamSummary {
+    /// Mean energy, in MeV
+    pub mean_energy: f64,
+    /// Standard deviation of the energy, in MeV
+    pub rms_energy: f64,
+    /// Mean position, in m
+    pub mean_position: ThreeVector,
+    /// Standard deviation of the position, in m
+    pub rms_position: ThreeVector,
+    /// Mean of (x', y'), in rad
+    pub mean_divergence: (f64, f64),
+    /// Standard deviation of (x', y'), in rad
+    pub rms_divergence: (f64, f64),
+    /// Geometric rms emittance in the x-x' and y-y' planes, in m rad
+    pub emittance: (f64, f64),
+}
+
+impl BeamSummary {
+    /// Summarizes the given particles. Probe particles are ignored.
+    #[allow(unused)]
+    pub fn from_particles(pt: &[Particle]) -> Self {
+        let pt: Vec<&Particle> = pt.iter().filter(|p| !p.is_probe()).collect();
+        let weight: f64 = pt.iter().map(|p| p.weight()).sum();
+        let mean = |f: &dyn Fn(&Particle) -> f64| -> f64 {
+            pt.iter().map(|p| p.weight() * f(p)).sum::<f64>() / weight
+        };
+
+        let energy = |p: &Particle| p.momentum()[0];
+        let x = |p: &Particle| p.position()[1];
+        let y = |p: &Particle| p.position()[2];
+        let z = |p: &Particle| p.position()[3];
+        let xp = |p: &Particle| { let u = p.normalized_momentum(); u[1] / u[3].abs() };
+        let yp = |p: &Particle| { let u = p.normalized_momentum(); u[2] / u[3].abs() };
+
+        let (e0, x0, y0, z0, xp0, yp0) = (mean(&energy), mean(&x), mean(&y), mean(&z), mean(&xp), mean(&yp));
+
+        // centred second moments
+        let var_x = mean(&|p: &Particle| (x(p) - x0).powi(2));
+        let var_y = mean(&|p: &Particle| (y(p) - y0).powi(2));
+        let var_xp = mean(&|p: &Particle| (xp(p) - xp0).powi(2));
+        let var_yp = mean(&|p: &Particle| (yp(p) - yp0).powi(2));
+        let cov_x = mean(&|p: &Particle| (x(p) - x0) * (xp(p) - xp0));
+        let cov_y = mean(&|p: &Particle| (y(p) - y0) * (yp(p) - yp0));
+
+        BeamSummary {
+            mean_energy: e0,
+            rms_energy: mean(&|p: &Particle| (energy(p) - e0).powi(2)).sqrt(),
+            mean_position: ThreeVector::new(x0, y0, z0),
+            rms_position: ThreeVector::new(var_x.sqrt(), var_y.sqrt(), mean(&|p: &Particle| (z(p) - z0).powi(2)).sqrt()),
+            mean_divergence: (xp0, yp0),
+            rms_divergence: (var_xp.sqrt(), var_yp.sqrt()),
+            emittance: (
+                (var_x * var_xp - cov_x * cov_x).max(0.0).sqrt(),
+                (var_y * var_yp - cov_y * cov_y).max(0.0).sqrt(),
+            ),
+        }
+    }
+
+    /// Returns the single-particle Courant-Snyder invariants, in the x-x' and
+    /// y-y' planes, of the given particles, for the Twiss parameters `alpha`
+    /// and `beta` (in m). These are normalized as actions, i.e.
+    /// `J = (γ x^2 + 2 α x x' + β x'^2) / 2`, where `γ = (1 + α^2) / β`,
+    /// so that the mean over a matched beam is its geometric rms emittance.
+    /// Coordinates are measured relative to the beam centroid, and
+    /// probe particles are ignored.
+    #[allow(unused)]
+    pub fn courant_snyder(pt: &[Particle], alpha: f64, beta: f64) -> Vec<(f64, f64)> {
+        let summary = Self::from_particles(pt);
+        let gamma = (1.0 + alpha * alpha) / beta;
+        let action = |x: f64, xp: f64| 0.5 * (gamma * x * x + 2.0 * alpha * x * xp + beta * xp * xp);
+
+        pt.iter()
+            .filter(|p| !p.is_probe())
+            .map(|p| {
+                let r = p.position();
+                let u = p.normalized_momentum();
+                let x = r[1] - summary.mean_position[0];
+                let y = r[2] - summary.mean_position[1];
+                let xp = u[1] / u[3].abs() - summary.mean_divergence.0;
+                let yp = u[2] / u[3].abs() - summary.mean_divergence.1;
+                (action(x, xp), action(y, yp))
+            })
+            .collect()
+    }
+
+    /// Estimates the rms divergence, in the x and y planes, that is added
+    /// by space charge as a bunch of electrons (or positrons), with total
+    /// charge `charge` (in C), drifts over a distance `length` (in m).
+    ///
+    /// The bunch is modelled as a uniformly filled cylinder, with radius
+    /// a = [2 (σ_x^2 + σ_y^2)]^(1/2) and length √12 σ_z, that has the
+    /// same rms size and mean energy. Inside it, the space-charge force
+    /// is linear, so that x'' = K x / a^2, where K = 2 I / (I_A β^3 γ^3)
+    /// is the generalized perveance, I is the beam current and I_A the
+    /// Alfvén current. The divergence is assumed to grow linearly over
+    /// the drift, i.e. the change in the transverse size is neglected.
+    #[allow(unused)]
+    pub fn space_charge_divergence(&self, charge: f64, length: f64) -> (f64, f64) {
+        let gamma = self.mean_energy / ELECTRON_MASS_MEV;
+        let beta = (1.0 - 1.0 / (gamma * gamma)).sqrt();
+        let (sigma_x, sigma_y, sigma_z) = (self.rms_position[0], self.rms_position[1], self.rms_position[2]);
+        let radius_sqd = 2.0 * (sigma_x.powi(2) + sigma_y.powi(2));
+        let current = charge.abs() * beta * SPEED_OF_LIGHT / (12_f64.sqrt() * sigma_z);
+        let alfven_current = 4.0 * std::f64::consts::PI * VACUUM_PERMITTIVITY * ELECTRON_MASS * SPEED_OF_LIGHT.powi(3) / ELEMENTARY_CHARGE;
+        let perveance = 2.0 * current / (alfven_current * (beta * gamma).powi(3));
+        (
+            perveance * length * sigma_x / radius_sqd,
+            perveance * length * sigma_y / radius_sqd,
+        )
+    }
+}
+
+/// Weighted sums of the phase-space coordinates of a set of particles,
+/// from which their [BeamSummary] and [rms_emittance] can be obtained.
+/// Unlike those functions, this does not require all the particles to be
+/// held at once: the sums for separate sets of particles, e.g. successive
+/// chunks or different MPI tasks, are combined by adding them together.
+/// Probe particles are ignored.
+#[allow(unused)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BeamMoments {
+    /// The particle count and the total weight, followed by the
+    /// weighted sums of the coordinates and their products
+    sums: [f64; 22],
+}
+
+#[allow(unused)]
+impl BeamMoments {
+    /// Returns the moments of the given particles
+    pub fn from_particles(pt: &[Particle]) -> Self {
+        let mut moments = Self::default();
+        moments.add(pt);
+        moments
+    }
+
+    /// Adds the given particles to the sums
+    pub fn add(&mut self, pt: &[Particle]) {
+        for p in pt.iter().filter(|p| !p.is_probe()) {
+            let w = p.weight();
+            let energy = p.momentum()[0];
+            let r = p.position();
+            let u = p.normalized_momentum();
+            let xp = u[1] / u[3].abs();
+            let yp = u[2] / u[3].abs();
+            let terms = [
+                1.0,
+                energy, energy * energy,
+                r[1], r[1] * r[1], r[2], r[2] * r[2], r[3], r[3] * r[3],
+                xp, xp * xp, yp, yp * yp, r[1] * xp, r[2] * yp,
+                u[1], u[1] * u[1], r[1] * u[1], u[2], u[2] * u[2], r[2] * u[2],
+            ];
+            self.sums[0] += 1.0;
+            for (sum, term) in self.sums[1..].iter_mut().zip(terms.iter()) {
+                *sum += w * term;
+            }
+        }
+    }
+
+    /// The sums, e.g. for reduction across MPI tasks
+    pub fn as_slice(&self) -> &[f64] {
+        &self.sums
+    }
+
+    /// The sums, e.g. for reduction across MPI tasks
+    pub fn as_mut_slice(&mut self) -> &mut [f64] {
+        &mut self.sums
+    }
+
+    /// Returns the number of particles
+    pub fn count(&self) -> usize {
+        self.sums[0].round() as usize
+    }
+
+    /// Returns the total weight of the particles
+    pub fn weight(&self) -> f64 {
+        self.sums[1]
+    }
+
+    /// Returns the total energy of the particles, in MeV
+    pub fn energy(&self) -> f64 {
+        self.sums[2]
+    }
+
+    /// Mean of the i-th sum
+    fn mean(&self, i: usize) -> f64 {
+        self.sums[i] / self.weight()
+    }
+
+    /// Variance of the quantity whose mean and mean square are the
+    /// i-th and j-th sums
+    fn var(&self, i: usize, j: usize) -> f64 {
+        (self.mean(j) - self.mean(i).powi(2)).max(0.0)
+    }
+
+    /// Covariance of the quantities whose means are the i-th and j-th sums,
+    /// and the mean of whose product is the k-th sum
+    fn cov(&self, i: usize, j: usize, k: usize) -> f64 {
+        self.mean(k) - self.mean(i) * self.mean(j)
+    }
+
+    /// Returns the [BeamSummary] of the particles
+    pub fn summary(&self) -> BeamSummary {
+        let emittance = |x: usize, xp: usize, cov: usize| {
+            (self.var(x, x + 1) * self.var(xp, xp + 1) - self.cov(x, xp, cov).powi(2)).max(0.0).sqrt()
+        };
+
+        BeamSummary {
+            mean_energy: self.mean(2),
+            rms_energy: self.var(2, 3).sqrt(),
+            mean_position: ThreeVector::new(self.mean(4), self.mean(6), self.mean(8)),
+            rms_position: ThreeVector::new(self.var(4, 5).sqrt(), self.var(6, 7).sqrt(), self.var(8, 9).sqrt()),
+            mean_divergence: (self.mean(10), self.mean(12)),
+            rms_divergence: (self.var(10, 11).sqrt(), self.var(12, 13).sqrt()),
+            emittance: (emittance(4, 10, 14), emittance(6, 12, 15)),
+        }
+    }
+
+    /// Returns the normalized rms emittance of the particles, as given by [rms_emittance]
+    pub fn normalized_emittance(&self) -> (f64, f64) {
+        let emittance = |x: usize, u: usize, cov: usize| {
+            (self.var(x, x + 1) * self.var(u, u + 1) - self.cov(x, u, cov).powi(2)).max(0.0).sqrt()
+        };
+        (emittance(4, 16, 18), emittance(6, 19, 21))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_distr::StandardNormal;
+    use rand_xoshiro::*;
+    use crate::geometry::FourVector;
+    use crate::particle::Species;
+    use super::*;
+
+    #[test]
+    fn identical_polarization() {
+        let pol = StokesVector::new(1.0, 0.6, 0.0, 0.8);
+        let photons: Vec<Particle> = (0..10)
+            .map(|i| {
+                Particle::create(Species::Photon, [0.0; 4].into())
+                    .with_normalized_momentum(FourVector::lightlike(0.0, 0.0, -1000.0))
+                    .with_polarization(pol)
+                    .with_weight(1.0 + i as f64)
+            })
+            .collect();
+
+        let (sv, dop) = mean_polarization(&photons).unwrap();
+        println!("<S> = [{:.3e}, {:.3e}, {:.3e}, {:.3e}], dop = {:.6e}", sv[0], sv[1], sv[2], sv[3], dop);
+        for i in 0..4 {
+            assert!((sv[i] - pol[i]).abs() < 1.0e-12);
+        }
+        assert!((dop - 1.0).abs() < 1.0e-12);
+
+        // no particles, or no weight, have no mean polarization
+        assert!(mean_polarization(&[]).is_none());
+        let weightless: Vec<Particle> = photons.iter().map(|pt| { let mut pt = *pt; pt.with_weight(0.0) }).collect();
+        assert!(mean_polarization(&weightless).is_none());
+    }
+
+    #[test]
+    fn matched_beam_invariants() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (alpha, beta, emittance) = (-1.5, 0.2, 2.0e-9);
+        let gamma = 1000.0;
+
+        // x = sqrt(eps beta) u1, x' = sqrt(eps / beta) (u2 - alpha u1)
+        let mut sample = || -> (f64, f64) {
+            let u1: f64 = rng.sample(StandardNormal);
+            let u2: f64 = rng.sample(StandardNormal);
+            ((emittance * beta).sqrt() * u1, (emittance / beta).sqrt() * (u2 - alpha * u1))
+        };
+
+        let electrons: Vec<Particle> = (0..100_000)
+            .map(|_| {
+                let (x, xp) = sample();
+                let (y, yp) = sample();
+                Particle::create(Species::Electron, FourVector::new(0.0, x, y, 0.0))
+                    .with_normalized_momentum(FourVector::new(0.0, gamma * xp, gamma * yp, -gamma).unitize())
+            })
+            .collect();
+
+        let summary = BeamSummary::from_particles(&electrons);
+        let invariants = BeamSummary::courant_snyder(&electrons, alpha, beta);
+        let n = invariants.len() as f64;
+
+        let mean_x = invariants.iter().map(|j| j.0).sum::<f64>() / n;
+        let mean_y = invariants.iter().map(|j| j.1).sum::<f64>() / n;
+
+        // exponentially distributed: P(J > eps) = 1/e
+        let frac_x = invariants.iter().filter(|j| j.0 > emittance).count() as f64 / n;
+        let frac_y = invariants.iter().filter(|j| j.1 > emittance).count() as f64 / n;
+
+        println!("<J> = ({:.4e}, {:.4e}), emittance = {:.4e} [summary: ({:.4e}, {:.4e})]", mean_x, mean_y, emittance, summary.emittance.0, summary.emittance.1);
+        println!("P(J > eps) = ({:.4}, {:.4}), expected {:.4}", frac_x, frac_y, (-1_f64).exp());
+
+        for mean in [mean_x, mean_y].iter() {
+            assert!((mean - emittance).abs() < 0.02 * emittance);
+        }
+        for frac in [frac_x, frac_y].iter() {
+            assert!((frac - (-1_f64).exp()).abs() < 0.01);
+        }
+        assert!((summary.emittance.0 - emittance).abs() < 0.02 * emittance);
+    }
+
+    #[test]
+    fn space_charge_expansion() {
+        use crate::particle::{BeamBuilder, LongitudinalDistribution};
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (gamma, radius, bunch_length) = (20.0, 100.0e-6, 300.0e-6);
+        let (charge, drift) = (1.0e-9, 0.01);
+        let summary = BeamBuilder::monoenergetic(Species::Electron, 1, gamma)
+            .with_uniformly_distributed_xy(radius)
+            .with_longitudinal_profile(LongitudinalDistribution::Uniform { length: bunch_length })
+            .summary(&mut rng, 100_000);
+        let (div_x, div_y) = summary.space_charge_divergence(charge, drift);
+
+        // radial kick at the edge of a uniform cylinder: r' = K L / a,
+        // where K = e I / (2 pi eps_0 m c^3 beta^3 gamma^3), and rms x = a / 2
+        let beta = (1.0 - 1.0 / (gamma * gamma)).sqrt();
+        let current = charge * beta * SPEED_OF_LIGHT / bunch_length;
+        let perveance = ELEMENTARY_CHARGE * current / (2.0 * std::f64::consts::PI * VACUUM_PERMITTIVITY * ELECTRON_MASS * SPEED_OF_LIGHT.powi(3) * (beta * gamma).powi(3));
+        let target = 0.5 * perveance * drift / radius;
+
+        let error = ((div_x - target).abs() / target, (div_y - target).abs() / target);
+        println!("space-charge divergence = ({:.4e}, {:.4e}), expected {:.4e}, error = ({:.3e}, {:.3e})", div_x, div_y, target, error.0, error.1);
+        assert!(error.0 < 0.02 && error.1 < 0.02);
+    }
+
+    #[test]
+    fn emittance_growth() {
+        use crate::field::{FocusedLaser, Laser, Polarization};
+        use crate::particle::BeamBuilder;
+        use crate::simulation::Simulation;
+
+        let beam = BeamBuilder::monoenergetic(Species::Electron, 1000, 1000.0)
+            .with_normally_distributed_xy(2.0e-6, 2.0e-6)
+            .with_divergence(1.0e-4);
+
+        // initial, and final after colliding with a laser of amplitude a0
+        let emittance = |a0: f64| -> ((f64, f64), (f64, f64)) {
+            let laser: Laser = FocusedLaser::new(a0, 0.8e-6, 2.0e-6, 10.0, Polarization::Linear, 0.0).into();
+            let sim = Simulation::new(beam, laser);
+            let initial = sim.build_beams(&mut Xoshiro256StarStar::seed_from_u64(0));
+            let output = sim.run(&mut Xoshiro256StarStar::seed_from_u64(0));
+            (rms_emittance(&initial), rms_emittance(&output.particles))
+        };
+
+        let (before, after) = emittance(0.0);
+        println!("without laser: emittance = ({:.4e}, {:.4e}) before, ({:.4e}, {:.4e}) after", before.0, before.1, after.0, after.1);
+        assert!(((after.0 - before.0) / before.0).abs() < 1.0e-3);
+        assert!(((after.1 - before.1) / before.1).abs() < 1.0e-3);
+
+        let (before, after) = emittance(100.0);
+        println!("with a0 = 100: emittance = ({:.4e}, {:.4e}) before, ({:.4e}, {:.4e}) after", before.0, before.1, after.0, after.1);
+        assert!(after.0 > 2.0 * before.0);
+        assert!(after.1 > 2.0 * before.1);
+    }
+
+    #[test]
+    fn moments_in_chunks() {
+        use crate::particle::BeamBuilder;
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let pt = BeamBuilder::new(Species::Electron, 1000)
+            .with_normal_energy_spectrum(1000.0, 100.0)
+            .with_normally_distributed_xy(2.0e-6, 3.0e-6)
+            .with_divergence(1.0e-3)
+            .build(&mut rng);
+
+        // adding particles in chunks is equivalent to summarizing them all at once
+        let mut moments = BeamMoments::default();
+        for chunk in pt.chunks(37) {
+            moments.add(chunk);
+        }
+
+        let summary = moments.summary();
+        let target = BeamSummary::from_particles(&pt);
+        let emittance = moments.normalized_emittance();
+        let target_emittance = rms_emittance(&pt);
+        println!("chunked: {:?}, {:?}", summary, emittance);
+        println!("at once: {:?}, {:?}", target, target_emittance);
+
+        let close = |a: f64, b: f64| ((a - b) / b).abs() < 1.0e-6;
+        assert_eq!(moments.count(), 1000);
+        assert!(close(moments.weight(), pt.iter().map(|p| p.weight()).sum()));
+        assert!(close(summary.mean_energy, target.mean_energy));
+        assert!(close(summary.rms_energy, target.rms_energy));
+        assert!(close(summary.rms_position[0], target.rms_position[0]));
+        assert!(close(summary.rms_position[1], target.rms_position[1]));
+        assert!(close(summary.rms_divergence.0, target.rms_divergence.0));
+        assert!(close(summary.emittance.0, target.emittance.0));
+        assert!(close(summary.emittance.1, target.emittance.1));
+        assert!(close(emittance.0, target_emittance.0));
+        assert!(close(emittance.1, target_emittance.1));
+    }
+
+    #[test]
+    fn energy_quantiles() {
+        use crate::particle::BeamBuilder;
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let num = 100_000;
+        let (gamma, sigma) = (1000.0, 100.0);
+        let beam = BeamBuilder::new(Species::Electron, num)
+            .with_normal_energy_spectrum(gamma, sigma)
+            .with_divergence(1.0e-3)
+            .build(&mut rng);
+
+        let cdf = Cdf::energy(&beam);
+        let (mean, std_dev) = (gamma * ELECTRON_MASS_MEV, sigma * ELECTRON_MASS_MEV);
+        let std_err = std_dev / (num as f64).sqrt();
+
+        // standard error of the median is sqrt(pi / 2) times that of the mean
+        let median = cdf.median().unwrap();
+        let error = (median - mean).abs() / (1.2533 * std_err);
+        println!("median energy = {:.6e} MeV, mean = {:.6e} MeV, {:.3} standard errors apart", median, mean, error);
+        assert!(error < 4.0);
+        assert!((cdf.at(median) - 0.5).abs() < 1.0e-3);
+
+        // 90th percentile of a normal distribution is 1.2816 sigma above the mean
+        let p90 = cdf.quantile(0.9).unwrap();
+        let expected = mean + 1.2816 * std_dev;
+        println!("90th percentile = {:.6e} MeV, expected {:.6e} MeV", p90, expected);
+        assert!((p90 - expected).abs() < 0.02 * std_dev);
+
+        // the CDF is monotonic and runs from 0 to 1
+        let points = cdf.points();
+        assert_eq!(points.len(), num);
+        assert!(points.windows(2).all(|w| w[1].0 >= w[0].0 && w[1].1 >= w[0].1));
+        assert!((points[num - 1].1 - 1.0).abs() < 1.0e-12);
+        assert_eq!(cdf.at(points[0].0 - 1.0), 0.0);
+
+        // angles are non-negative
+        let angles = Cdf::angle(&beam);
+        assert!(angles.quantile(0.0).unwrap() >= 0.0);
+        let median = angles.median().unwrap();
+        assert!(median > 0.0 && median < 3.0e-3);
+    }
+
+    #[test]
+    fn degenerate_cdf() {
+        // no samples, or no weight
+        for samples in [vec![], vec![(1.0, 0.0), (2.0, 0.0)]].iter() {
+            let cdf = Cdf::new(samples);
+            assert!(cdf.median().is_none());
+            assert!(cdf.points().is_empty());
+            assert_eq!(cdf.at(1.5), 0.0);
+        }
+
+        // NaN values are ignored
+        let cdf = Cdf::new(&[(1.0, 1.0), (f64::NAN, 1.0), (3.0, 1.0), (2.0, 1.0)]);
+        assert_eq!(cdf.points().len(), 3);
+        assert_eq!(cdf.median(), Some(2.0));
+        assert_eq!(cdf.quantile(1.0), Some(3.0));
+        assert!((cdf.at(1.0) - 1.0 / 3.0).abs() < 1.0e-12);
+    }
+}
@@ -0,0 +1,715 @@
+//! Propagation of single particles through a field
+
+use rand::prelude::*;
+use crate::field::{Field, EquationOfMotion, RadiationEvent, RadiationMode};
+use crate::geometry::{FourVector, ThreeVector};
+use crate::particle::{Particle, Species};
+
+/// Advances `pt` by a single timestep `dt`, updating its position,
+/// momentum and absorbed energy, and returns the change in lab time.
+/// Photons are unaffected by the field, so they are moved along
+/// straight lines without calling the field's pusher.
+fn step<F: Field>(field: &F, pt: &mut Particle, dt: f64, eqn: EquationOfMotion) -> f64 {
+    if pt.species() == Species::Photon {
+        free_stream(pt, dt);
+        return dt;
+    }
+
+    let (r, u, dt_actual, work) = field.push(
+        pt.position(),
+        pt.normalized_momentum(),
+        pt.charge_to_mass_ratio(),
+        dt,
+        eqn,
+    );
+    pt.with_position(r);
+    pt.with_normalized_momentum(u);
+    pt.update_absorbed_energy(work);
+    dt_actual
+}
+
+/// Moves `pt` along a straight line, at its current velocity,
+/// for a time `dt`.
+fn free_stream(pt: &mut Particle, dt: f64) {
+    use crate::constants::SPEED_OF_LIGHT;
+    let u = pt.normalized_momentum();
+    pt.with_position(pt.position() + SPEED_OF_LIGHT * u * dt / u[0]);
+}
+
+/// Returns true if `pt` starts inside `field`, or upstream of the pulse, as
+/// determined by [Field::contains], but closer to the pulse than a particle
+/// placed according to [Field::ideal_initial_z], i.e. at `z = z0` at
+/// time `-z0/c`. All lasers propagate along +z and are centred on the
+/// origin, so the latter is equivalent to `ct - z > -2 z0`.
+/// Particles that are already downstream of the pulse, or outside the
+/// domain of the field, do not start inside.
+#[allow(unused)]
+pub fn starts_inside<F: Field>(field: &F, pt: &Particle) -> bool {
+    let r = pt.position();
+    field.contains(r) && r[0] - r[3] > -2.0 * field.ideal_initial_z()
+}
+
+/// If `pt` starts inside the field (see [starts_inside]), moves it backwards
+/// along its straight-line trajectory to the point where it would have been
+/// sufficiently distant from the laser. Returns `None` if this is impossible,
+/// i.e. if the particle is moving along +z at the speed of light.
+#[allow(unused)]
+pub fn move_to_field_free_start<F: Field>(field: &F, pt: Particle) -> Option<Particle> {
+    if !starts_inside(field, &pt) {
+        return Some(pt);
+    }
+
+    let r = pt.position();
+    let u = pt.normalized_momentum();
+
+    // d(ct - z)/d(ct) = 1 - u_z / gamma along the trajectory
+    let rate = 1.0 - u[3] / u[0];
+    if rate <= 0.0 {
+        return None;
+    }
+
+    let ct = (r[0] - r[3] + 2.0 * field.ideal_initial_z()) / rate;
+    let mut pt = pt;
+    pt.with_position(r - ct * u / u[0]);
+    Some(pt)
+}
+
+/// Advances `pt` through `field`, using timestep `dt`, until it
+/// leaves the field, returning the particle at its final position.
+/// If a `plane` is given, the particle reflects off it whenever a step
+/// takes it across (see [ReflectingPlane::reflect]).
+#[allow(unused)]
+pub fn propagate<F: Field>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion, plane: Option<ReflectingPlane>) -> Particle {
+    let mut pt = pt;
+    while field.contains(pt.position()) {
+        step(field, &mut pt, dt, eqn);
+        if let Some(plane) = plane.as_ref() {
+            plane.reflect(&mut pt);
+        }
+    }
+    pt
+}
+
+/// As [propagate], but also returns the number of steps taken.
+#[allow(unused)]
+pub fn propagate_counting<F: Field>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion) -> (Particle, usize) {
+    let mut pt = pt;
+    let mut n = 0;
+    while field.contains(pt.position()) {
+        step(field, &mut pt, dt, eqn);
+        n += 1;
+    }
+    (pt, n)
+}
+
+/// A plane off which particles reflect specularly, rather than
+/// passing through it (see [propagate]).
+#[derive(Copy, Clone)]
+pub struct ReflectingPlane {
+    normal: ThreeVector,
+    point: ThreeVector,
+}
+
+impl ReflectingPlane {
+    /// Constructs the plane that passes through `point` (in m) with the
+    /// given `normal`, which points into the region where particles
+    /// are allowed to move. The normal need not be a unit vector.
+    #[allow(unused)]
+    pub fn new(normal: ThreeVector, point: ThreeVector) -> Self {
+        ReflectingPlane {
+            normal: normal.normalize(),
+            point,
+        }
+    }
+
+    /// Returns the signed distance of the four-position `r` from the plane,
+    /// which is negative if `r` is on the forbidden side.
+    fn distance(&self, r: FourVector) -> f64 {
+        (ThreeVector::from(r) - self.point) * self.normal
+    }
+
+    /// If `pt` has crossed the plane, mirrors its position back into the
+    /// allowed region and reverses the normal component of its momentum,
+    /// preserving the tangential components and its energy.
+    /// Returns true if the particle was reflected.
+    ///
+    /// This is applied at the end of a step, so the point of impact is
+    /// resolved only to within the distance travelled in a single timestep.
+    pub fn reflect(&self, pt: &mut Particle) -> bool {
+        let r = pt.position();
+        let d = self.distance(r);
+        if d >= 0.0 {
+            return false;
+        }
+
+        let u = pt.normalized_momentum();
+        let u_normal = ThreeVector::from(u) * self.normal;
+        // only reflect particles that are moving away from the plane
+        if u_normal < 0.0 {
+            pt.with_normalized_momentum((ThreeVector::from(u) - 2.0 * u_normal * self.normal).with_time(u[0]));
+        }
+        pt.with_position((ThreeVector::from(r) - 2.0 * d * self.normal).with_time(r[0]));
+        true
+    }
+}
+
+/// Creates the photon emitted by `parent` in the given `event`, at
+/// the position, and with the momentum and polarization, given by the
+/// event. The photon inherits the weight, id (as its parent id) and
+/// probe status of its parent, and so is ready to be tracked, e.g.
+/// through a second laser pulse. Its own id is left unset.
+pub fn photon_from_event(event: &RadiationEvent, parent: &Particle) -> Particle {
+    let mut photon = Particle::create(Species::Photon, event.r)
+        .with_payload(event.a_eff)
+        .with_parent_chi(event.chi)
+        .with_weight(parent.weight())
+        .with_parent_id(parent.id())
+        .with_polarization(event.pol)
+        .with_normalized_momentum(event.k);
+    if parent.is_probe() {
+        photon.as_probe();
+    }
+    photon
+}
+
+/// As [propagate], but also samples photon emission along the
+/// trajectory, returning the particle at its final position and
+/// the photons it emitted (see [photon_from_event]), in order of
+/// emission. The emitted photons are not tracked.
+///
+/// The particle recoils on emission if `mode` is `RadiationMode::Quantum`.
+/// Its interaction count is incremented by the multiplicity.
+#[allow(unused)]
+pub fn propagate_collecting_photons<F: Field, R: Rng>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion, rng: &mut R, mode: RadiationMode) -> (Particle, Vec<Particle>) {
+    let mut pt = pt;
+    let mut photons = Vec::new();
+    while field.contains(pt.position()) {
+        let dt_actual = step(field, &mut pt, dt, eqn);
+        if pt.species() == Species::Photon {
+            continue;
+        }
+        if let Some(event) = field.radiate(pt.position(), pt.normalized_momentum(), dt_actual, rng, mode, 1.0) {
+            photons.push(photon_from_event(&event, &pt));
+            if mode == RadiationMode::Quantum {
+                pt.with_normalized_momentum(event.u_prime);
+                pt.update_absorbed_energy(event.absorption);
+            }
+            pt.update_interaction_count(1.0);
+        }
+    }
+    (pt, photons)
+}
+
+/// As [propagate_collecting_photons], but returns only the number
+/// of photons emitted, i.e. the multiplicity.
+#[allow(unused)]
+pub fn propagate_radiating<F: Field, R: Rng>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion, rng: &mut R, mode: RadiationMode) -> (Particle, usize) {
+    let (pt, photons) = propagate_collecting_photons(field, pt, dt, eqn, rng, mode);
+    (pt, photons.len())
+}
+
+/// Advances `pt` through `field` until the lab time reaches `t_final`
+/// (in seconds), whether or not the particle has left the field,
+/// using timestep `dt`. The last step is shortened so as not to
+/// overshoot `t_final`. Particles that start later than `t_final`
+/// are returned unchanged.
+#[allow(unused)]
+pub fn propagate_until_time<F: Field>(field: &F, pt: Particle, t_final: f64, dt: f64, eqn: EquationOfMotion) -> Particle {
+    use crate::constants::SPEED_OF_LIGHT;
+    let mut pt = pt;
+    loop {
+        // the time elapsed in a step need not be exactly that requested,
+        // e.g. for pushers that step in proper time, so it is taken from
+        // the particle's position, and we stop within a small fraction
+        // of a step of the target
+        let remaining = t_final - pt.position()[0] / SPEED_OF_LIGHT;
+        if remaining <= 1.0e-6 * dt {
+            break;
+        }
+        step(field, &mut pt, dt.min(remaining), eqn);
+    }
+    pt
+}
+
+/// Advances the photon `pt` through `field`, using timestep `dt`, until it
+/// leaves the field, checking for pair creation after every step.
+/// Returns the photon at its final position and the total probability
+/// that it would have created a pair, which is also added to its
+/// interaction count. The photon is not depleted.
+#[allow(unused)]
+pub fn propagate_photon<F: Field, R: Rng>(field: &F, pt: Particle, dt: f64, rng: &mut R) -> (Particle, f64) {
+    let mut pt = pt;
+    let mut total = 0.0;
+    while field.contains(pt.position()) {
+        free_stream(&mut pt, dt);
+        let (prob, _, _) = field.pair_create(pt.position(), pt.normalized_momentum(), pt.polarization(), dt, rng, 1.0);
+        pt.update_interaction_count(prob);
+        total += prob;
+    }
+    (pt, total)
+}
+
+/// Estimates the number of photons emitted by `pt`, and the number of
+/// electron-positron pairs subsequently created by those photons, without
+/// Monte Carlo sampling. `pt` should be representative of the beam,
+/// e.g. have its mean energy and position.
+///
+/// The LCFA emission rate is integrated along the particle's trajectory,
+/// which is calculated using `eqn` and timestep `dt`. This should be
+/// `ModifiedLandauLifshitz`, if available, to account for the average
+/// energy loss. The photons emitted in each step are assumed to have the
+/// mean energy of the synchrotron spectrum and to move parallel to the
+/// particle, and the LCFA pair creation rate is integrated along the
+/// remainder of the trajectory. As this neglects stochastic effects and
+/// the breadth of the photon spectrum, the result is indicative only.
+///
+/// The fields must be available via [Field::fields].
+#[allow(unused)]
+#[allow(non_snake_case)]
+pub fn estimate_yield<F: Field>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion) -> (f64, f64) {
+    use crate::constants::{ALPHA_FINE, COMPTON_TIME, CRITICAL_FIELD, SPEED_OF_LIGHT};
+    use crate::geometry::StokesVector;
+    use crate::lcfa;
+
+    // transverse force on a particle moving along n at speed beta, and the
+    // magnitude of the electric field in its rest frame, divided by gamma
+    let rest_frame_field = |n: ThreeVector, beta: f64, E: ThreeVector, B: ThreeVector| -> (ThreeVector, f64) {
+        let F = E + SPEED_OF_LIGHT * beta * n.cross(B);
+        let E_rf_sqd = F.norm_sqr() - (beta * (E * n)).powi(2);
+        (F - (F * n) * n, E_rf_sqd.max(0.0).sqrt())
+    };
+
+    // (dt, fields) at every step
+    let mut pt = pt;
+    let mut trajectory = vec![];
+    let mut photons = 0.0;
+    let mut emitted = vec![];
+
+    while field.contains(pt.position()) {
+        let dt_actual = step(field, &mut pt, dt, eqn);
+        let (E, B, _) = field.fields(pt.position());
+        trajectory.push((dt_actual, E, B));
+
+        let u = pt.normalized_momentum();
+        let n = ThreeVector::from(u).normalize();
+        let beta = ThreeVector::from(u).norm_sqr().sqrt() / u[0];
+        let chi = u[0] * rest_frame_field(n, beta, E, B).1 / CRITICAL_FIELD;
+        if chi <= 0.0 {
+            continue;
+        }
+
+        let rate = lcfa::photon_emission::rate(chi, u[0]);
+        let power = 2.0 * ALPHA_FINE * chi * chi * lcfa::photon_emission::gaunt_factor(chi) / (3.0 * COMPTON_TIME);
+        let number = rate * dt_actual;
+        let omega = power / rate;
+
+        photons += number;
+        emitted.push((trajectory.len(), number, omega * FourVector::new(1.0, n[0], n[1], n[2])));
+    }
+
+    let pairs = emitted.iter()
+        .map(|(start, number, ell)| {
+            let n = ThreeVector::from(*ell).normalize();
+            let exponent: f64 = trajectory[*start..].iter()
+                .map(|(dt, E, B)| {
+                    let (a_perp, E_rf) = rest_frame_field(n, 1.0, *E, *B);
+                    let chi = ell[0] * E_rf / CRITICAL_FIELD;
+                    if chi > 0.0 {
+                        lcfa::pair_creation::probability(*ell, StokesVector::unpolarized(), chi, a_perp, *dt).0
+                    } else {
+                        0.0
+                    }
+                })
+                .sum();
+            number * (1.0 - (-exponent).exp())
+        })
+        .sum();
+
+    (photons, pairs)
+}
+
+/// As [propagate], but calls `callback(step, r, u, (E, B, a))` after
+/// every step, where `r` and `u` are the particle's new position and
+/// normalized momentum, and `E`, `B` and `a` the fields there.
+/// Steps are numbered from zero.
+#[allow(unused)]
+pub fn propagate_with<F, C>(field: &F, pt: Particle, dt: f64, eqn: EquationOfMotion, mut callback: C) -> Particle
+where
+    F: Field,
+    C: FnMut(usize, FourVector, FourVector, (ThreeVector, ThreeVector, f64))
+{
+    let mut pt = pt;
+    let mut n = 0;
+    while field.contains(pt.position()) {
+        step(field, &mut pt, dt, eqn);
+        let r = pt.position();
+        callback(n, r, pt.normalized_momentum(), field.fields(r));
+        n += 1;
+    }
+    pt
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::*;
+    use rand_xoshiro::*;
+    use crate::field::{FastPlaneWave, FocusedLaser, Laser, PlaneWave, Polarization};
+    use crate::lcfa;
+    use super::*;
+
+    #[test]
+    fn callback_every_step() {
+        let laser = FastPlaneWave::new(10.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -100.0).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u);
+
+        // count steps by hand
+        let mut expected = 0;
+        let (mut r, mut u) = (electron.position(), electron.normalized_momentum());
+        while laser.contains(r) {
+            let new = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            r = new.0;
+            u = new.1;
+            expected += 1;
+        }
+
+        let mut calls = 0;
+        let mut t_last = electron.position()[0];
+        let mut monotonic = true;
+        let final_pt = propagate_with(&laser, electron, dt, EquationOfMotion::Lorentz, |n, r, _, _| {
+            monotonic = monotonic && n == calls && r[0] > t_last;
+            t_last = r[0];
+            calls += 1;
+        });
+
+        println!("callback called {} times, expected {}, final ct = {:.6e} m", calls, expected, final_pt.position()[0]);
+        assert_eq!(calls, expected);
+        assert!(monotonic);
+        assert_eq!(final_pt.position()[0], r[0]);
+    }
+
+    #[test]
+    fn step_count() {
+        let laser = FastPlaneWave::new(10.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let photon = Particle::create(Species::Photon, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(FourVector::lightlike(0.0, 0.0, -1000.0));
+
+        let (final_pt, n) = propagate_counting(&laser, photon, dt, EquationOfMotion::Lorentz);
+        let distance = (final_pt.position()[3] - z0).abs();
+        let expected = distance / (SPEED_OF_LIGHT * dt);
+        println!("took {} steps to travel {:.3e} m, expected {:.3}", n, distance, expected);
+        assert!((n as f64 - expected).abs() <= 1.0);
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn mean_multiplicity() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u);
+
+        // without recoil, the trajectory is deterministic, so integrate
+        // the emission rate along it
+        let mut expected = 0.0;
+        propagate_with(&laser, electron, dt, EquationOfMotion::Lorentz, |_, _, u, (E, B, _)| {
+            let beta = ThreeVector::from(u) / u[0];
+            let E_rf_sqd = (E + SPEED_OF_LIGHT * beta.cross(B)).norm_sqr() - (E * beta).powi(2);
+            let chi = u[0] * E_rf_sqd.max(0.0).sqrt() / CRITICAL_FIELD;
+            expected += dt * lcfa::photon_emission::classical::rate(chi, u[0]);
+        });
+
+        let num = 1000;
+        let total: usize = (0..num)
+            .map(|_| propagate_radiating(&laser, electron, dt, EquationOfMotion::Lorentz, &mut rng, RadiationMode::Classical).1)
+            .sum();
+        let mean = (total as f64) / (num as f64);
+
+        let error = (mean - expected).abs() / expected;
+        println!("mean multiplicity = {:.4}, expected = {:.4}, error = {:.3e}", mean, expected, error);
+        assert!(error < 0.075);
+    }
+
+    #[test]
+    fn reposition_inside_field() {
+        let laser = FocusedLaser::new(10.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0);
+        let z0 = laser.ideal_initial_z();
+
+        // at the focus, moving at an angle to the laser axis
+        let u = FourVector::new(0.0, 10.0, 0.0, -100.0).unitize();
+        let r = FourVector::new(0.0, 1.0e-6, 0.0, 0.0);
+        let electron = Particle::create(Species::Electron, r).with_normalized_momentum(u);
+        assert!(starts_inside(&laser, &electron));
+
+        let moved = move_to_field_free_start(&laser, electron).unwrap();
+        let r_new = moved.position();
+        let dr = r - r_new;
+        println!("moved from [{:.3e}, {:.3e}, {:.3e}, {:.3e}] to [{:.3e}, {:.3e}, {:.3e}, {:.3e}]", r[0], r[1], r[2], r[3], r_new[0], r_new[1], r_new[2], r_new[3]);
+        assert!(!starts_inside(&laser, &moved));
+        assert!(((r_new[0] - r_new[3]) + 2.0 * z0).abs() < 1.0e-9 * z0);
+
+        // momentum unchanged, and displacement parallel to the velocity
+        let u_new = moved.normalized_momentum();
+        assert!(u_new == u);
+        let deviation = ThreeVector::from(dr).cross(ThreeVector::from(u)).norm_sqr().sqrt() / (ThreeVector::from(dr).norm_sqr().sqrt() * ThreeVector::from(u).norm_sqr().sqrt());
+        assert!(dr[0] > 0.0 && deviation < 1.0e-12);
+        assert!((ThreeVector::from(dr).norm_sqr().sqrt() / dr[0] - ThreeVector::from(u).norm_sqr().sqrt() / u[0]).abs() < 1.0e-12);
+
+        // particles already outside are unaffected
+        let outside = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0)).with_normalized_momentum(u);
+        assert!(!starts_inside(&laser, &outside));
+        assert!(move_to_field_free_start(&laser, outside).unwrap().position() == outside.position());
+
+        // as are those that the pulse has already passed
+        let downstream = Particle::create(Species::Electron, FourVector::new(10.0 * z0, 0.0, 0.0, 0.0)).with_normalized_momentum(u);
+        assert!(!laser.contains(downstream.position()));
+        assert!(!starts_inside(&laser, &downstream));
+        assert!(move_to_field_free_start(&laser, downstream).unwrap().position() == downstream.position());
+
+        // and those outside a restricted domain
+        let cylinder = FocusedLaser::new(10.0, 0.8e-6, 4.0e-6, 10.0, Polarization::Circular, 0.0)
+            .with_domain_cylinder(0.5e-6, -z0, z0);
+        assert!(!starts_inside(&cylinder, &electron));
+        assert!(move_to_field_free_start(&cylinder, electron).unwrap().position() == r);
+
+        // photons copropagating with the laser cannot be moved
+        let photon = Particle::create(Species::Photon, r).with_normalized_momentum(FourVector::lightlike(0.0, 0.0, 100.0));
+        assert!(move_to_field_free_start(&laser, photon).is_none());
+    }
+
+    #[test]
+    fn synchronized_final_time() {
+        // the ponderomotive pusher steps in proper time, so the lab time
+        // elapsed in a step is not exactly that requested
+        let lasers: [Laser; 2] = [
+            FastPlaneWave::new(10.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0).into(),
+            PlaneWave::new(10.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0).into(),
+        ];
+
+        for laser in lasers.iter() {
+            let dt = laser.max_timestep().unwrap();
+            let z0 = laser.ideal_initial_z();
+            let r = FourVector::new(-z0, 0.0, 0.0, z0);
+
+            // finishes partway through a step
+            let t_final = (z0 + 0.37 * SPEED_OF_LIGHT * dt) / SPEED_OF_LIGHT;
+
+            let final_times: Vec<f64> = [10.0, 1000.0].iter()
+                .map(|&u_z| {
+                    let u = FourVector::new(0.0, 0.0, 0.0, -u_z).unitize();
+                    let electron = Particle::create(Species::Electron, r).with_normalized_momentum(u);
+                    let electron = propagate_until_time(laser, electron, t_final, dt, EquationOfMotion::Lorentz);
+                    let t = electron.position()[0] / SPEED_OF_LIGHT;
+                    println!("u_z = {:.0}: final z = {:.6e} m, final t = {:.9e} s, target = {:.9e} s", u_z, electron.position()[3], t, t_final);
+                    t
+                })
+                .collect();
+
+            for t in final_times.iter() {
+                assert!((t - t_final).abs() < 1.0e-3 * dt);
+            }
+            assert!((final_times[0] - final_times[1]).abs() < dt);
+        }
+    }
+
+    /// Counts the number of times the wrapped field's pusher is called
+    struct CountingField<F: Field> {
+        field: F,
+        pushes: std::cell::Cell<usize>,
+    }
+
+    impl<F: Field> Field for CountingField<F> {
+        fn max_timestep(&self) -> Option<f64> {
+            self.field.max_timestep()
+        }
+
+        fn contains(&self, r: FourVector) -> bool {
+            self.field.contains(r)
+        }
+
+        fn ideal_initial_z(&self) -> f64 {
+            self.field.ideal_initial_z()
+        }
+
+        fn push(&self, r: FourVector, u: FourVector, rqm: f64, dt: f64, eqn: EquationOfMotion) -> (FourVector, FourVector, f64, f64) {
+            self.pushes.set(self.pushes.get() + 1);
+            self.field.push(r, u, rqm, dt, eqn)
+        }
+
+        fn fields(&self, r: FourVector) -> (ThreeVector, ThreeVector, f64) {
+            self.field.fields(r)
+        }
+
+        fn energy(&self) -> (f64, &'static str) {
+            self.field.energy()
+        }
+    }
+
+    #[test]
+    fn photon_fast_path() {
+        let laser = FastPlaneWave::new(100.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let laser = CountingField { field: laser, pushes: std::cell::Cell::new(0) };
+        let photon = Particle::create(Species::Photon, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(FourVector::lightlike(0.0, 0.0, -2.0e4));
+
+        // general path: push, then check for pair creation
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (mut r, ell) = (photon.position(), photon.normalized_momentum());
+        let mut expected = 0.0;
+        while laser.contains(r) {
+            r = laser.push(r, ell, 0.0, dt, EquationOfMotion::Lorentz).0;
+            expected += laser.pair_create(r, ell, photon.polarization(), dt, &mut rng, 1.0).0;
+        }
+        let general_pushes = laser.pushes.replace(0);
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let (final_pt, prob) = propagate_photon(&laser, photon, dt, &mut rng);
+        let error = (prob - expected).abs() / expected;
+
+        println!(
+            "pair creation probability = {:.6e}, expected {:.6e}, error = {:.3e}, pushes = {} [general], {} [fast path]",
+            prob, expected, error, general_pushes, laser.pushes.get(),
+        );
+        assert!(expected > 0.0 && error < 1.0e-12);
+        assert!((final_pt.interaction_count() - prob).abs() < 1.0e-12 * prob);
+        assert!(final_pt.position() == r && final_pt.normalized_momentum() == ell);
+        assert!(general_pushes > 0 && laser.pushes.get() == 0);
+
+        // and when propagating without pair creation
+        let final_pt = propagate(&laser, photon, dt, EquationOfMotion::Lorentz, None);
+        assert!(final_pt.position() == r);
+        assert_eq!(laser.pushes.get(), 0);
+    }
+
+    #[test]
+    fn emitted_photons_as_primaries() {
+        let laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u)
+            .with_weight(2.5)
+            .with_id(7);
+
+        // record the emission spectrum by hand, using an identically seeded rng
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let mut events = vec![];
+        for _ in 0..100 {
+            let mut pt = electron;
+            while laser.contains(pt.position()) {
+                let dt_actual = step(&laser, &mut pt, dt, EquationOfMotion::Lorentz);
+                if let Some(event) = laser.radiate(pt.position(), pt.normalized_momentum(), dt_actual, &mut rng, RadiationMode::Quantum, 1.0) {
+                    events.push(event);
+                    pt.with_normalized_momentum(event.u_prime);
+                }
+            }
+        }
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let photons: Vec<Particle> = (0..100)
+            .flat_map(|_| propagate_collecting_photons(&laser, electron, dt, EquationOfMotion::Lorentz, &mut rng, RadiationMode::Quantum).1)
+            .collect();
+
+        // compare binned energy spectra
+        let k_max = events.iter().map(|ev| ev.k[0]).fold(0.0, f64::max);
+        let bin = |k: f64| ((20.0 * k / k_max) as usize).min(19);
+        let mut expected = [0usize; 20];
+        let mut found = [0usize; 20];
+        events.iter().for_each(|ev| expected[bin(ev.k[0])] += 1);
+        photons.iter().for_each(|ph| found[bin(ph.normalized_momentum()[0])] += 1);
+
+        println!("{} events, {} photons, k_max = {:.3e}", events.len(), photons.len(), k_max);
+        println!("expected = {:?}\nfound = {:?}", expected, found);
+        assert!(!photons.is_empty());
+        assert_eq!(expected, found);
+
+        for (ph, ev) in photons.iter().zip(events.iter()) {
+            assert!(ph.species() == Species::Photon);
+            assert!(ph.position() == ev.r && ph.normalized_momentum() == ev.k);
+            assert!(ph.polarization() == ev.pol);
+            assert_eq!(ph.weight(), electron.weight());
+            assert_eq!(ph.parent_id(), electron.id());
+        }
+
+        // and can be injected into a second stage
+        let photon = photons[0];
+        let final_pt = propagate(&laser, photon, dt, EquationOfMotion::Lorentz, None);
+        assert!(!laser.contains(final_pt.position()));
+        assert!(final_pt.normalized_momentum() == photon.normalized_momentum());
+    }
+
+    #[test]
+    fn yield_estimate() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let laser = FastPlaneWave::new(50.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let u = FourVector::new(0.0, 0.0, 0.0, -20000.0).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u);
+
+        let (photons, pairs) = estimate_yield(&laser, electron, dt, EquationOfMotion::ModifiedLandauLifshitz);
+
+        // full Monte Carlo, using the total pair creation probability
+        // of every emitted photon
+        let num = 200;
+        let (mc_photons, mc_pairs) = (0..num)
+            .map(|_| {
+                let (_, emitted) = propagate_collecting_photons(&laser, electron, dt, EquationOfMotion::Lorentz, &mut rng, RadiationMode::Quantum);
+                let pairs: f64 = emitted.iter()
+                    .map(|ph| propagate_photon(&laser, *ph, dt, &mut rng).1)
+                    .sum();
+                (emitted.len() as f64, pairs)
+            })
+            .fold((0.0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+        let (mc_photons, mc_pairs) = (mc_photons / (num as f64), mc_pairs / (num as f64));
+
+        println!("estimated yield = {:.3e} photons, {:.3e} pairs; Monte Carlo = {:.3e} photons, {:.3e} pairs", photons, pairs, mc_photons, mc_pairs);
+        assert!(mc_pairs > 0.0);
+        assert!(photons > 0.5 * mc_photons && photons < 2.0 * mc_photons);
+        assert!(pairs > 0.5 * mc_pairs && pairs < 2.0 * mc_pairs);
+    }
+
+    #[test]
+    fn reflection_off_plane() {
+        let laser = FastPlaneWave::new(0.0, 0.8e-6, 4.0, Polarization::Linear, 0.0, 0.0);
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+
+        // plane at x = 0.1 um, particles allowed at smaller x
+        let x_plane = 0.1e-6;
+        let plane = ReflectingPlane::new(ThreeVector::new(-2.0, 0.0, 0.0), ThreeVector::new(x_plane, 0.0, 0.0));
+
+        let u = FourVector::new(0.0, 5.0, 1.0, -100.0).unitize();
+        let electron = Particle::create(Species::Electron, FourVector::new(-z0, 0.0, 0.0, z0))
+            .with_normalized_momentum(u);
+
+        let without = propagate(&laser, electron, dt, EquationOfMotion::Lorentz, None);
+        assert!(without.position()[1] > x_plane);
+
+        let pt = propagate(&laser, electron, dt, EquationOfMotion::Lorentz, Some(plane));
+        let u_final = pt.normalized_momentum();
+        println!(
+            "u = [{:.6e}, {:.6e}, {:.6e}, {:.6e}] -> [{:.6e}, {:.6e}, {:.6e}, {:.6e}], final x = {:.3e} m",
+            u[0], u[1], u[2], u[3], u_final[0], u_final[1], u_final[2], u_final[3], pt.position()[1],
+        );
+        assert!(pt.position()[1] < x_plane);
+
+        // normal momentum reversed, tangential momentum and energy preserved
+        assert!((u_final[1] + u[1]).abs() < 1.0e-9 * u[0]);
+        assert!((u_final[2] - u[2]).abs() < 1.0e-9 * u[0]);
+        assert!((u_final[3] - u[3]).abs() < 1.0e-9 * u[0]);
+        assert!((u_final[0] - u[0]).abs() < 1.0e-9 * u[0]);
+    }
+}
@@ -0,0 +1,172 @@
+//! Random number generators that record, and replay, the sequence
+//! of random draws, for debugging stochastic processes
+
+use rand::{RngCore, Error};
+
+/// Wraps a random number generator, logging every draw made from it.
+///
+/// Draws are recorded as `u64`s, in the order they are made;
+/// `next_u32` records its (widened) output and `fill_bytes` is
+/// implemented by repeated calls to `next_u64`. The log can be
+/// passed to [ReplayRng] to reproduce exactly the same draws,
+/// e.g. in `radiate` or `pair_create`, independently of the original
+/// generator.
+pub struct RecordingRng<R: RngCore> {
+    inner: R,
+    log: Vec<u64>,
+}
+
+impl<R: RngCore> RecordingRng<R> {
+    #[allow(unused)]
+    pub fn new(inner: R) -> Self {
+        RecordingRng {
+            inner,
+            log: Vec::new(),
+        }
+    }
+
+    /// Returns the draws made so far
+    #[allow(unused)]
+    pub fn log(&self) -> &[u64] {
+        &self.log
+    }
+
+    /// Returns the draws made so far, consuming the generator
+    #[allow(unused)]
+    pub fn into_log(self) -> Vec<u64> {
+        self.log
+    }
+}
+
+impl<R: RngCore> RngCore for RecordingRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        let x = self.inner.next_u32();
+        self.log.push(x as u64);
+        x
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let x = self.inner.next_u64();
+        self.log.push(x);
+        x
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Plays back a sequence of draws recorded by a [RecordingRng].
+///
+/// Panics if more draws are requested than were recorded.
+pub struct ReplayRng {
+    log: Vec<u64>,
+    next: usize,
+}
+
+impl ReplayRng {
+    #[allow(unused)]
+    pub fn new(log: Vec<u64>) -> Self {
+        ReplayRng {
+            log,
+            next: 0,
+        }
+    }
+
+    /// Returns the number of recorded draws not yet used
+    #[allow(unused)]
+    pub fn remaining(&self) -> usize {
+        self.log.len() - self.next
+    }
+
+    fn pop(&mut self) -> u64 {
+        let x = *self.log.get(self.next)
+            .unwrap_or_else(|| panic!("replay exhausted after {} draws", self.log.len()));
+        self.next += 1;
+        x
+    }
+}
+
+impl RngCore for ReplayRng {
+    fn next_u32(&mut self) -> u32 {
+        self.pop() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.pop()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        fill_bytes_via_next_u64(self, dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// Fills `dest` with the little-endian bytes of successive `u64`s
+fn fill_bytes_via_next_u64<R: RngCore>(rng: &mut R, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+        let bytes = rng.next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::prelude::*;
+    use rand_xoshiro::*;
+    use crate::constants::*;
+    use crate::field::*;
+    use crate::geometry::FourVector;
+    use super::*;
+
+    /// Returns the radiation events along the trajectory of an electron
+    fn run<R: Rng>(laser: &Laser, rng: &mut R) -> Vec<RadiationEvent> {
+        let dt = laser.max_timestep().unwrap();
+        let z0 = laser.ideal_initial_z();
+        let mut r = FourVector::new(-z0, 0.0, 0.0, z0);
+        let mut u = FourVector::new(0.0, 0.0, 0.0, -1000.0).unitize();
+        let mut events = vec![];
+        while laser.contains(r) {
+            let (r_new, u_new, dt_actual, _) = laser.push(r, u, ELECTRON_CHARGE / ELECTRON_MASS, dt, EquationOfMotion::Lorentz);
+            r = r_new;
+            u = u_new;
+            if let Some(event) = laser.radiate(r, u, dt_actual, rng, RadiationMode::Quantum, 1.0) {
+                u = event.u_prime;
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    #[test]
+    fn replay_reproduces_emission() {
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+
+        let mut rng = RecordingRng::new(Xoshiro256StarStar::seed_from_u64(0));
+        let original = run(&laser, &mut rng);
+        let log = rng.into_log();
+
+        let mut rng = ReplayRng::new(log.clone());
+        let replayed = run(&laser, &mut rng);
+
+        println!("{} draws, {} events, {} draws unused on replay", log.len(), original.len(), rng.remaining());
+        assert!(!original.is_empty());
+        assert_eq!(original.len(), replayed.len());
+        assert_eq!(rng.remaining(), 0);
+        for (a, b) in original.iter().zip(replayed.iter()) {
+            assert_eq!(a.r, b.r);
+            assert_eq!(a.k, b.k);
+            assert_eq!(a.u_prime, b.u_prime);
+            assert_eq!(a.pol, b.pol);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! A high-level interface for colliding a particle beam with a laser pulse
+
+use std::collections::HashMap;
+use rand::prelude::*;
+use yaml_rust::{Yaml, YamlEmitter, yaml::Hash};
+use crate::constants::*;
+use crate::field::{Field, Laser, EquationOfMotion, RadiationMode};
+use crate::particle::{BeamBuilder, BeamMoments, Particle, Species};
+use crate::propagate;
+
+/// Combines a particle beam, a laser pulse and the choice of dynamics,
+/// so that the whole collision can be run in a single call.
+///
+/// The beam is initialized at the laser's [ideal_initial_z](Field::ideal_initial_z)
+/// (increased by three times its rms length), and each particle is
+/// pushed until it leaves the laser pulse. By default, particles
+/// follow the Lorentz force and do not emit photons.
+/// Further beams, e.g. of a different species, can be added with
+/// [with_beam](Simulation::with_beam).
+pub struct Simulation {
+    beams: Vec<BeamBuilder>,
+    laser: Laser,
+    eqn: EquationOfMotion,
+    species_eqn: HashMap<Species, EquationOfMotion>,
+    mode: Option<RadiationMode>,
+    dt_multiplier: f64,
+}
+
+/// The result of a [Simulation]: the beam particles at the end of the
+/// collision and the photons they emitted.
+#[allow(unused)]
+pub struct SimulationOutput {
+    pub particles: Vec<Particle>,
+    pub photons: Vec<Particle>,
+    /// The number of beam particles that started inside the laser pulse
+    /// and could not be moved outside it (see
+    /// [move_to_field_free_start](propagate::move_to_field_free_start)),
+    /// which were tracked from their initial positions instead.
+    pub unmoved: usize,
+}
+
+impl Simulation {
+    #[allow(unused)]
+    pub fn new(beam: BeamBuilder, laser: Laser) -> Self {
+        Self {
+            beams: vec![beam],
+            laser,
+            eqn: EquationOfMotion::Lorentz,
+            species_eqn: HashMap::new(),
+            mode: None,
+            dt_multiplier: 1.0,
+        }
+    }
+
+    /// Adds another beam, which collides with the laser at the same
+    /// time as the first. Its particles are given ids that follow on
+    /// from those of the beams already added.
+    #[allow(unused)]
+    pub fn with_beam(self, beam: BeamBuilder) -> Self {
+        let mut beams = self.beams;
+        beams.push(beam);
+        Self {
+            beams,
+            ..self
+        }
+    }
+
+    /// Sets the equation of motion used to push charged particles,
+    /// unless overridden for a particular species.
+    #[allow(unused)]
+    pub fn with_equation_of_motion(self, eqn: EquationOfMotion) -> Self {
+        Self {
+            eqn,
+            ..self
+        }
+    }
+
+    /// Sets the equation of motion used to push particles of each of
+    /// the given species, replacing the one set by
+    /// [with_equation_of_motion](Simulation::with_equation_of_motion)
+    /// for those species only.
+    ///
+    /// The equivalent in the input file is `control:equation_of_motion`.
+    #[allow(unused)]
+    pub fn with_equations_of_motion(self, species_eqn: HashMap<Species, EquationOfMotion>) -> Self {
+        Self {
+            species_eqn,
+            ..self
+        }
+    }
+
+    /// Enables photon emission, in the given `mode`.
+    #[allow(unused)]
+    pub fn with_radiation_mode(self, mode: RadiationMode) -> Self {
+        Self {
+            mode: Some(mode),
+            ..self
+        }
+    }
+
+    /// Scales the laser's [max_timestep](Field::max_timestep) by the given factor
+    /// (the default is unity).
+    #[allow(unused)]
+    pub fn with_dt_multiplier(self, dt_multiplier: f64) -> Self {
+        Self {
+            dt_multiplier,
+            ..self
+        }
+    }
+
+    /// Builds the beams and pushes every particle through the laser.
+    /// Emitted photons are given ids that follow on from those of the
+    /// beam particles; they are not themselves tracked.
+    #[allow(unused)]
+    pub fn run<R: Rng>(&self, rng: &mut R) -> SimulationOutput {
+        let primaries = self.build_beams(rng);
+        self.collide(primaries, rng)
+    }
+
+    /// As [run](Simulation::run), but sampling the beams with `beam_rng` and
+    /// the QED processes with `qed_rng`, so that either can be held fixed
+    /// while the other is varied.
+    #[allow(unused)]
+    pub fn run_with_rngs<R: Rng, S: Rng>(&self, beam_rng: &mut R, qed_rng: &mut S) -> SimulationOutput {
+        let primaries = self.build_beams(beam_rng);
+        self.collide(primaries, qed_rng)
+    }
+
+    /// Returns the particles of all the beams, positioned at their
+    /// initial z, and with consecutive ids.
+    #[allow(unused)]
+    pub fn build_beams<R: Rng>(&self, rng: &mut R) -> Vec<Particle> {
+        let laser = &self.laser;
+        let mut primaries: Vec<Particle> = Vec::new();
+        for beam in self.beams.iter() {
+            let initial_z = laser.ideal_initial_z() + 3.0 * beam.sigma_z;
+            let offset = primaries.len() as u64;
+            let beam = beam.with_initial_z(initial_z)
+                .build(rng)
+                .into_iter()
+                .map(|mut pt| {
+                    let id = pt.id() + offset;
+                    pt.with_id(id).with_parent_id(id)
+                });
+            primaries.extend(beam);
+        }
+        primaries
+    }
+
+    /// Returns a short, human-readable summary of the collision, in YAML,
+    /// for the given `output` (see [yaml_summary]).
+    #[allow(unused)]
+    pub fn yaml_summary(&self, output: &SimulationOutput) -> String {
+        let moments = |species: Species| -> BeamMoments {
+            let pt: Vec<Particle> = output.particles.iter()
+                .chain(output.photons.iter())
+                .filter(|pt| pt.species() == species)
+                .copied()
+                .collect();
+            BeamMoments::from_particles(&pt)
+        };
+
+        yaml_summary(
+            &self.laser,
+            &BeamMoments::from_particles(&output.particles),
+            &moments(Species::Electron),
+            &moments(Species::Positron),
+            &moments(Species::Photon),
+        )
+    }
+
+    /// Pushes each of the `primaries` through the laser
+    fn collide<R: Rng>(&self, primaries: Vec<Particle>, rng: &mut R) -> SimulationOutput {
+        let laser = &self.laser;
+        let dt = laser.max_timestep().expect("laser does not specify a maximum timestep") * self.dt_multiplier;
+        let mut current_id = primaries.len() as u64;
+
+        let mut particles = Vec::with_capacity(primaries.len());
+        let mut photons = Vec::new();
+        let mut unmoved = 0;
+
+        for pt in primaries.into_iter() {
+            let pt = propagate::move_to_field_free_start(laser, pt).unwrap_or_else(|| {
+                unmoved += 1;
+                pt
+            });
+            let eqn = self.species_eqn.get(&pt.species()).copied().unwrap_or(self.eqn);
+            let pt = match self.mode {
+                Some(mode) => {
+                    let (pt, emitted) = propagate::propagate_collecting_photons(laser, pt, dt, eqn, rng, mode);
+                    for mut photon in emitted.into_iter() {
+                        photons.push(photon.with_id(current_id));
+                        current_id += 1;
+                    }
+                    pt
+                },
+                None => propagate::propagate(laser, pt, dt, eqn, None),
+            };
+            particles.push(pt);
+        }
+
+        SimulationOutput {
+            particles,
+            photons,
+            unmoved,
+        }
+    }
+}
+
+/// Returns a short, human-readable summary of a collision, in YAML,
+/// that gives the parameters of the `laser`, summarizes the `beam`
+/// particles at the end of the collision, and lists the yields of
+/// `electrons`, `positrons` and `photons`. Energies are given in MeV,
+/// unless stated, and lengths in m; pairs of values are for the x and y planes.
+#[allow(unused)]
+pub fn yaml_summary<F: Field>(laser: &F, beam: &BeamMoments, electrons: &BeamMoments, positrons: &BeamMoments, photons: &BeamMoments) -> String {
+    let real = |x: f64| Yaml::Real(format!("{:e}", x));
+    let pair = |(x, y): (f64, f64)| Yaml::Array(vec![real(x), real(y)]);
+    let key = |s: &str| Yaml::String(s.to_owned());
+
+    let mut params = Hash::new();
+    if let Some(a0) = laser.nominal_a0() {
+        params.insert(key("a0"), real(a0));
+    }
+    if let Some(omega) = laser.carrier_frequency() {
+        params.insert(key("wavelength"), real(2.0 * std::f64::consts::PI * SPEED_OF_LIGHT / omega));
+    }
+    let (energy, unit) = laser.energy();
+    params.insert(key("energy"), real(energy));
+    params.insert(key("energy_unit"), key(unit));
+    if let Some((number, unit)) = laser.photon_number() {
+        params.insert(key("photon_number"), real(number));
+        params.insert(key("photon_number_unit"), key(unit));
+    }
+
+    let summary = beam.summary();
+    let mut final_beam = Hash::new();
+    final_beam.insert(key("particles"), Yaml::Integer(beam.count() as i64));
+    final_beam.insert(key("mean_energy"), real(summary.mean_energy));
+    final_beam.insert(key("rms_energy"), real(summary.rms_energy));
+    final_beam.insert(key("rms_divergence"), pair(summary.rms_divergence));
+    final_beam.insert(key("emittance"), pair(summary.emittance));
+    final_beam.insert(key("normalized_emittance"), pair(beam.normalized_emittance()));
+
+    let mut yields = Hash::new();
+    yields.insert(key("photons"), real(photons.weight()));
+    yields.insert(key("photon_energy"), real(photons.energy()));
+    yields.insert(key("electrons"), real(electrons.weight()));
+    yields.insert(key("positrons"), real(positrons.weight()));
+
+    let mut root = Hash::new();
+    root.insert(key("laser"), Yaml::Hash(params));
+    root.insert(key("beam"), Yaml::Hash(final_beam));
+    root.insert(key("yields"), Yaml::Hash(yields));
+
+    let mut s = String::new();
+    YamlEmitter::new(&mut s).dump(&Yaml::Hash(root)).unwrap();
+    s.push('\n');
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use rand_xoshiro::*;
+    use crate::field::*;
+    use crate::geometry::ThreeVector;
+    use crate::particle::Species;
+    use super::*;
+
+    #[test]
+    fn lp_deflection() {
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let n_cycles = 10.0;
+        let y0 = 2.0e-6;
+        let gamma = (1.0_f64 + 1000.0 * 1000.0).sqrt();
+
+        let beam = BeamBuilder::monoenergetic(Species::Electron, 1, gamma)
+            .with_offset(ThreeVector::new(0.0, y0, 0.0));
+
+        let fast_laser: Laser = FastFocusedLaser::new(100.0, 0.8e-6, 4.0e-6, n_cycles, Polarization::Linear, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .into();
+        let laser: Laser = FocusedLaser::new(100.0, 0.8e-6, 4.0e-6, n_cycles, Polarization::Linear, 0.0)
+            .with_envelope(Envelope::Gaussian)
+            .into();
+
+        let lorentz = Simulation::new(beam, fast_laser).run(&mut rng);
+        let pond = Simulation::new(beam, laser).run(&mut rng);
+
+        assert!(lorentz.photons.is_empty() && pond.photons.is_empty());
+        assert!(lorentz.unmoved == 0 && pond.unmoved == 0);
+
+        let lorentz = lorentz.particles[0].normalized_momentum();
+        let pond = pond.particles[0].normalized_momentum();
+
+        let pond_angle = 1.0e3 * pond[2].atan2(-pond[3]);
+        let lorentz_angle = 1.0e3 * lorentz[2].atan2(-lorentz[3]);
+        let error = ((pond_angle - lorentz_angle) / lorentz_angle).abs();
+
+        println!("angle [PF] = {:.3e}, angle [LF] = {:.3e}, error = {:.3}%, predicted = {:.3e}", pond_angle, lorentz_angle, 100.0 * error, ponderomotive_deflection_coefficient(Envelope::Gaussian));
+        assert!(error < 1.0e-2);
+    }
+
+    #[test]
+    fn species_equations_of_motion() {
+        let gamma = 1000.0;
+        let laser: Laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let electrons = BeamBuilder::monoenergetic(Species::Electron, 1, gamma);
+        let positrons = BeamBuilder::monoenergetic(Species::Positron, 1, gamma);
+
+        let mut species_eqn = HashMap::new();
+        species_eqn.insert(Species::Electron, EquationOfMotion::LandauLifshitz);
+
+        let mut rng = Xoshiro256StarStar::seed_from_u64(0);
+        let output = Simulation::new(electrons, laser)
+            .with_beam(positrons)
+            .with_equations_of_motion(species_eqn)
+            .run(&mut rng);
+
+        // reference, with the Lorentz force only
+        let laser: Laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let reference = Simulation::new(positrons, laser).run(&mut rng);
+
+        let electron = output.particles.iter().find(|pt| pt.species() == Species::Electron).unwrap();
+        let positron = output.particles.iter().find(|pt| pt.species() == Species::Positron).unwrap();
+        let u_e = electron.normalized_momentum();
+        let u_p = positron.normalized_momentum();
+        let u_ref = reference.particles[0].normalized_momentum();
+
+        println!("final gamma = {:.6e} [electron, LL], {:.6e} [positron, Lorentz], {:.6e} [reference]", u_e[0], u_p[0], u_ref[0]);
+        assert_eq!(electron.id(), 0);
+        assert_eq!(positron.id(), 1);
+        assert!(u_e[0] < 0.99 * gamma);
+        assert!((u_p[0] - gamma).abs() < 1.0e-4 * gamma);
+        assert_eq!(u_p, u_ref);
+    }
+
+    #[test]
+    fn independent_seeds() {
+        let laser: Laser = FastPlaneWave::new(20.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0).into();
+        let beam = BeamBuilder::new(Species::Electron, 10)
+            .with_normal_energy_spectrum(2000.0, 100.0)
+            .with_divergence(1.0e-3)
+            .with_normally_distributed_xy(1.0e-6, 1.0e-6);
+        let sim = Simulation::new(beam, laser).with_radiation_mode(RadiationMode::Quantum);
+
+        let run = |beam_seed: u64, qed_seed: u64| {
+            let mut beam_rng = Xoshiro256StarStar::seed_from_u64(beam_seed);
+            let mut qed_rng = Xoshiro256StarStar::seed_from_u64(qed_seed);
+            let initial = sim.build_beams(&mut Xoshiro256StarStar::seed_from_u64(beam_seed));
+            (initial, sim.run_with_rngs(&mut beam_rng, &mut qed_rng))
+        };
+
+        let (initial_a, a) = run(0, 1);
+        let (initial_b, b) = run(0, 2);
+        let (_, c) = run(0, 1);
+
+        let energies = |output: &SimulationOutput| -> Vec<f64> {
+            output.photons.iter().map(|pt| pt.normalized_momentum()[0]).collect()
+        };
+
+        println!("{} and {} photons emitted with the same beam but different QED seeds", a.photons.len(), b.photons.len());
+        for (pa, pb) in initial_a.iter().zip(initial_b.iter()) {
+            assert_eq!(pa.position(), pb.position());
+            assert_eq!(pa.normalized_momentum(), pb.normalized_momentum());
+        }
+        assert!(!a.photons.is_empty());
+        assert!(energies(&a) != energies(&b));
+        assert!(energies(&a) == energies(&c));
+    }
+
+    #[test]
+    fn yaml_summary() {
+        use yaml_rust::YamlLoader;
+
+        let laser: Laser = FastPlaneWave::new(10.0, 0.8e-6, 8.0, Polarization::Linear, 0.0, 0.0)
+            .with_carrier_envelope_phase(0.5 * std::f64::consts::PI)
+            .into();
+        let beam = BeamBuilder::new(Species::Electron, 10)
+            .with_normal_energy_spectrum(2000.0, 100.0)
+            .with_divergence(1.0e-3)
+            .with_normally_distributed_xy(1.0e-6, 1.0e-6);
+        let sim = Simulation::new(beam, laser).with_radiation_mode(RadiationMode::Quantum);
+        let output = sim.run(&mut Xoshiro256StarStar::seed_from_u64(0));
+
+        let s = sim.yaml_summary(&output);
+        println!("{}", s);
+
+        let yaml = YamlLoader::load_from_str(&s).unwrap();
+        let yaml = &yaml[0];
+        let summary = BeamMoments::from_particles(&output.particles).summary();
+        let photon_energy = BeamMoments::from_particles(&output.photons).energy();
+
+        assert_eq!(yaml["laser"]["a0"].as_f64().map(|a0| (a0 - 10.0).abs() < 1.0e-9), Some(true));
+        assert_eq!(yaml["laser"]["wavelength"].as_f64().map(|l| (l - 0.8e-6).abs() < 1.0e-15), Some(true));
+        assert_eq!(yaml["laser"]["energy"].as_f64(), Some(sim.laser.energy().0));
+        assert_eq!(yaml["laser"]["energy_unit"].as_str(), Some("J/m^2"));
+        assert_eq!(yaml["laser"]["photon_number"].as_f64(), sim.laser.photon_number().map(|n| n.0));
+        assert_eq!(yaml["beam"]["particles"].as_i64(), Some(10));
+        assert_eq!(yaml["beam"]["mean_energy"].as_f64(), Some(summary.mean_energy));
+        assert_eq!(yaml["beam"]["emittance"][1].as_f64(), Some(summary.emittance.1));
+        assert_eq!(yaml["yields"]["photons"].as_f64(), Some(output.photons.len() as f64));
+        assert_eq!(yaml["yields"]["photon_energy"].as_f64(), Some(photon_energy));
+        assert_eq!(yaml["yields"]["electrons"].as_f64(), Some(10.0));
+        assert_eq!(yaml["yields"]["positrons"].as_f64(), Some(0.0));
+    }
+}
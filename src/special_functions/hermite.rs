@@ -0,0 +1,56 @@
+//! Evaluates the (physicists') Hermite polynomials, H_n(x).
+
+pub trait Hermite {
+    /// Evaluates the Hermite polynomial of order `n`
+    fn hermite(&self, n: u32) -> Self;
+
+    /// Evaluates the Hermite polynomial of order `n` and its derivative
+    fn hermite_and_deriv(&self, n: u32) -> (Self, Self) where Self: Sized;
+}
+
+impl Hermite for f64 {
+    fn hermite(&self, n: u32) -> Self {
+        self.hermite_and_deriv(n).0
+    }
+
+    fn hermite_and_deriv(&self, n: u32) -> (Self, Self) {
+        // H_{n+1}(x) = 2 x H_n(x) - 2 n H_{n-1}(x),
+        // starting from H_0 = 1 and H_1 = 2 x
+        let x = *self;
+        let mut h_prev = 0.0;
+        let mut h = 1.0;
+        for k in 0..n {
+            let h_next = 2.0 * x * h - 2.0 * (k as f64) * h_prev;
+            h_prev = h;
+            h = h_next;
+        }
+        // H_n'(x) = 2 n H_{n-1}(x)
+        (h, 2.0 * (n as f64) * h_prev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn low_order() {
+        for i in 0..20 {
+            let x = -2.0 + 0.2 * (i as f64);
+            let target = [
+                1.0,
+                2.0 * x,
+                4.0 * x * x - 2.0,
+                8.0 * x.powi(3) - 12.0 * x,
+                16.0 * x.powi(4) - 48.0 * x * x + 12.0,
+            ];
+            for (n, t) in target.iter().enumerate() {
+                let (h, dh) = x.hermite_and_deriv(n as u32);
+                let dh_target = if n > 0 { 2.0 * (n as f64) * target[n - 1] } else { 0.0 };
+                println!("x = {:.2}, H_{}(x) = {:.6e} [{:.6e}], H_{}'(x) = {:.6e} [{:.6e}]", x, n, h, t, n, dh, dh_target);
+                assert!((h - t).abs() < 1.0e-12 * t.abs().max(1.0));
+                assert!((dh - dh_target).abs() < 1.0e-12 * dh_target.abs().max(1.0));
+            }
+        }
+    }
+}
@@ -4,6 +4,7 @@
 mod bessel;
 mod airy;
 mod double_bessel;
+mod hermite;
 
 // Pending removal
 // mod factorial;
@@ -11,6 +12,7 @@ mod double_bessel;
 pub use bessel::*;
 pub use airy::*;
 pub use double_bessel::*;
+pub use hermite::*;
 
 const SERIES_MAX_LENGTH: usize = 20;
 